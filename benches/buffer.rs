@@ -0,0 +1,105 @@
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use dopepad::core::TextBuffer;
+
+fn large_document(lines: usize) -> String {
+    (0..lines)
+        .map(|i| format!("line {i} with some words to render and search"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn bench_line_starts(c: &mut Criterion) {
+    let buffer = TextBuffer::from_text(large_document(200_000), None, false);
+    let top = 100_000;
+    let height = 200;
+
+    c.bench_function("line_starts_per_line_200_rows", |b| {
+        b.iter(|| {
+            (top..top + height)
+                .map(|l| buffer.line_start_char_idx(black_box(l)))
+                .collect::<Vec<_>>()
+        })
+    });
+    c.bench_function("line_starts_batched_200_rows", |b| {
+        b.iter(|| buffer.line_start_char_indices(black_box(top), height))
+    });
+}
+
+fn bench_insert_char(c: &mut Criterion) {
+    c.bench_function("insert_char_10k", |b| {
+        b.iter(|| {
+            let mut buffer = TextBuffer::new(None, false);
+            for _ in 0..10_000 {
+                buffer.insert_char(black_box('x'));
+            }
+            buffer
+        })
+    });
+}
+
+fn bench_find_matches(c: &mut Criterion) {
+    let buffer = TextBuffer::from_text(large_document(50_000), None, false);
+    c.bench_function("find_matches_50k_lines", |b| {
+        b.iter(|| buffer.find_matches(black_box("line 4999")))
+    });
+}
+
+fn bench_move_down(c: &mut Criterion) {
+    let buffer = TextBuffer::from_text(large_document(50_000), None, false);
+    c.bench_function("move_down_50k_lines", |b| {
+        b.iter_batched(
+            || buffer.clone(),
+            |mut buffer| {
+                for _ in 0..50_000 {
+                    buffer.move_down();
+                }
+                buffer
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_word_motion(c: &mut Criterion) {
+    let buffer = TextBuffer::from_text(large_document(5_000), None, false);
+    c.bench_function("move_word_right_cold_5k_lines", |b| {
+        b.iter_batched(
+            || buffer.clone(),
+            |mut buffer| {
+                for _ in 0..40_000 {
+                    buffer.move_word_right();
+                }
+                buffer
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+
+    let mut warm = buffer.clone();
+    for _ in 0..40_000 {
+        warm.move_word_right();
+    }
+    warm.goto_line(1);
+    c.bench_function("move_word_right_warm_5k_lines", |b| {
+        b.iter_batched(
+            || warm.clone(),
+            |mut buffer| {
+                for _ in 0..40_000 {
+                    buffer.move_word_right();
+                }
+                buffer
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_line_starts,
+    bench_insert_char,
+    bench_find_matches,
+    bench_move_down,
+    bench_word_motion
+);
+criterion_main!(benches);
@@ -0,0 +1,150 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Single-line text field with a caret, used by the overlay inputs.
+///
+/// The caret is a char index into the text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineInput {
+    text: String,
+    caret: usize,
+}
+
+impl LineInput {
+    pub fn new(text: impl Into<String>) -> Self {
+        let text = text.into();
+        let caret = text.chars().count();
+        Self { text, caret }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn caret(&self) -> usize {
+        self.caret
+    }
+
+    pub fn insert(&mut self, c: char) {
+        let at = self.byte_index(self.caret);
+        self.text.insert(at, c);
+        self.caret += 1;
+    }
+
+    /// Inserts pasted text at the caret, dropping line breaks and other
+    /// control chars as well as anything `accept` rejects.
+    pub fn insert_str(&mut self, text: &str, accept: impl Fn(char) -> bool) -> bool {
+        let mut changed = false;
+        for c in text.chars().filter(|&c| !c.is_control() && accept(c)) {
+            self.insert(c);
+            changed = true;
+        }
+        changed
+    }
+
+    pub fn backspace(&mut self) -> bool {
+        if self.caret == 0 {
+            return false;
+        }
+        self.caret -= 1;
+        let at = self.byte_index(self.caret);
+        self.text.remove(at);
+        true
+    }
+
+    pub fn delete(&mut self) -> bool {
+        if self.caret >= self.text.chars().count() {
+            return false;
+        }
+        let at = self.byte_index(self.caret);
+        self.text.remove(at);
+        true
+    }
+
+    /// Applies an editing or caret key. Typed chars are only inserted when
+    /// `accept` allows them. Returns `true` if the text changed.
+    pub fn handle_key(&mut self, key: &KeyEvent, accept: impl Fn(char) -> bool) -> bool {
+        match key.code {
+            KeyCode::Left => self.caret = self.caret.saturating_sub(1),
+            KeyCode::Right => self.caret = (self.caret + 1).min(self.text.chars().count()),
+            KeyCode::Home => self.caret = 0,
+            KeyCode::End => self.caret = self.text.chars().count(),
+            KeyCode::Backspace => return self.backspace(),
+            KeyCode::Delete => return self.delete(),
+            KeyCode::Char(c)
+                if !key.modifiers.contains(KeyModifiers::CONTROL)
+                    && !key.modifiers.contains(KeyModifiers::ALT)
+                    && accept(c) =>
+            {
+                self.insert(c);
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn byte_index(&self, caret: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(caret)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+}
+
+impl From<&str> for LineInput {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::LineInput;
+
+    fn press(input: &mut LineInput, code: KeyCode) -> bool {
+        input.handle_key(&KeyEvent::new(code, KeyModifiers::NONE), |_| true)
+    }
+
+    #[test]
+    fn caret_moves_and_edits_mid_string() {
+        let mut input = LineInput::new("nöte");
+        assert_eq!(input.caret(), 4);
+
+        press(&mut input, KeyCode::Left);
+        press(&mut input, KeyCode::Left);
+        assert!(press(&mut input, KeyCode::Backspace));
+        assert_eq!(input.as_str(), "nte");
+        assert!(press(&mut input, KeyCode::Char('o')));
+        assert_eq!(input.as_str(), "note");
+        assert!(press(&mut input, KeyCode::Delete));
+        assert_eq!(input.as_str(), "noe");
+
+        press(&mut input, KeyCode::Home);
+        assert!(!press(&mut input, KeyCode::Backspace));
+        press(&mut input, KeyCode::End);
+        assert!(!press(&mut input, KeyCode::Delete));
+        assert_eq!(input.caret(), 3);
+    }
+
+    #[test]
+    fn pasted_text_is_inserted_at_the_caret_on_one_line() {
+        let mut input = LineInput::new("a.txt");
+        input.handle_key(&KeyEvent::new(KeyCode::Home, KeyModifiers::NONE), |_| true);
+        assert!(input.insert_str("x/y\r\nz_", |c| c != '/'));
+        assert_eq!(input.as_str(), "xyz_a.txt");
+        assert_eq!(input.caret(), 4);
+        assert!(!input.insert_str("\n", |_| true));
+    }
+
+    #[test]
+    fn rejected_and_control_chars_are_ignored() {
+        let mut input = LineInput::default();
+        let slash = KeyEvent::new(KeyCode::Char('/'), KeyModifiers::NONE);
+        let ctrl_a = KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL);
+        assert!(!input.handle_key(&slash, |c| c != '/'));
+        assert!(!input.handle_key(&ctrl_a, |_| true));
+        assert_eq!(input.as_str(), "");
+    }
+}
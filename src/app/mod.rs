@@ -1,26 +1,44 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::env;
 use std::fs;
-use std::io;
-use std::io::ErrorKind;
-use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::{Context, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
 use clap::Parser;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use crossterm::execute;
 use crossterm::terminal::{
     EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
 };
+use encoding_rs::{Encoding, UTF_8};
 use ratatui::Terminal;
-use ratatui::backend::CrosstermBackend;
+use ratatui::backend::{Backend, CrosstermBackend};
+use serde::{Deserialize, Serialize};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM, SIGWINCH};
+use signal_hook::iterator::Signals;
 
-use crate::core::{Command, SearchState, TextBuffer};
-use crate::input::map_key_event;
+mod line_input;
+
+pub use line_input::LineInput;
+
+use crate::core::{
+    Command, Cursor, Dictionary, OutlineItem, SearchState, TextBuffer, apply_command,
+};
+use crate::input::{Keymap, map_key_event, map_key_for};
 use crate::io::{
-    EolStyle, load_document, load_sidecar, save_document, save_sidecar, sidecar_path_for,
+    EolStyle, IoError, NoteMeta, SidecarSnapshot, SidecarStorage, load_colors, load_document,
+    load_meta, remove_colors, restore_sidecar, save_colors, save_document_chunks, save_meta,
+    snapshot_sidecar,
 };
-use crate::ui::{UiModel, draw};
+use crate::ui::{UiModel, draw, help_max_scroll, is_markdown_path};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "DopePad - TUI Notepad")]
@@ -31,6 +49,211 @@ struct Cli {
     readonly: bool,
     #[arg(long)]
     no_style: bool,
+    #[arg(long)]
+    center_search: bool,
+    #[arg(long)]
+    sidecar_index: bool,
+    #[arg(long)]
+    append: bool,
+    #[arg(long)]
+    no_mouse: bool,
+    #[arg(long, conflicts_with = "keymap")]
+    modal: bool,
+    #[arg(long)]
+    clock: bool,
+    #[arg(long, value_name = "NAME")]
+    category: Option<String>,
+    #[arg(long)]
+    indent_guides: bool,
+    #[arg(long)]
+    trailing_whitespace: bool,
+    #[arg(long, value_name = "COL", value_parser = clap::value_parser!(u16).range(1..))]
+    ruler: Option<u16>,
+    #[arg(long)]
+    auto_pairs: bool,
+    #[arg(long)]
+    smart_quotes: bool,
+    #[arg(long, value_name = "WORDLIST")]
+    spellcheck: Option<PathBuf>,
+    #[arg(long, value_name = "PRESET", value_parser = parse_keymap, default_value = "default")]
+    keymap: Keymap,
+    #[arg(
+        long,
+        value_name = "EXT",
+        value_delimiter = ',',
+        value_parser = parse_extension,
+        default_values = DEFAULT_EXTENSIONS
+    )]
+    extensions: Vec<String>,
+    #[arg(long, value_name = "LABEL", value_parser = parse_encoding)]
+    encoding: Option<&'static Encoding>,
+    #[arg(long, value_name = "FORMAT", value_parser = parse_time_format)]
+    new_template: Option<String>,
+    #[arg(long, value_name = "FORMAT", value_parser = parse_time_format, default_value = DEFAULT_TIME_FORMAT)]
+    time_format: String,
+}
+
+const JOURNAL_CATEGORY: &str = "journal";
+const HELP_PAGE: usize = 10;
+const CATEGORY_ORDER_FILE: &str = ".order";
+const UI_PREFS_FILE: &str = ".ui.json";
+const PERSONAL_DICTIONARY_FILE: &str = ".dictionary";
+const DEFAULT_EXTENSIONS: [&str; 2] = ["txt", "md"];
+const DRAFTS_DIR: &str = ".drafts";
+const DRAFT_STEM: &str = "untitled";
+const NOTE_DRAFTS_DIR: &str = "notes";
+const EXTERNAL_DRAFTS_DIR: &str = "other";
+const DRAFT_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+const MAX_REPEAT_COUNT: usize = 9999;
+const RULE_WIDTH: usize = 80;
+const SAVED_MESSAGE_DURATION: Duration = Duration::from_secs(2);
+const STATUS_TICK: Duration = Duration::from_secs(1);
+const IDLE_POLL: Duration = Duration::from_secs(60);
+const FILE_MANAGER: &str = if cfg!(target_os = "macos") {
+    "open"
+} else if cfg!(windows) {
+    "explorer"
+} else {
+    "xdg-open"
+};
+
+/// Resolves Goto input: a 1-based line number or a percentage of the
+/// document such as `50%`.
+fn goto_target(input: &str, line_count: usize) -> Option<usize> {
+    match input.strip_suffix('%') {
+        Some(percent) => {
+            let percent = percent.parse::<usize>().ok()?.min(100);
+            Some((line_count * percent / 100).max(1))
+        }
+        None => input.parse().ok(),
+    }
+}
+
+fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("unknown encoding: {label}"))
+}
+
+fn parse_keymap(name: &str) -> Result<Keymap, String> {
+    Keymap::from_name(name).ok_or_else(|| format!("unknown keymap: {name} (default, vim, emacs)"))
+}
+
+fn parse_extension(ext: &str) -> Result<String, String> {
+    let ext = ext.trim().trim_start_matches('.');
+    if ext.is_empty() || ext.contains(['/', '\\']) {
+        return Err(format!("invalid extension: {ext}"));
+    }
+    Ok(ext.to_string())
+}
+
+fn is_file_name_char(c: char) -> bool {
+    c != '/' && c != '\\'
+}
+
+/// Runs `command` through the platform shell with `input` on stdin. A final
+/// line break is added for the command and taken off its output when `input`
+/// had none, so line-based tools like `sort` keep the note's ending.
+fn run_filter(command: &str, input: &str) -> Result<String, String> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut child = process::Command::new(shell)
+        .args([flag, command])
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Cannot run `{command}`: {e}"))?;
+
+    let open_ended = !input.is_empty() && !input.ends_with('\n');
+    let mut stdin_text = input.to_string();
+    if open_ended {
+        stdin_text.push('\n');
+    }
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    let writer = thread::spawn(move || stdin.write_all(stdin_text.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Cannot run `{command}`: {e}"))?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let status = match output.status.code() {
+            Some(code) => format!("exited with status {code}"),
+            None => "was terminated".to_string(),
+        };
+        return Err(if stderr.trim().is_empty() {
+            format!("`{command}` {status}")
+        } else {
+            format!("`{command}` {status}:\n{}", stderr.trim_end())
+        });
+    }
+    let mut text = String::from_utf8(output.stdout)
+        .map_err(|_| format!("`{command}` printed text that is not valid UTF-8"))?;
+    if open_ended && text.ends_with('\n') {
+        text.pop();
+    }
+    Ok(text)
+}
+
+/// Comma-separated tags, trimmed, without a leading `#`, empty and repeated
+/// entries dropped.
+fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(',') {
+        let tag = tag.trim().trim_start_matches('#').trim();
+        if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// Digits, then at most one trailing `%`.
+fn push_goto_char(input: &mut String, c: char) {
+    let accepted = if c == '%' {
+        !input.is_empty() && !input.ends_with('%')
+    } else {
+        c.is_ascii_digit() && !input.ends_with('%')
+    };
+    if accepted {
+        input.push(c);
+    }
+}
+
+fn has_note_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension().is_some_and(|e| {
+        let e = e.to_string_lossy();
+        extensions.iter().any(|ext| ext.eq_ignore_ascii_case(&e))
+    })
+}
+
+fn note_file_name(filename: &str, extensions: &[String]) -> String {
+    let name = filename.trim();
+    if has_note_extension(Path::new(name), extensions) {
+        return name.to_string();
+    }
+    let ext = extensions.first().map(String::as_str).unwrap_or("txt");
+    format!("{name}.{ext}")
+}
+
+fn parse_time_format(format: &str) -> Result<String, String> {
+    let format = format.replace("\\n", "\n");
+    if StrftimeItems::new(&format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("invalid time format: {format}"));
+    }
+    Ok(format)
+}
+
+fn render_note_template(template: &str, file_name: &str, now: &DateTime<Local>) -> String {
+    template
+        .split("{name}")
+        .map(|part| now.format(part).to_string())
+        .collect::<Vec<_>>()
+        .join(file_name)
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -42,21 +265,34 @@ pub enum AppMode {
 #[derive(Debug, Clone)]
 pub enum Overlay {
     None,
-    Help,
+    Help {
+        scroll: usize,
+    },
     Search {
-        input: String,
+        input: LineInput,
         state: SearchState,
     },
     Goto {
         input: String,
     },
+    CommandLine {
+        input: LineInput,
+    },
+    Outline {
+        items: Vec<OutlineItem>,
+        selected: usize,
+    },
+    NoteMeta {
+        path: PathBuf,
+        title: LineInput,
+        tags: LineInput,
+        tags_focus: bool,
+    },
     SaveAs {
-        filename: String,
-        category_index: usize,
+        form: NoteForm,
     },
     NewFile {
-        filename: String,
-        category_index: usize,
+        form: NoteForm,
     },
     NewCategory {
         name: String,
@@ -65,24 +301,61 @@ pub enum Overlay {
     ConfirmUnsaved {
         file_name: String,
         pending: PendingAction,
-        choice: ConfirmChoice,
+        choice: UnsavedChoice,
     },
     ConfirmDelete {
         file_name: String,
         path: PathBuf,
         choice: ConfirmChoice,
     },
+    ConfirmOverwrite {
+        form: NoteForm,
+        path: PathBuf,
+        choice: ConfirmChoice,
+    },
+    ConfirmCategory {
+        name: String,
+        origin: Box<Overlay>,
+        choice: ConfirmChoice,
+    },
+    RecoverDraft {
+        path: PathBuf,
+        note: Option<String>,
+        choice: ConfirmChoice,
+    },
+    FileInfo {
+        file_name: String,
+        size: u64,
+        modified: Option<SystemTime>,
+        lines: usize,
+        estimated: bool,
+    },
     Error {
         message: String,
     },
 }
 
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UiPrefs {
+    #[serde(default)]
+    tree_focus: bool,
+    #[serde(default)]
+    tree_sort: TreeSort,
+    #[serde(default)]
+    tree_group: TreeGroup,
+    #[serde(default)]
+    markdown_preview: bool,
+    #[serde(default)]
+    overwrite: bool,
+}
+
 #[derive(Debug, Clone)]
 pub enum PendingAction {
     Quit,
     OpenPath(PathBuf),
     OpenNewFileOverlay { preferred_category: Option<usize> },
     DeletePath(PathBuf),
+    OpenJournal,
 }
 
 #[derive(Debug, Clone)]
@@ -92,12 +365,205 @@ pub enum PostCategoryAction {
     OpenNewFile { preferred_category: Option<usize> },
 }
 
+/// File name plus target category, shared by the Save As and New file overlays.
+///
+/// The category comes from the Up/Down picker unless a name is typed into
+/// the category field (Tab switches fields and completes names there).
+#[derive(Debug, Clone)]
+pub struct NoteForm {
+    pub filename: LineInput,
+    pub category: LineInput,
+    pub category_index: usize,
+    pub category_focus: bool,
+    completion: Option<String>,
+}
+
+impl NoteForm {
+    pub fn new(filename: impl Into<String>, category_index: usize) -> Self {
+        Self {
+            filename: LineInput::new(filename),
+            category: LineInput::default(),
+            category_index,
+            category_focus: false,
+            completion: None,
+        }
+    }
+
+    fn category_name<'a>(&'a self, categories: &'a [String]) -> Option<&'a str> {
+        let typed = self.category.as_str().trim();
+        if typed.is_empty() {
+            categories.get(self.category_index).map(String::as_str)
+        } else {
+            Some(typed)
+        }
+    }
+
+    fn missing_category(&self, categories: &[String]) -> Option<String> {
+        let typed = self.category.as_str().trim();
+        (!typed.is_empty() && !categories.iter().any(|c| c == typed)).then(|| typed.to_string())
+    }
+
+    fn sync_category(&mut self, categories: &[String]) {
+        let typed = self.category.as_str().trim();
+        if let Some(index) = categories.iter().position(|c| c == typed) {
+            self.category_index = index;
+        }
+    }
+
+    fn handle_key(&mut self, key: &KeyEvent, categories: &[String]) {
+        match key.code {
+            KeyCode::Up | KeyCode::Down if !categories.is_empty() => {
+                let len = categories.len();
+                self.category_index = if key.code == KeyCode::Up {
+                    (self.category_index + len - 1) % len
+                } else {
+                    (self.category_index + 1) % len
+                };
+                self.category = LineInput::default();
+                self.completion = None;
+            }
+            KeyCode::Tab if !self.category_focus => self.category_focus = true,
+            KeyCode::Tab => {
+                if !self.complete_category(categories) {
+                    self.category_focus = false;
+                }
+            }
+            KeyCode::BackTab => self.category_focus = !self.category_focus,
+            _ if self.category_focus => {
+                if self.category.handle_key(key, is_file_name_char) {
+                    self.completion = None;
+                    self.sync_category(categories);
+                }
+            }
+            _ => {
+                self.filename.handle_key(key, is_file_name_char);
+            }
+        }
+    }
+
+    fn paste(&mut self, text: &str, categories: &[String]) {
+        if self.category_focus {
+            if self.category.insert_str(text, is_file_name_char) {
+                self.completion = None;
+                self.sync_category(categories);
+            }
+        } else {
+            self.filename.insert_str(text, is_file_name_char);
+        }
+    }
+
+    /// Extends the typed category to the longest common prefix of the
+    /// matching names, then cycles through them on repeated Tabs.
+    fn complete_category(&mut self, categories: &[String]) -> bool {
+        let typed = self.category.as_str().to_string();
+        let prefix = self.completion.clone().unwrap_or_else(|| typed.clone());
+        if prefix.is_empty() {
+            return false;
+        }
+        let matches: Vec<&String> = categories
+            .iter()
+            .filter(|c| c.starts_with(&prefix))
+            .collect();
+        let Some(first) = matches.first() else {
+            return false;
+        };
+        if matches.len() == 1 && **first == typed {
+            return false;
+        }
+        let common = matches.iter().fold(first.as_str(), |acc, name| {
+            let len = acc
+                .char_indices()
+                .zip(name.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(acc.len().min(name.len()), |((i, _), _)| i);
+            &acc[..len]
+        });
+        let completed = if self.completion.is_none() && common.len() > typed.len() {
+            common.to_string()
+        } else {
+            let current = matches.iter().position(|name| **name == typed);
+            self.completion = Some(prefix);
+            matches[current.map_or(0, |i| (i + 1) % matches.len())].clone()
+        };
+        self.category = LineInput::new(completed);
+        self.sync_category(categories);
+        true
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConfirmChoice {
     Yes,
     No,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsavedChoice {
+    Save,
+    Discard,
+    Cancel,
+}
+
+impl UnsavedChoice {
+    pub const ALL: [UnsavedChoice; 3] = [Self::Save, Self::Discard, Self::Cancel];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Save => "Save",
+            Self::Discard => "Discard",
+            Self::Cancel => "Cancel",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Self::Save => Self::Discard,
+            Self::Discard => Self::Cancel,
+            Self::Cancel => Self::Save,
+        }
+    }
+
+    fn prev(self) -> Self {
+        match self {
+            Self::Save => Self::Cancel,
+            Self::Discard => Self::Save,
+            Self::Cancel => Self::Discard,
+        }
+    }
+
+    fn from_key(c: char) -> Option<Self> {
+        match c.to_ascii_lowercase() {
+            's' | 'y' => Some(Self::Save),
+            'd' | 'n' => Some(Self::Discard),
+            'c' => Some(Self::Cancel),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameIssue {
+    Empty,
+    Exists,
+    Overwrites,
+    NewCategory,
+}
+
+impl NameIssue {
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::Empty => "File name cannot be empty",
+            Self::Exists => "A file with this name already exists",
+            Self::Overwrites => "File exists; Enter asks to overwrite it",
+            Self::NewCategory => "New category; Enter asks to create it",
+        }
+    }
+
+    pub fn blocks_enter(self) -> bool {
+        matches!(self, Self::Empty | Self::Exists)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum TreeNodeKind {
     Category,
@@ -118,6 +584,10 @@ pub struct FileTree {
     pub nodes: Vec<TreeNode>,
     pub selected: usize,
     pub focus: bool,
+    pub filter: String,
+    pub filtering: bool,
+    pub sort: TreeSort,
+    pub group: TreeGroup,
 }
 
 impl FileTree {
@@ -126,6 +596,35 @@ impl FileTree {
             nodes: Vec::new(),
             selected: 0,
             focus: false,
+            filter: String::new(),
+            filtering: false,
+            sort: TreeSort::Name,
+            group: TreeGroup::Category,
+        }
+    }
+
+    pub fn is_visible(&self, node: &TreeNode) -> bool {
+        if self.filter.is_empty() || !matches!(node.kind, TreeNodeKind::File) {
+            return true;
+        }
+        let needle = self.filter.to_lowercase();
+        node.label.to_lowercase().contains(&needle)
+            || node
+                .path
+                .as_ref()
+                .and_then(|p| p.file_name())
+                .is_some_and(|n| n.to_string_lossy().to_lowercase().contains(&needle))
+    }
+
+    fn is_selectable(&self, idx: usize) -> bool {
+        let node = &self.nodes[idx];
+        matches!(node.kind, TreeNodeKind::File) && self.is_visible(node)
+    }
+
+    fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+        if self.selected >= self.nodes.len() || !self.is_selectable(self.selected) {
+            self.select_first_file();
         }
     }
 
@@ -144,7 +643,7 @@ impl FileTree {
             .nodes
             .iter()
             .enumerate()
-            .find(|(_, n)| matches!(n.kind, TreeNodeKind::File))
+            .find(|(_, n)| matches!(n.kind, TreeNodeKind::File) && self.is_visible(n))
         {
             self.selected = idx;
         } else {
@@ -165,7 +664,7 @@ impl FileTree {
             } else {
                 (idx + 1) % len
             };
-            if matches!(self.nodes[idx].kind, TreeNodeKind::File) {
+            if self.is_selectable(idx) {
                 self.selected = idx;
                 break;
             }
@@ -173,22 +672,229 @@ impl FileTree {
     }
 }
 
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(150);
+const SEARCH_DEBOUNCE_MIN_LINES: usize = 5_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeSort {
+    #[default]
+    Name,
+    Modified,
+}
+
+impl TreeSort {
+    fn toggled(self) -> Self {
+        match self {
+            Self::Name => Self::Modified,
+            Self::Modified => Self::Name,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Modified => "modified",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeGroup {
+    #[default]
+    Category,
+    Tag,
+}
+
+impl TreeGroup {
+    fn toggled(self) -> Self {
+        match self {
+            Self::Category => Self::Tag,
+            Self::Tag => Self::Category,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Category => "category",
+            Self::Tag => "tag",
+        }
+    }
+}
+
+const LINE_COUNT_SAMPLE_BYTES: u64 = 1 << 20;
+
+fn remove_draft(path: &Path) {
+    if path.exists() {
+        let _ = fs::remove_file(path);
+        let _ = remove_colors(path, SidecarStorage::PerFile);
+    }
+}
+
+fn estimate_line_count(path: &Path, size: u64) -> io::Result<(usize, bool)> {
+    let mut sample = Vec::new();
+    fs::File::open(path)?
+        .take(LINE_COUNT_SAMPLE_BYTES)
+        .read_to_end(&mut sample)?;
+    let newlines = sample.iter().filter(|&&b| b == b'\n').count();
+    let ends_open = sample.last().is_some_and(|&b| b != b'\n');
+    if size <= LINE_COUNT_SAMPLE_BYTES {
+        return Ok((newlines + usize::from(ends_open), false));
+    }
+    let estimate = (newlines as f64 * size as f64 / sample.len() as f64).round() as usize;
+    Ok((estimate, true))
+}
+
+fn sort_by_modified_desc(files: &mut [NoteEntry]) {
+    files.sort_by_cached_key(|entry| {
+        let modified = fs::metadata(&entry.path)
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        (std::cmp::Reverse(modified), entry.path.clone())
+    });
+}
+
+#[derive(Debug, Clone)]
+struct DeletedNote {
+    path: PathBuf,
+    bytes: Vec<u8>,
+    sidecar: Option<SidecarSnapshot>,
+}
+
+#[derive(Debug, Clone)]
+struct NoteEntry {
+    path: PathBuf,
+    meta: NoteMeta,
+}
+
+impl NoteEntry {
+    fn label(&self) -> String {
+        match &self.meta.title {
+            Some(title) => title.clone(),
+            None => self
+                .path
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_else(|| "sem_nome.txt".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DirListing {
+    modified: SystemTime,
+    files: Vec<NoteEntry>,
+}
+
+/// Notes in `dir` with their sidecar metadata, cached until the directory
+/// changes. Metadata edits made here drop the entry themselves.
+fn cached_note_listing(
+    cache: &mut HashMap<PathBuf, DirListing>,
+    dir: &Path,
+    extensions: &[String],
+    storage: SidecarStorage,
+) -> Vec<NoteEntry> {
+    let modified = fs::metadata(dir).and_then(|m| m.modified()).ok();
+    if let Some(modified) = modified
+        && let Some(listing) = cache.get(dir)
+        && listing.modified == modified
+    {
+        return listing.files.clone();
+    }
+
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()
+        .into_iter()
+        .flat_map(|it| it.filter_map(|e| e.ok()))
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && has_note_extension(p, extensions))
+        .collect();
+    files.sort();
+    let files: Vec<NoteEntry> = files
+        .into_iter()
+        .map(|path| {
+            let meta = load_meta(&path, storage).unwrap_or_default();
+            NoteEntry { path, meta }
+        })
+        .collect();
+
+    match modified {
+        Some(modified) => {
+            cache.insert(
+                dir.to_path_buf(),
+                DirListing {
+                    modified,
+                    files: files.clone(),
+                },
+            );
+        }
+        None => {
+            cache.remove(dir);
+        }
+    }
+    files
+}
+
 pub struct App {
     pub buffer: TextBuffer,
     pub overlay: Overlay,
     pub mode: AppMode,
     pub eol: EolStyle,
+    pub encoding: &'static Encoding,
+    pub forced_encoding: Option<&'static Encoding>,
+    pub bom: bool,
     pub running: bool,
     pub needs_redraw: bool,
     pub no_style: bool,
+    pub sidecar_storage: SidecarStorage,
     pub notes_root: PathBuf,
     pub file_tree: FileTree,
     pub pending_after_save: Option<PendingAction>,
     pub categories: Vec<String>,
+    pub center_search: bool,
+    pub status_message: Option<String>,
+    pub new_template: Option<String>,
+    pub time_format: String,
+    pub extensions: Vec<String>,
+    pub readonly_locked: bool,
+    pub keymap: Keymap,
+    pub normal_mode: bool,
+    pub clock: bool,
+    pub indent_guides: bool,
+    pub spell: Option<Dictionary>,
+    pub markdown_preview: bool,
+    pub trailing_whitespace: bool,
+    pub ruler: Option<usize>,
+    pub note_meta: NoteMeta,
+    pub screen_height: u16,
+    pub pending_errors: VecDeque<String>,
+    draft_generation: Option<u64>,
+    draft_saved_at: Option<Instant>,
+    untitled_draft: Option<PathBuf>,
+    last_deleted: Option<DeletedNote>,
+    last_category_index: Option<usize>,
+    pending_delete: bool,
+    pending_count: Option<usize>,
+    last_edit: Option<Command>,
+    recording: Option<Vec<Command>>,
+    last_macro: Vec<Command>,
+    status_expires_at: Option<Instant>,
+    last_tick: Option<Instant>,
+    shutdown: Arc<AtomicBool>,
+    file_manager: &'static str,
+    dir_cache: HashMap<PathBuf, DirListing>,
+    search_pending_since: Option<Instant>,
 }
 
 impl App {
-    fn new(mut buffer: TextBuffer, eol: EolStyle, no_style: bool, notes_root: PathBuf) -> Self {
+    pub fn new(
+        mut buffer: TextBuffer,
+        eol: EolStyle,
+        encoding: &'static Encoding,
+        no_style: bool,
+        notes_root: PathBuf,
+    ) -> Self {
         let mode = if buffer.readonly {
             AppMode::ReadOnly
         } else {
@@ -201,25 +907,73 @@ impl App {
             overlay: Overlay::None,
             mode,
             eol,
+            encoding,
+            forced_encoding: None,
+            bom: false,
             running: true,
             needs_redraw: true,
             no_style,
+            sidecar_storage: SidecarStorage::PerFile,
             notes_root,
             file_tree: FileTree::new(),
             pending_after_save: None,
             categories: Vec::new(),
+            center_search: false,
+            status_message: None,
+            new_template: None,
+            time_format: DEFAULT_TIME_FORMAT.to_string(),
+            extensions: DEFAULT_EXTENSIONS.map(String::from).to_vec(),
+            readonly_locked: false,
+            keymap: Keymap::Default,
+            normal_mode: false,
+            clock: false,
+            indent_guides: false,
+            spell: None,
+            markdown_preview: false,
+            trailing_whitespace: false,
+            ruler: None,
+            note_meta: NoteMeta::default(),
+            screen_height: 24,
+            pending_errors: VecDeque::new(),
+            draft_generation: None,
+            draft_saved_at: None,
+            untitled_draft: None,
+            last_deleted: None,
+            last_category_index: None,
+            pending_delete: false,
+            pending_count: None,
+            last_edit: None,
+            recording: None,
+            last_macro: Vec::new(),
+            status_expires_at: None,
+            last_tick: None,
+            file_manager: FILE_MANAGER,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            dir_cache: HashMap::new(),
+            search_pending_since: None,
         };
         app.refresh_tree();
         app
     }
 
     fn open_error(&mut self, msg: impl Into<String>) {
-        self.overlay = Overlay::Error {
-            message: msg.into(),
-        };
+        if matches!(self.overlay, Overlay::Error { .. }) {
+            self.pending_errors.push_back(msg.into());
+        } else {
+            self.overlay = Overlay::Error {
+                message: msg.into(),
+            };
+        }
         self.needs_redraw = true;
     }
 
+    fn next_error_overlay(&mut self) -> Overlay {
+        match self.pending_errors.pop_front() {
+            Some(message) => Overlay::Error { message },
+            None => Overlay::None,
+        }
+    }
+
     fn current_file_name(&self) -> String {
         self.buffer
             .path
@@ -233,7 +987,7 @@ impl App {
         self.overlay = Overlay::ConfirmUnsaved {
             file_name: self.current_file_name(),
             pending,
-            choice: ConfirmChoice::Yes,
+            choice: UnsavedChoice::Save,
         };
         self.needs_redraw = true;
     }
@@ -241,9 +995,44 @@ impl App {
     fn refresh_tree(&mut self) {
         let selected_before = self.file_tree.selected_path();
         self.refresh_categories();
-        let mut nodes = Vec::new();
+        let mut listings = Vec::with_capacity(self.categories.len());
+        for category in &self.categories {
+            let dir = self.notes_root.join(category);
+            let mut files = cached_note_listing(
+                &mut self.dir_cache,
+                &dir,
+                &self.extensions,
+                self.sidecar_storage,
+            );
+            if self.file_tree.sort == TreeSort::Modified {
+                sort_by_modified_desc(&mut files);
+            }
+            listings.push(files);
+        }
+
+        self.file_tree.nodes = match self.file_tree.group {
+            TreeGroup::Category => self.category_nodes(listings),
+            TreeGroup::Tag => self.tag_nodes(listings),
+        };
+
+        if let Some(prev_path) = selected_before
+            && let Some((idx, _)) = self
+                .file_tree
+                .nodes
+                .iter()
+                .enumerate()
+                .find(|(_, n)| n.path.as_ref() == Some(&prev_path))
+        {
+            self.file_tree.selected = idx;
+            return;
+        }
+        self.file_tree.select_first_file();
+    }
 
-        for (category_index, category) in self.categories.iter().enumerate() {
+    fn category_nodes(&self, listings: Vec<Vec<NoteEntry>>) -> Vec<TreeNode> {
+        let mut nodes = Vec::new();
+        for (category_index, (category, files)) in self.categories.iter().zip(listings).enumerate()
+        {
             nodes.push(TreeNode {
                 label: format!("[{category}]"),
                 kind: TreeNodeKind::Category,
@@ -251,16 +1040,6 @@ impl App {
                 category_index: Some(category_index),
             });
 
-            let dir = self.notes_root.join(category);
-            let mut files: Vec<PathBuf> = fs::read_dir(&dir)
-                .ok()
-                .into_iter()
-                .flat_map(|it| it.filter_map(|e| e.ok()))
-                .map(|e| e.path())
-                .filter(|p| p.is_file() && p.extension().map(|e| e == "txt").unwrap_or(false))
-                .collect();
-            files.sort();
-
             if files.is_empty() {
                 nodes.push(TreeNode {
                     label: "  (empty)".to_string(),
@@ -269,39 +1048,134 @@ impl App {
                     category_index: Some(category_index),
                 });
             } else {
-                for path in files {
-                    let file_name = path
-                        .file_name()
-                        .map(|f| f.to_string_lossy().to_string())
-                        .unwrap_or_else(|| "sem_nome.txt".to_string());
+                for entry in files {
                     nodes.push(TreeNode {
-                        label: format!("  {file_name}"),
+                        label: format!("  {}", entry.label()),
                         kind: TreeNodeKind::File,
-                        path: Some(path),
+                        path: Some(entry.path),
                         category_index: Some(category_index),
                     });
                 }
             }
         }
+        nodes
+    }
 
-        self.file_tree.nodes = nodes;
-
-        if let Some(prev_path) = selected_before {
-            if let Some((idx, _)) = self
-                .file_tree
-                .nodes
-                .iter()
-                .enumerate()
-                .find(|(_, n)| n.path.as_ref() == Some(&prev_path))
-            {
-                self.file_tree.selected = idx;
-                return;
+    /// One heading per tag (sorted), then untagged notes; a note with several
+    /// tags is listed under each of them.
+    fn tag_nodes(&self, listings: Vec<Vec<NoteEntry>>) -> Vec<TreeNode> {
+        let mut by_tag: BTreeMap<String, Vec<TreeNode>> = BTreeMap::new();
+        let mut untagged = Vec::new();
+        for (category_index, (category, files)) in self.categories.iter().zip(listings).enumerate()
+        {
+            for entry in files {
+                let node = TreeNode {
+                    label: format!("  {category}/{}", entry.label()),
+                    kind: TreeNodeKind::File,
+                    path: Some(entry.path.clone()),
+                    category_index: Some(category_index),
+                };
+                for tag in &entry.meta.tags {
+                    by_tag.entry(tag.clone()).or_default().push(node.clone());
+                }
+                if entry.meta.tags.is_empty() {
+                    untagged.push(node);
+                }
             }
         }
-        self.file_tree.select_first_file();
-    }
 
-    fn refresh_categories(&mut self) {
+        let heading = |label: String| TreeNode {
+            label,
+            kind: TreeNodeKind::Category,
+            path: None,
+            category_index: None,
+        };
+        let mut nodes = Vec::new();
+        for (tag, files) in by_tag {
+            nodes.push(heading(format!("[#{tag}]")));
+            nodes.extend(files);
+        }
+        if !untagged.is_empty() {
+            nodes.push(heading("[no tag]".to_string()));
+            nodes.extend(untagged);
+        }
+        nodes
+    }
+
+    /// Restores the tree, preview and overwrite settings from the last
+    /// session; a missing or unreadable prefs file keeps the defaults. Tree
+    /// focus is left alone when `restore_focus` is false, e.g. when a file
+    /// was named on the command line.
+    fn load_ui_prefs(&mut self, restore_focus: bool) {
+        let Ok(raw) = fs::read_to_string(self.notes_root.join(UI_PREFS_FILE)) else {
+            return;
+        };
+        let Ok(prefs) = serde_json::from_str::<UiPrefs>(&raw) else {
+            return;
+        };
+        self.file_tree.sort = prefs.tree_sort;
+        self.file_tree.group = prefs.tree_group;
+        if restore_focus {
+            self.file_tree.focus = prefs.tree_focus;
+        }
+        self.markdown_preview = prefs.markdown_preview;
+        self.buffer.overwrite = prefs.overwrite;
+        self.force_refresh_tree();
+    }
+
+    fn save_ui_prefs(&self) -> Result<()> {
+        let prefs = UiPrefs {
+            tree_focus: self.file_tree.focus,
+            tree_sort: self.file_tree.sort,
+            tree_group: self.file_tree.group,
+            markdown_preview: self.markdown_preview,
+            overwrite: self.buffer.overwrite,
+        };
+        let path = self.notes_root.join(UI_PREFS_FILE);
+        let raw = serde_json::to_string_pretty(&prefs).context("serializing UI prefs")?;
+        fs::write(&path, raw).with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Focuses the tree on `name` (exact match first, then ignoring case),
+    /// selecting its first note or the category row when it has none. The tag
+    /// view has no category rows, so this switches back to the category view.
+    fn focus_category(&mut self, name: &str) -> bool {
+        let Some(index) = self.categories.iter().position(|c| c == name).or_else(|| {
+            self.categories
+                .iter()
+                .position(|c| c.eq_ignore_ascii_case(name))
+        }) else {
+            return false;
+        };
+        if self.file_tree.group == TreeGroup::Tag {
+            self.file_tree.group = TreeGroup::Category;
+            self.refresh_tree();
+        }
+        let rows = || {
+            self.file_tree
+                .nodes
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| n.category_index == Some(index))
+        };
+        let selected = rows()
+            .find(|(_, n)| matches!(n.kind, TreeNodeKind::File))
+            .or_else(|| rows().next())
+            .map(|(i, _)| i);
+        if let Some(selected) = selected {
+            self.file_tree.selected = selected;
+        }
+        self.file_tree.focus = true;
+        self.last_category_index = Some(index);
+        true
+    }
+
+    fn force_refresh_tree(&mut self) {
+        self.dir_cache.clear();
+        self.refresh_tree();
+    }
+
+    fn refresh_categories(&mut self) {
         let mut categories: Vec<String> = fs::read_dir(&self.notes_root)
             .ok()
             .into_iter()
@@ -314,18 +1188,62 @@ impl App {
                     None
                 }
             })
+            .filter(|name| !name.starts_with('.'))
             .collect();
         categories.sort_by_key(|s| s.to_lowercase());
-        self.categories = categories;
+
+        let order =
+            fs::read_to_string(self.notes_root.join(CATEGORY_ORDER_FILE)).unwrap_or_default();
+        let mut ordered = Vec::with_capacity(categories.len());
+        for name in order.lines().map(str::trim) {
+            if let Some(pos) = categories.iter().position(|c| c == name) {
+                ordered.push(categories.remove(pos));
+            }
+        }
+        ordered.append(&mut categories);
+        self.categories = ordered;
+    }
+
+    fn move_selected_category(&mut self, direction: isize) -> Result<()> {
+        let Some(from) = self.file_tree.selected_category_index() else {
+            return Ok(());
+        };
+        let Some(to) = from
+            .checked_add_signed(direction)
+            .filter(|&to| to < self.categories.len())
+        else {
+            return Ok(());
+        };
+        let mut order = self.categories.clone();
+        order.swap(from, to);
+        let path = self.notes_root.join(CATEGORY_ORDER_FILE);
+        let mut contents = order.join("\n");
+        contents.push('\n');
+        fs::write(&path, contents).with_context(|| format!("writing {}", path.display()))?;
+        self.refresh_tree();
+        if self.file_tree.selected_category_index() != Some(to)
+            && let Some(idx) = self
+                .file_tree
+                .nodes
+                .iter()
+                .position(|n| n.category_index == Some(to))
+        {
+            self.file_tree.selected = idx;
+        }
+        Ok(())
     }
 
-    fn handle_key(&mut self, key: KeyEvent) {
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        if key.kind == KeyEventKind::Release {
+            return;
+        }
+        self.clear_status_message();
         if key.modifiers.contains(KeyModifiers::CONTROL)
             && key.modifiers.contains(KeyModifiers::SHIFT)
             && matches!(key.code, KeyCode::Char('S') | KeyCode::Char('s'))
         {
             if self.buffer.readonly {
-                self.open_error("Readonly mode: save disabled");
+                self.notify_readonly();
             } else {
                 self.open_save_as_overlay();
             }
@@ -333,6 +1251,11 @@ impl App {
             return;
         }
 
+        if matches!(map_key_event(key, false), Some(Command::ForceQuit)) {
+            self.apply_command(Command::ForceQuit);
+            return;
+        }
+
         if !matches!(self.overlay, Overlay::None) {
             self.handle_overlay_key(key);
             return;
@@ -343,9 +1266,87 @@ impl App {
             return;
         }
 
-        if let Some(cmd) = map_key_event(key, false) {
-            self.apply_command(cmd);
+        if self.normal_mode
+            && key.modifiers.is_empty()
+            && let KeyCode::Char(c) = key.code
+            && let Some(digit) = c.to_digit(10)
+            && (digit != 0 || self.pending_count.is_some())
+        {
+            let count = self.pending_count.unwrap_or(0) * 10 + digit as usize;
+            let count = count.min(MAX_REPEAT_COUNT);
+            self.pending_count = Some(count);
+            self.status_message = Some(format!("Count: {count}"));
+            self.needs_redraw = true;
+            return;
+        }
+        let count = self.pending_count.take().unwrap_or(1);
+        if self.normal_mode && key.code == KeyCode::Char('d') && key.modifiers.is_empty() {
+            self.pending_delete = !self.pending_delete;
+            if !self.pending_delete {
+                self.apply_command(Command::DeleteLine);
+            }
+            return;
+        }
+        self.pending_delete = false;
+        if let Some(cmd) = map_key_for(self.keymap, self.normal_mode, key, false) {
+            let times =
+                if cmd.is_motion() || matches!(cmd, Command::RepeatLastEdit | Command::PlayMacro) {
+                    count
+                } else {
+                    1
+                };
+            for _ in 0..times {
+                self.apply_command(cmd.clone());
+            }
+        }
+        if self.buffer.append_only() && matches!(self.overlay, Overlay::None) {
+            self.buffer.move_to_document_end();
+        }
+    }
+
+    pub fn handle_paste(&mut self, text: &str) {
+        self.clear_status_message();
+        let mut overlay = std::mem::replace(&mut self.overlay, Overlay::None);
+        match &mut overlay {
+            Overlay::None if !self.file_tree.focus => {
+                self.buffer.at_each_cursor(|b| b.insert_str(text));
+            }
+            Overlay::Search { input, state } => {
+                let changed = input.insert_str(text, |_| true);
+                if changed {
+                    self.schedule_search(input.as_str(), state);
+                }
+            }
+            Overlay::SaveAs { form } | Overlay::NewFile { form } => {
+                form.paste(text, &self.categories);
+            }
+            Overlay::Goto { input } => {
+                for c in text.chars() {
+                    push_goto_char(input, c);
+                }
+            }
+            Overlay::CommandLine { input } => {
+                input.insert_str(text, |_| true);
+            }
+            Overlay::NoteMeta {
+                title,
+                tags,
+                tags_focus,
+                ..
+            } => {
+                let input = if *tags_focus { tags } else { title };
+                input.insert_str(text, |_| true);
+            }
+            Overlay::NewCategory { name, .. } => name.extend(
+                text.chars()
+                    .filter(|&c| !c.is_control() && is_file_name_char(c)),
+            ),
+            _ => {}
         }
+        if matches!(self.overlay, Overlay::None) {
+            self.overlay = overlay;
+        }
+        self.needs_redraw = true;
     }
 
     fn open_save_as_overlay(&mut self) {
@@ -376,12 +1377,12 @@ impl App {
             .path
             .as_ref()
             .and_then(|p| self.category_index_for_path(p))
+            .or(self.last_category_index)
             .unwrap_or(0)
             .min(self.categories.len().saturating_sub(1));
 
         self.overlay = Overlay::SaveAs {
-            filename,
-            category_index,
+            form: NoteForm::new(filename, category_index),
         };
     }
 
@@ -400,11 +1401,11 @@ impl App {
                     .as_ref()
                     .and_then(|p| self.category_index_for_path(p))
             })
+            .or(self.last_category_index)
             .unwrap_or(0)
             .min(self.categories.len().saturating_sub(1));
         self.overlay = Overlay::NewFile {
-            filename: "new_note.txt".to_string(),
-            category_index,
+            form: NoteForm::new("new_note.txt", category_index),
         };
     }
 
@@ -416,6 +1417,114 @@ impl App {
         self.needs_redraw = true;
     }
 
+    /// Loads the word list behind `--spellcheck` and the personal words kept
+    /// in the notes root.
+    fn load_spellcheck(&mut self, word_list: &Path) -> Result<()> {
+        let raw = fs::read(word_list)
+            .with_context(|| format!("reading word list {}", word_list.display()))?;
+        let mut dictionary = Dictionary::from_word_list(&String::from_utf8_lossy(&raw));
+        if let Ok(personal) = fs::read_to_string(self.notes_root.join(PERSONAL_DICTIONARY_FILE)) {
+            dictionary.set_personal_words(&personal);
+        }
+        self.spell = Some(dictionary);
+        Ok(())
+    }
+
+    fn add_word_to_dictionary(&mut self) -> Result<()> {
+        let Some(dictionary) = self.spell.as_mut() else {
+            self.status_message = Some("Spellcheck is off (start with --spellcheck)".to_string());
+            return Ok(());
+        };
+        let Some(word) = self.buffer.word_at_cursor() else {
+            self.status_message = Some("No word under cursor".to_string());
+            return Ok(());
+        };
+        if !dictionary.add_personal(&word) {
+            self.status_message = Some(format!("\"{word}\" is already in the dictionary"));
+            return Ok(());
+        }
+        let path = self.notes_root.join(PERSONAL_DICTIONARY_FILE);
+        fs::write(&path, dictionary.personal_list())
+            .with_context(|| format!("writing {}", path.display()))?;
+        self.status_message = Some(format!("Added \"{word}\" to the dictionary"));
+        Ok(())
+    }
+
+    /// Hands `dir` to the system file manager without waiting for it; only a
+    /// launch failure is reported.
+    fn open_in_file_manager(&mut self, dir: &Path) {
+        let spawned = process::Command::new(self.file_manager)
+            .arg(dir)
+            .stdin(process::Stdio::null())
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::null())
+            .spawn();
+        match spawned {
+            Ok(mut child) => {
+                thread::spawn(move || child.wait());
+                self.status_message = Some(format!("Opened {}", dir.display()));
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => self.open_error(format!(
+                "Cannot open {}: `{}` was not found",
+                dir.display(),
+                self.file_manager
+            )),
+            Err(e) => self.open_error(format!(
+                "Cannot open {} with `{}`: {e}",
+                dir.display(),
+                self.file_manager
+            )),
+        }
+    }
+
+    fn open_note_meta(&mut self, path: PathBuf) {
+        let meta = if self.buffer.path.as_ref() == Some(&path) {
+            self.note_meta.clone()
+        } else {
+            load_meta(&path, self.sidecar_storage).unwrap_or_default()
+        };
+        self.overlay = Overlay::NoteMeta {
+            path,
+            title: LineInput::new(meta.title.unwrap_or_default()),
+            tags: LineInput::new(meta.tags.join(", ")),
+            tags_focus: false,
+        };
+    }
+
+    fn write_note_meta(&mut self, path: &Path, meta: NoteMeta) -> Result<()> {
+        save_meta(path, &meta, self.sidecar_storage)
+            .with_context(|| format!("saving title/tags for {}", path.display()))?;
+        if let Some(dir) = path.parent() {
+            self.dir_cache.remove(dir);
+        }
+        if self.buffer.path.as_deref() == Some(path) {
+            self.note_meta = meta;
+        }
+        self.refresh_tree();
+        Ok(())
+    }
+
+    fn open_file_info(&mut self, path: &Path) {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                self.open_error(format!("Cannot read file info: {e}"));
+                return;
+            }
+        };
+        let (lines, estimated) = estimate_line_count(path, metadata.len()).unwrap_or((0, true));
+        self.overlay = Overlay::FileInfo {
+            file_name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            lines,
+            estimated,
+        };
+    }
+
     fn open_delete_confirmation(&mut self, path: PathBuf) {
         let file_name = path
             .file_name()
@@ -437,13 +1546,53 @@ impl App {
     }
 
     fn handle_tree_key(&mut self, key: KeyEvent) {
+        if self.file_tree.filtering {
+            let no_ctrl = !key.modifiers.contains(KeyModifiers::CONTROL)
+                && !key.modifiers.contains(KeyModifiers::ALT);
+            match key.code {
+                KeyCode::Char(c) if no_ctrl => {
+                    let mut filter = std::mem::take(&mut self.file_tree.filter);
+                    filter.push(c);
+                    self.file_tree.set_filter(filter);
+                    self.needs_redraw = true;
+                    return;
+                }
+                KeyCode::Backspace => {
+                    let mut filter = std::mem::take(&mut self.file_tree.filter);
+                    filter.pop();
+                    self.file_tree.set_filter(filter);
+                    self.needs_redraw = true;
+                    return;
+                }
+                KeyCode::Enter => self.file_tree.filtering = false,
+                _ => {}
+            }
+        }
+        let plain = key.modifiers.difference(KeyModifiers::SHIFT).is_empty();
         match key.code {
+            KeyCode::Esc if self.file_tree.filtering || !self.file_tree.filter.is_empty() => {
+                self.file_tree.filtering = false;
+                self.file_tree.set_filter(String::new());
+            }
             KeyCode::Esc => self.file_tree.focus = false,
+            KeyCode::Char('/') => self.file_tree.filtering = true,
+            KeyCode::Up | KeyCode::Down if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                if self.file_tree.group == TreeGroup::Tag {
+                    self.status_message =
+                        Some("Categories can be reordered in the category view".to_string());
+                    self.needs_redraw = true;
+                    return;
+                }
+                let direction = if key.code == KeyCode::Up { -1 } else { 1 };
+                if let Err(e) = self.move_selected_category(direction) {
+                    self.open_error(format!("Reorder failed: {e:#}"));
+                }
+            }
             KeyCode::Up => self.file_tree.move_selection(-1),
             KeyCode::Down => self.file_tree.move_selection(1),
             KeyCode::Char('n') | KeyCode::Char('N') => {
                 if self.buffer.readonly {
-                    self.open_error("Readonly mode: cannot create files");
+                    self.notify_readonly();
                     return;
                 }
                 if self.buffer.dirty {
@@ -454,9 +1603,44 @@ impl App {
                 }
                 self.open_new_file_overlay(self.file_tree.selected_category_index());
             }
-            KeyCode::Char('c') | KeyCode::Char('C') => {
+            KeyCode::Char('c') | KeyCode::Char('C') if plain => {
                 self.open_new_category_overlay(PostCategoryAction::None);
             }
+            KeyCode::Char('r') | KeyCode::Char('R') if plain => self.force_refresh_tree(),
+            KeyCode::Char('t') | KeyCode::Char('T') if plain => {
+                if let Some(path) = self.file_tree.selected_path() {
+                    self.open_note_meta(path);
+                }
+            }
+            KeyCode::Char('o') | KeyCode::Char('O') if plain => {
+                let dir = match (
+                    self.file_tree.selected_path(),
+                    self.file_tree.selected_category_index(),
+                ) {
+                    (Some(path), _) => path.parent().map(Path::to_path_buf),
+                    (None, Some(index)) => {
+                        self.categories.get(index).map(|c| self.notes_root.join(c))
+                    }
+                    (None, None) => None,
+                }
+                .unwrap_or_else(|| self.notes_root.clone());
+                self.open_in_file_manager(&dir);
+            }
+            KeyCode::Char('i') | KeyCode::Char('I') if plain => {
+                if let Some(path) = self.file_tree.selected_path() {
+                    self.open_file_info(&path);
+                }
+            }
+            KeyCode::Char('s') | KeyCode::Char('S') if plain => {
+                self.file_tree.sort = self.file_tree.sort.toggled();
+                self.refresh_tree();
+                self.status_message = Some(format!("Sorted by {}", self.file_tree.sort.label()));
+            }
+            KeyCode::Char('g') | KeyCode::Char('G') if plain => {
+                self.file_tree.group = self.file_tree.group.toggled();
+                self.refresh_tree();
+                self.status_message = Some(format!("Grouped by {}", self.file_tree.group.label()));
+            }
             KeyCode::Enter => {
                 if self.buffer.dirty {
                     if let Some(path) = self.file_tree.selected_path() {
@@ -464,15 +1648,15 @@ impl App {
                     }
                     return;
                 }
-                if let Some(path) = self.file_tree.selected_path() {
-                    if let Err(e) = self.open_document(&path) {
-                        self.open_error(format!("Failed to open file: {e:#}"));
-                    }
+                if let Some(path) = self.file_tree.selected_path()
+                    && let Err(e) = self.open_document(&path)
+                {
+                    self.open_error(format!("Failed to open file: {e:#}"));
                 }
             }
-            KeyCode::Delete | KeyCode::Char('d') | KeyCode::Char('D') => {
+            KeyCode::Delete | KeyCode::Char('d') | KeyCode::Char('D') if plain => {
                 if self.buffer.readonly {
-                    self.open_error("Readonly mode: cannot delete files");
+                    self.notify_readonly();
                     return;
                 }
                 if let Some(path) = self.file_tree.selected_path() {
@@ -488,6 +1672,19 @@ impl App {
             {
                 self.file_tree.focus = false;
             }
+            KeyCode::Char('z') | KeyCode::Char('Z')
+                if key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                if let Err(e) = self.undo_delete() {
+                    self.open_error(format!("Undo delete failed: {e:#}"));
+                }
+            }
+            _ if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                if let Some(cmd) = map_key_for(self.keymap, false, key, false) {
+                    self.apply_command(cmd);
+                }
+                return;
+            }
             _ => {}
         }
         self.needs_redraw = true;
@@ -498,14 +1695,61 @@ impl App {
         let mut next = current;
 
         match next {
-            Overlay::Help => {
+            Overlay::Help { scroll } => {
+                let last = help_max_scroll(self.screen_height, self.keymap);
+                next = match key.code {
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::F(1) => Overlay::None,
+                    KeyCode::Up => Overlay::Help {
+                        scroll: scroll.saturating_sub(1),
+                    },
+                    KeyCode::Down => Overlay::Help {
+                        scroll: (scroll + 1).min(last),
+                    },
+                    KeyCode::PageUp => Overlay::Help {
+                        scroll: scroll.saturating_sub(HELP_PAGE),
+                    },
+                    KeyCode::PageDown => Overlay::Help {
+                        scroll: (scroll + HELP_PAGE).min(last),
+                    },
+                    KeyCode::Home => Overlay::Help { scroll: 0 },
+                    _ => Overlay::Help { scroll },
+                };
+            }
+            Overlay::FileInfo { .. } => {
                 if matches!(key.code, KeyCode::Esc | KeyCode::Enter) {
                     next = Overlay::None;
                 }
             }
+            Overlay::RecoverDraft {
+                path,
+                note,
+                mut choice,
+            } => match key.code {
+                KeyCode::Esc => next = Overlay::None,
+                KeyCode::Left | KeyCode::Up | KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    choice = ConfirmChoice::Yes;
+                    next = Overlay::RecoverDraft { path, note, choice };
+                }
+                KeyCode::Right | KeyCode::Down | KeyCode::Char('n') | KeyCode::Char('N') => {
+                    choice = ConfirmChoice::No;
+                    next = Overlay::RecoverDraft { path, note, choice };
+                }
+                KeyCode::Enter => {
+                    if choice == ConfirmChoice::Yes {
+                        if let Err(e) = self.recover_draft(&path) {
+                            self.open_error(format!("Draft recovery failed: {e:#}"));
+                            return;
+                        }
+                    } else {
+                        remove_draft(&path);
+                    }
+                    next = Overlay::None;
+                }
+                _ => next = Overlay::RecoverDraft { path, note, choice },
+            },
             Overlay::Error { .. } => {
                 if matches!(key.code, KeyCode::Esc | KeyCode::Enter) {
-                    next = Overlay::None;
+                    next = self.next_error_overlay();
                 }
             }
             Overlay::ConfirmUnsaved {
@@ -514,32 +1758,24 @@ impl App {
                 mut choice,
             } => match key.code {
                 KeyCode::Esc => next = Overlay::None,
-                KeyCode::Left | KeyCode::Up => {
-                    choice = ConfirmChoice::Yes;
-                    next = Overlay::ConfirmUnsaved {
-                        file_name,
-                        pending,
-                        choice,
-                    };
-                }
-                KeyCode::Right | KeyCode::Down => {
-                    choice = ConfirmChoice::No;
+                KeyCode::Left | KeyCode::Up | KeyCode::BackTab => {
+                    choice = choice.prev();
                     next = Overlay::ConfirmUnsaved {
                         file_name,
                         pending,
                         choice,
                     };
                 }
-                KeyCode::Char('y') | KeyCode::Char('Y') => {
-                    choice = ConfirmChoice::Yes;
+                KeyCode::Right | KeyCode::Down | KeyCode::Tab => {
+                    choice = choice.next();
                     next = Overlay::ConfirmUnsaved {
                         file_name,
                         pending,
                         choice,
                     };
                 }
-                KeyCode::Char('n') | KeyCode::Char('N') => {
-                    choice = ConfirmChoice::No;
+                KeyCode::Char(c) if UnsavedChoice::from_key(c).is_some() => {
+                    choice = UnsavedChoice::from_key(c).unwrap_or(choice);
                     next = Overlay::ConfirmUnsaved {
                         file_name,
                         pending,
@@ -547,19 +1783,24 @@ impl App {
                     };
                 }
                 KeyCode::Enter => {
-                    if choice == ConfirmChoice::Yes {
-                        if let Some(path) = self.buffer.path.clone() {
-                            if let Err(e) = self.persist_to_path(&path) {
-                                self.open_error(format!("Save failed: {e:#}"));
+                    match choice {
+                        UnsavedChoice::Save => {
+                            if let Some(path) = self.buffer.path.clone() {
+                                if let Err(e) = self.persist_to_path(&path) {
+                                    self.open_error(format!("Save failed: {e:#}"));
+                                    return;
+                                }
+                                self.execute_pending_action(pending);
+                            } else {
+                                self.open_save_as_overlay_with_pending(Some(pending));
                                 return;
                             }
+                        }
+                        UnsavedChoice::Discard => {
+                            self.discard_draft();
                             self.execute_pending_action(pending);
-                        } else {
-                            self.open_save_as_overlay_with_pending(Some(pending));
-                            return;
                         }
-                    } else {
-                        self.execute_pending_action(pending);
+                        UnsavedChoice::Cancel => {}
                     }
                     next = Overlay::None;
                 }
@@ -610,11 +1851,11 @@ impl App {
                     };
                 }
                 KeyCode::Enter => {
-                    if choice == ConfirmChoice::Yes {
-                        if let Err(e) = self.delete_note_path(&path) {
-                            self.open_error(format!("Delete failed: {e:#}"));
-                            return;
-                        }
+                    if choice == ConfirmChoice::Yes
+                        && let Err(e) = self.delete_note_path(&path)
+                    {
+                        self.open_error(format!("Delete failed: {e:#}"));
+                        return;
                     }
                     next = Overlay::None;
                 }
@@ -626,6 +1867,83 @@ impl App {
                     }
                 }
             },
+            Overlay::ConfirmOverwrite {
+                form,
+                path,
+                mut choice,
+            } => match key.code {
+                KeyCode::Esc => next = Overlay::SaveAs { form },
+                KeyCode::Left | KeyCode::Up | KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    choice = ConfirmChoice::Yes;
+                    next = Overlay::ConfirmOverwrite { form, path, choice };
+                }
+                KeyCode::Right | KeyCode::Down | KeyCode::Char('n') | KeyCode::Char('N') => {
+                    choice = ConfirmChoice::No;
+                    next = Overlay::ConfirmOverwrite { form, path, choice };
+                }
+                KeyCode::Enter => {
+                    if choice == ConfirmChoice::Yes {
+                        if let Err(e) = self.save_as_to(&path) {
+                            self.open_error(format!("Save As failed: {e:#}"));
+                            return;
+                        }
+                        next = Overlay::None;
+                    } else {
+                        next = Overlay::SaveAs { form };
+                    }
+                }
+                _ => next = Overlay::ConfirmOverwrite { form, path, choice },
+            },
+            Overlay::ConfirmCategory {
+                name,
+                origin,
+                mut choice,
+            } => match key.code {
+                KeyCode::Esc => next = *origin,
+                KeyCode::Left | KeyCode::Up | KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    choice = ConfirmChoice::Yes;
+                    next = Overlay::ConfirmCategory {
+                        name,
+                        origin,
+                        choice,
+                    };
+                }
+                KeyCode::Right | KeyCode::Down | KeyCode::Char('n') | KeyCode::Char('N') => {
+                    choice = ConfirmChoice::No;
+                    next = Overlay::ConfirmCategory {
+                        name,
+                        origin,
+                        choice,
+                    };
+                }
+                KeyCode::Enter => {
+                    let mut origin = *origin;
+                    if choice == ConfirmChoice::No {
+                        next = origin;
+                    } else {
+                        let tree_focus = self.file_tree.focus;
+                        let created = self.create_category(&name);
+                        self.file_tree.focus = tree_focus;
+                        if let Err(e) = created {
+                            self.open_error(format!("Category creation failed: {e:#}"));
+                            return;
+                        }
+                        if let Overlay::SaveAs { form } | Overlay::NewFile { form } = &mut origin {
+                            form.sync_category(&self.categories);
+                        }
+                        self.overlay = origin;
+                        self.handle_overlay_key(key);
+                        return;
+                    }
+                }
+                _ => {
+                    next = Overlay::ConfirmCategory {
+                        name,
+                        origin,
+                        choice,
+                    }
+                }
+            },
             Overlay::NewCategory {
                 mut name,
                 next: next_action,
@@ -664,8 +1982,7 @@ impl App {
                 KeyCode::Char(c)
                     if !key.modifiers.contains(KeyModifiers::CONTROL)
                         && !key.modifiers.contains(KeyModifiers::ALT)
-                        && c != '/'
-                        && c != '\\' =>
+                        && is_file_name_char(c) =>
                 {
                     name.push(c);
                     next = Overlay::NewCategory {
@@ -687,154 +2004,174 @@ impl App {
                     next = Overlay::Goto { input };
                 }
                 KeyCode::Enter => {
-                    if let Ok(n) = input.parse::<usize>() {
+                    if let Some(n) = goto_target(&input, self.buffer.line_count()) {
                         self.buffer.goto_line(n);
                     }
                     next = Overlay::None;
                 }
-                KeyCode::Char(c) if c.is_ascii_digit() => {
-                    input.push(c);
+                KeyCode::Char(c) => {
+                    push_goto_char(&mut input, c);
                     next = Overlay::Goto { input };
                 }
                 _ => next = Overlay::Goto { input },
             },
-            Overlay::SaveAs {
-                mut filename,
-                mut category_index,
+            Overlay::NoteMeta {
+                path,
+                mut title,
+                mut tags,
+                mut tags_focus,
             } => match key.code {
                 KeyCode::Esc => next = Overlay::None,
-                KeyCode::Up => {
-                    if !self.categories.is_empty() {
-                        if category_index == 0 {
-                            category_index = self.categories.len() - 1;
-                        } else {
-                            category_index -= 1;
-                        }
-                    }
-                    next = Overlay::SaveAs {
-                        filename,
-                        category_index,
+                KeyCode::Enter => {
+                    let title = title.as_str().trim();
+                    let meta = NoteMeta {
+                        title: (!title.is_empty()).then(|| title.to_string()),
+                        tags: parse_tags(tags.as_str()),
                     };
-                }
-                KeyCode::Down => {
-                    if !self.categories.is_empty() {
-                        category_index = (category_index + 1) % self.categories.len();
+                    next = Overlay::None;
+                    if let Err(e) = self.write_note_meta(&path, meta) {
+                        self.open_error(format!("{e:#}"));
                     }
-                    next = Overlay::SaveAs {
-                        filename,
-                        category_index,
-                    };
                 }
-                KeyCode::Backspace => {
-                    filename.pop();
-                    next = Overlay::SaveAs {
-                        filename,
-                        category_index,
+                _ => {
+                    match key.code {
+                        KeyCode::Tab | KeyCode::BackTab | KeyCode::Up | KeyCode::Down => {
+                            tags_focus = !tags_focus;
+                        }
+                        _ if tags_focus => {
+                            tags.handle_key(&key, |_| true);
+                        }
+                        _ => {
+                            title.handle_key(&key, |_| true);
+                        }
+                    }
+                    next = Overlay::NoteMeta {
+                        path,
+                        title,
+                        tags,
+                        tags_focus,
                     };
                 }
-                KeyCode::Enter => {
-                    if filename.trim().is_empty() {
-                        self.open_error("File name cannot be empty");
-                        return;
-                    }
-                    if let Err(e) = self.save_to_category(&filename, category_index) {
-                        self.open_error(format!("Save As failed: {e:#}"));
-                        return;
-                    }
-                    if let Some(pending) = self.pending_after_save.take() {
-                        self.execute_pending_action(pending);
+            },
+            Overlay::Outline { items, selected } => {
+                let last = items.len().saturating_sub(1);
+                let selected = match key.code {
+                    KeyCode::Up => selected.saturating_sub(1),
+                    KeyCode::Down => (selected + 1).min(last),
+                    KeyCode::PageUp => selected.saturating_sub(HELP_PAGE),
+                    KeyCode::PageDown => (selected + HELP_PAGE).min(last),
+                    KeyCode::Home => 0,
+                    KeyCode::End => last,
+                    _ => selected,
+                };
+                next = match key.code {
+                    KeyCode::Esc => Overlay::None,
+                    KeyCode::Enter => {
+                        if let Some(item) = items.get(selected) {
+                            self.buffer.goto_line(item.line + 1);
+                            self.buffer.move_home();
+                            self.buffer.center_viewport();
+                        }
+                        Overlay::None
                     }
+                    _ => Overlay::Outline { items, selected },
+                };
+            }
+            Overlay::CommandLine { mut input } => match key.code {
+                KeyCode::Esc => next = Overlay::None,
+                KeyCode::Backspace if input.as_str().is_empty() => next = Overlay::None,
+                KeyCode::Enter => {
                     next = Overlay::None;
-                }
-                KeyCode::Char(c)
-                    if !key.modifiers.contains(KeyModifiers::CONTROL)
-                        && !key.modifiers.contains(KeyModifiers::ALT)
-                        && c != '/'
-                        && c != '\\' =>
-                {
-                    filename.push(c);
-                    next = Overlay::SaveAs {
-                        filename,
-                        category_index,
-                    };
+                    self.run_command_line(input.as_str());
                 }
                 _ => {
-                    next = Overlay::SaveAs {
-                        filename,
-                        category_index,
-                    }
+                    input.handle_key(&key, |_| true);
+                    next = Overlay::CommandLine { input };
                 }
             },
-            Overlay::NewFile {
-                mut filename,
-                mut category_index,
-            } => match key.code {
+            Overlay::SaveAs { mut form } => match key.code {
                 KeyCode::Esc => next = Overlay::None,
-                KeyCode::Up => {
-                    if !self.categories.is_empty() {
-                        if category_index == 0 {
-                            category_index = self.categories.len() - 1;
+                KeyCode::Enter
+                    if self
+                        .note_form_issue(&form, true)
+                        .is_some_and(NameIssue::blocks_enter) =>
+                {
+                    next = Overlay::SaveAs { form };
+                }
+                KeyCode::Enter => {
+                    if let Some(name) = form.missing_category(&self.categories) {
+                        next = Overlay::ConfirmCategory {
+                            name,
+                            origin: Box::new(Overlay::SaveAs { form }),
+                            choice: ConfirmChoice::Yes,
+                        };
+                    } else {
+                        let path = match self.note_path(&form) {
+                            Ok(path) => path,
+                            Err(e) => {
+                                self.open_error(format!("Save As failed: {e:#}"));
+                                return;
+                            }
+                        };
+                        if path.exists() && self.buffer.path.as_deref() != Some(path.as_path()) {
+                            next = Overlay::ConfirmOverwrite {
+                                form,
+                                path,
+                                choice: ConfirmChoice::No,
+                            };
                         } else {
-                            category_index -= 1;
+                            if let Err(e) = self.save_as_to(&path) {
+                                self.open_error(format!("Save As failed: {e:#}"));
+                                return;
+                            }
+                            next = Overlay::None;
                         }
                     }
-                    next = Overlay::NewFile {
-                        filename,
-                        category_index,
-                    };
                 }
-                KeyCode::Down => {
-                    if !self.categories.is_empty() {
-                        category_index = (category_index + 1) % self.categories.len();
-                    }
-                    next = Overlay::NewFile {
-                        filename,
-                        category_index,
-                    };
+                _ => {
+                    form.handle_key(&key, &self.categories);
+                    next = Overlay::SaveAs { form };
                 }
-                KeyCode::Backspace => {
-                    filename.pop();
-                    next = Overlay::NewFile {
-                        filename,
-                        category_index,
-                    };
+            },
+            Overlay::NewFile { mut form } => match key.code {
+                KeyCode::Esc => next = Overlay::None,
+                KeyCode::Enter
+                    if self
+                        .note_form_issue(&form, false)
+                        .is_some_and(NameIssue::blocks_enter) =>
+                {
+                    next = Overlay::NewFile { form };
                 }
                 KeyCode::Enter => {
-                    if filename.trim().is_empty() {
-                        self.open_error("File name cannot be empty");
-                        return;
-                    }
-                    match self.create_new_file_in_category(&filename, category_index) {
-                        Ok(path) => {
-                            if let Err(e) = self.open_document(&path) {
-                                self.open_error(format!("Failed to open new file: {e:#}"));
+                    if let Some(name) = form.missing_category(&self.categories) {
+                        next = Overlay::ConfirmCategory {
+                            name,
+                            origin: Box::new(Overlay::NewFile { form }),
+                            choice: ConfirmChoice::Yes,
+                        };
+                    } else {
+                        match self.create_new_file_in_category(
+                            form.filename.as_str(),
+                            form.category_index,
+                        ) {
+                            Ok(path) => {
+                                if let Err(e) = self.open_document(&path) {
+                                    self.open_error(format!("Failed to open new file: {e:#}"));
+                                    return;
+                                }
+                                self.buffer.move_to_document_end();
+                                next = Overlay::None;
+                            }
+                            Err(e) => {
+                                self.open_error(format!("File creation failed: {e:#}"));
                                 return;
                             }
-                            next = Overlay::None;
-                        }
-                        Err(e) => {
-                            self.open_error(format!("File creation failed: {e:#}"));
-                            return;
                         }
                     }
                 }
-                KeyCode::Char(c)
-                    if !key.modifiers.contains(KeyModifiers::CONTROL)
-                        && !key.modifiers.contains(KeyModifiers::ALT)
-                        && c != '/'
-                        && c != '\\' =>
-                {
-                    filename.push(c);
-                    next = Overlay::NewFile {
-                        filename,
-                        category_index,
-                    };
-                }
                 _ => {
-                    next = Overlay::NewFile {
-                        filename,
-                        category_index,
-                    }
+                    form.handle_key(&key, &self.categories);
+                    next = Overlay::NewFile { form };
                 }
             },
             Overlay::Search {
@@ -842,14 +2179,11 @@ impl App {
                 mut state,
             } => match key.code {
                 KeyCode::Esc => next = Overlay::None,
-                KeyCode::Backspace => {
-                    input.pop();
-                    state = self.build_search_state(&input, 0);
-                    self.jump_to_search_match(&state);
-                    next = Overlay::Search { input, state };
-                }
                 KeyCode::Enter => {
-                    if !state.matches.is_empty() {
+                    if self.search_pending_since.take().is_some() {
+                        state = self.build_search_state(input.as_str());
+                        self.jump_to_search_match(&state);
+                    } else if !state.matches.is_empty() {
                         let curr = state.current.unwrap_or(0);
                         let next_idx = if key.modifiers.contains(KeyModifiers::SHIFT) {
                             if curr == 0 {
@@ -865,30 +2199,60 @@ impl App {
                     }
                     next = Overlay::Search { input, state };
                 }
-                KeyCode::Char(c)
-                    if !key.modifiers.contains(KeyModifiers::CONTROL)
-                        && !key.modifiers.contains(KeyModifiers::ALT) =>
-                {
-                    input.push(c);
-                    state = self.build_search_state(&input, 0);
-                    self.jump_to_search_match(&state);
+                _ => {
+                    if input.handle_key(&key, |_| true) {
+                        self.schedule_search(input.as_str(), &mut state);
+                    }
                     next = Overlay::Search { input, state };
                 }
-                _ => next = Overlay::Search { input, state },
             },
             Overlay::None => {}
         }
 
-        self.overlay = next;
+        if matches!(self.overlay, Overlay::None) {
+            self.overlay = next;
+        }
         self.needs_redraw = true;
     }
 
-    fn build_search_state(&self, query: &str, current_idx: usize) -> SearchState {
+    fn schedule_search(&mut self, input: &str, state: &mut SearchState) {
+        if self.buffer.line_count() >= SEARCH_DEBOUNCE_MIN_LINES {
+            self.search_pending_since = Some(Instant::now());
+        } else {
+            *state = self.build_search_state(input);
+            self.jump_to_search_match(state);
+        }
+    }
+
+    fn flush_pending_search(&mut self) {
+        let Some(since) = self.search_pending_since else {
+            return;
+        };
+        if since.elapsed() < SEARCH_DEBOUNCE {
+            return;
+        }
+        self.search_pending_since = None;
+        if let Overlay::Search { input, .. } = &self.overlay {
+            let input = input.clone();
+            let state = self.build_search_state(input.as_str());
+            self.jump_to_search_match(&state);
+            self.overlay = Overlay::Search { input, state };
+            self.needs_redraw = true;
+        }
+    }
+
+    fn build_search_state(&self, query: &str) -> SearchState {
         let matches = self.buffer.find_matches(query);
+        let cursor_line = self.buffer.cursor.line;
         let current = if matches.is_empty() {
             None
         } else {
-            Some(current_idx.min(matches.len() - 1))
+            Some(
+                matches
+                    .iter()
+                    .position(|&line| line >= cursor_line)
+                    .unwrap_or(0),
+            )
         };
         SearchState {
             query: query.to_string(),
@@ -898,9 +2262,16 @@ impl App {
     }
 
     fn jump_to_search_match(&mut self, state: &SearchState) {
-        if let Some(i) = state.current {
-            if let Some(&line) = state.matches.get(i) {
-                self.buffer.goto_line(line + 1);
+        if let Some(i) = state.current
+            && let Some(&line) = state.matches.get(i)
+        {
+            self.buffer.goto_line(line + 1);
+            let line_start = self.buffer.line_start_char_idx(line);
+            if let Some(range) = self.buffer.next_match_after(line_start, &state.query) {
+                self.buffer.set_cursor_char_index(range.start);
+            }
+            if self.center_search {
+                self.buffer.center_viewport();
             }
         }
     }
@@ -918,46 +2289,38 @@ impl App {
             PendingAction::OpenNewFileOverlay { preferred_category } => {
                 self.open_new_file_overlay(preferred_category);
             }
-            PendingAction::DeletePath(path) => {
-                if let Err(e) = self.delete_note_path(&path) {
-                    self.open_error(format!("Delete failed: {e:#}"));
+            PendingAction::DeletePath(path) => self.open_delete_confirmation(path),
+            PendingAction::OpenJournal => {
+                if let Err(e) = self.open_journal(&Local::now()) {
+                    self.open_error(format!("Failed to open journal: {e:#}"));
                 }
             }
         }
     }
 
+    fn notify_readonly(&mut self) {
+        let hint = if self.readonly_locked {
+            "started with --readonly"
+        } else {
+            "Ctrl+R to unlock"
+        };
+        self.status_message = Some(format!("Readonly mode: changes are disabled ({hint})"));
+        self.needs_redraw = true;
+    }
+
     fn apply_command(&mut self, cmd: Command) {
+        if self.buffer.readonly && cmd.is_mutating() {
+            self.notify_readonly();
+            return;
+        }
+        if let Some(recording) = &mut self.recording
+            && cmd.is_recordable()
+        {
+            recording.push(cmd.clone());
+        }
         match cmd {
-            Command::Insert(c) => self.buffer.insert_char(c),
-            Command::NewLine => self.buffer.insert_newline(),
-            Command::Backspace => self.buffer.backspace(),
-            Command::Delete => self.buffer.delete(),
-            Command::MoveLeft => self.buffer.move_left(),
-            Command::MoveRight => self.buffer.move_right(),
-            Command::MoveUp => self.buffer.move_up(),
-            Command::MoveDown => self.buffer.move_down(),
-            Command::MoveHome => self.buffer.move_home(),
-            Command::MoveEnd => self.buffer.move_end(),
-            Command::PageUp => self.buffer.page_up(),
-            Command::PageDown => self.buffer.page_down(),
-            Command::SetLineColor(cid) => {
-                if self.buffer.readonly {
-                    self.open_error("Readonly mode: cannot modify styles");
-                } else {
-                    self.buffer.set_current_char_color(Some(cid));
-                }
-            }
-            Command::ResetLineColor => {
-                if self.buffer.readonly {
-                    self.open_error("Readonly mode: cannot modify styles");
-                } else {
-                    self.buffer.set_current_char_color(None);
-                }
-            }
             Command::Save => {
-                if self.buffer.readonly {
-                    self.open_error("Readonly mode: save disabled");
-                } else if let Some(path) = self.buffer.path.clone() {
+                if let Some(path) = self.buffer.path.clone() {
                     if let Err(e) = self.persist_to_path(&path) {
                         self.open_error(format!("Save failed: {e:#}"));
                     }
@@ -972,14 +2335,77 @@ impl App {
                     self.running = false;
                 }
             }
-            Command::OpenHelp => self.overlay = Overlay::Help,
+            Command::ForceQuit => self.running = false,
+            Command::InsertMode => self.normal_mode = false,
+            Command::AppendMode => {
+                if self.buffer.cursor.col < self.buffer.line_len_chars(self.buffer.cursor.line) {
+                    self.buffer.move_right();
+                }
+                self.normal_mode = false;
+            }
+            Command::OpenCommandLine => {
+                self.overlay = Overlay::CommandLine {
+                    input: LineInput::default(),
+                }
+            }
+            Command::OpenFilter => {
+                self.overlay = Overlay::CommandLine {
+                    input: LineInput::new("!"),
+                }
+            }
+            Command::NormalMode => self.normal_mode = self.keymap == Keymap::Vim,
+            Command::OpenHelp => self.overlay = Overlay::Help { scroll: 0 },
             Command::OpenSearch => {
-                let state = self.build_search_state("", 0);
+                let state = self.build_search_state("");
                 self.overlay = Overlay::Search {
-                    input: String::new(),
+                    input: LineInput::default(),
                     state,
                 };
             }
+            Command::CountOccurrences => match self.buffer.word_at_cursor() {
+                Some(word) => {
+                    let count = self.buffer.count_occurrences(&word);
+                    self.status_message = Some(format!("\"{word}\": {count} occurrence(s)"));
+                }
+                None => self.status_message = Some("No word under cursor".to_string()),
+            },
+            Command::ToggleMarkdownPreview => {
+                self.markdown_preview = !self.markdown_preview;
+                let state = if self.markdown_preview { "on" } else { "off" };
+                self.status_message = Some(if self.markdown_preview && !self.is_markdown_note() {
+                    "Markdown preview on (shown for .md notes)".to_string()
+                } else {
+                    format!("Markdown preview {state}")
+                });
+            }
+            Command::AddWordToDictionary => {
+                if let Err(e) = self.add_word_to_dictionary() {
+                    self.open_error(format!("Saving the dictionary failed: {e:#}"));
+                }
+            }
+            Command::EditNoteMeta => match self.buffer.path.clone() {
+                Some(path) => self.open_note_meta(path),
+                None => self.status_message = Some("Save the note to give it a title".to_string()),
+            },
+            Command::OpenFolder => {
+                let dir = self
+                    .buffer
+                    .path
+                    .as_deref()
+                    .and_then(Path::parent)
+                    .unwrap_or(&self.notes_root)
+                    .to_path_buf();
+                self.open_in_file_manager(&dir);
+            }
+            Command::OpenOutline => {
+                let items = self.buffer.outline();
+                let cursor_line = self.buffer.cursor.line;
+                let selected = items
+                    .iter()
+                    .rposition(|item| item.line <= cursor_line)
+                    .unwrap_or(0);
+                self.overlay = Overlay::Outline { items, selected };
+            }
             Command::OpenGoto => {
                 self.overlay = Overlay::Goto {
                     input: String::new(),
@@ -990,9 +2416,7 @@ impl App {
                 self.file_tree.focus = !self.file_tree.focus;
             }
             Command::NewFile => {
-                if self.buffer.readonly {
-                    self.open_error("Readonly mode: cannot create files");
-                } else if self.buffer.dirty {
+                if self.buffer.dirty {
                     self.request_unsaved_confirmation(PendingAction::OpenNewFileOverlay {
                         preferred_category: None,
                     });
@@ -1000,23 +2424,148 @@ impl App {
                     self.open_new_file_overlay(None);
                 }
             }
-            _ => {}
+            Command::ToggleReadonly => self.toggle_readonly(),
+            Command::ToggleEol => {
+                self.eol = self.eol.toggled();
+                self.buffer.mark_dirty();
+                self.status_message = Some(format!(
+                    "Line endings: {} (saved on next save)",
+                    self.eol.label()
+                ));
+            }
+            Command::NewCategory => self.open_new_category_overlay(PostCategoryAction::None),
+            Command::InsertDateTime => self.insert_date_time(&Local::now()),
+            Command::InsertRule => self.insert_rule(),
+            Command::OpenJournal => {
+                if self.buffer.dirty {
+                    self.request_unsaved_confirmation(PendingAction::OpenJournal);
+                } else {
+                    self.execute_pending_action(PendingAction::OpenJournal);
+                }
+            }
+            Command::ToggleMacroRecording => match self.recording.take() {
+                Some(commands) => {
+                    self.status_message =
+                        Some(format!("Recorded a macro of {} command(s)", commands.len()));
+                    self.last_macro = commands;
+                }
+                None => {
+                    self.recording = Some(Vec::new());
+                    self.status_message = Some("Recording macro".to_string());
+                }
+            },
+            Command::PlayMacro => {
+                if self.last_macro.is_empty() {
+                    self.status_message = Some("No macro recorded".to_string());
+                }
+                for cmd in self.last_macro.clone() {
+                    self.apply_command(cmd);
+                }
+            }
+            Command::RepeatLastEdit => match self.last_edit.clone() {
+                Some(cmd) => self.apply_command(cmd),
+                None => self.status_message = Some("No edit to repeat".to_string()),
+            },
+            other => {
+                let replay = other.is_replayable().then(|| other.clone());
+                if apply_command(&mut self.buffer, other) && replay.is_some() {
+                    self.last_edit = replay;
+                }
+            }
         }
         self.needs_redraw = true;
     }
 
-    fn save_to_category(&mut self, filename: &str, category_index: usize) -> Result<()> {
-        let mut final_name = filename.trim().to_string();
-        if !final_name.ends_with(".txt") {
-            final_name.push_str(".txt");
+    fn run_command_line(&mut self, line: &str) {
+        match line.trim() {
+            "" => {}
+            "w" => self.apply_command(Command::Save),
+            "q" => self.apply_command(Command::Quit),
+            "q!" => self.apply_command(Command::ForceQuit),
+            "wq" | "x" => {
+                if self.buffer.path.is_none() {
+                    self.open_save_as_overlay_with_pending(Some(PendingAction::Quit));
+                } else {
+                    self.apply_command(Command::Save);
+                    if !self.buffer.dirty {
+                        self.running = false;
+                    }
+                }
+            }
+            other if other.starts_with('!') => self.filter_buffer(&other[1..]),
+            other => match goto_target(other, self.buffer.line_count()) {
+                Some(n) => self.buffer.goto_line(n),
+                None => self.status_message = Some(format!("Not an editor command: {other}")),
+            },
         }
-        let category = self
-            .categories
-            .get(category_index)
+    }
+
+    /// Replaces the note with the output of `command` fed the note on stdin.
+    /// A failed or non-zero run leaves the note untouched and shows stderr.
+    fn filter_buffer(&mut self, command: &str) {
+        let command = command.trim();
+        if command.is_empty() {
+            return;
+        }
+        if self.buffer.readonly || self.buffer.append_only() {
+            self.notify_readonly();
+            return;
+        }
+        let text = self.buffer.as_string();
+        match run_filter(command, &text) {
+            Ok(output) if output == text => {
+                self.status_message = Some(format!("`{command}` made no changes"));
+            }
+            Ok(output) => {
+                self.buffer.replace_text(&output);
+                self.status_message = Some(format!("Filtered through `{command}`"));
+            }
+            Err(message) => self.open_error(message),
+        }
+    }
+
+    fn note_path(&self, form: &NoteForm) -> Result<PathBuf> {
+        let final_name = note_file_name(form.filename.as_str(), &self.extensions);
+        let category = form
+            .category_name(&self.categories)
             .context("invalid category for save")?;
-        let path = self.notes_root.join(category).join(final_name);
-        self.persist_to_path(&path)?;
+        Ok(self.notes_root.join(category).join(final_name))
+    }
+
+    pub fn name_issue(&self) -> Option<NameIssue> {
+        match &self.overlay {
+            Overlay::SaveAs { form } => self.note_form_issue(form, true),
+            Overlay::NewFile { form } => self.note_form_issue(form, false),
+            _ => None,
+        }
+    }
+
+    fn note_form_issue(&self, form: &NoteForm, save_as: bool) -> Option<NameIssue> {
+        if form.filename.as_str().trim().is_empty() {
+            return Some(NameIssue::Empty);
+        }
+        if form.missing_category(&self.categories).is_some() {
+            return Some(NameIssue::NewCategory);
+        }
+        let path = self.note_path(form).ok()?;
+        if !path.exists() {
+            return None;
+        }
+        if !save_as {
+            return Some(NameIssue::Exists);
+        }
+        (self.buffer.path.as_deref() != Some(path.as_path())).then_some(NameIssue::Overwrites)
+    }
+
+    fn save_as_to(&mut self, path: &Path) -> Result<()> {
+        self.persist_to_path(path)?;
         self.refresh_tree();
+        if let Some(index) = self.category_index_for_path(path) {
+            self.last_category_index = Some(index);
+        }
+        if let Some(pending) = self.pending_after_save.take() {
+            self.execute_pending_action(pending);
+        }
         Ok(())
     }
 
@@ -1025,10 +2574,7 @@ impl App {
         filename: &str,
         category_index: usize,
     ) -> Result<PathBuf> {
-        let mut final_name = filename.trim().to_string();
-        if !final_name.ends_with(".txt") {
-            final_name.push_str(".txt");
-        }
+        let final_name = note_file_name(filename, &self.extensions);
         let category = self
             .categories
             .get(category_index)
@@ -1037,11 +2583,102 @@ impl App {
         if path.exists() {
             anyhow::bail!("file already exists: {}", path.display());
         }
-        fs::write(&path, "").with_context(|| format!("creating file {}", path.display()))?;
+        let initial = match &self.new_template {
+            Some(template) => {
+                let file_name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                render_note_template(template, &file_name, &Local::now())
+            }
+            None => String::new(),
+        };
+        fs::write(&path, initial).with_context(|| format!("creating file {}", path.display()))?;
         self.refresh_tree();
+        if let Some(index) = self.category_index_for_path(&path) {
+            self.last_category_index = Some(index);
+        }
         Ok(path)
     }
 
+    fn toggle_readonly(&mut self) {
+        if self.buffer.readonly && self.readonly_locked {
+            self.status_message = Some("Started with --readonly: editing stays locked".to_string());
+            return;
+        }
+        let readonly = !self.buffer.readonly;
+        self.buffer.readonly = readonly;
+        self.mode = if readonly {
+            AppMode::ReadOnly
+        } else {
+            AppMode::Edit
+        };
+    }
+
+    fn insert_date_time(&mut self, now: &DateTime<Local>) {
+        self.buffer
+            .insert_str(&now.format(&self.time_format).to_string());
+    }
+
+    /// Puts a separator on a line of its own at the cursor: `---` in Markdown
+    /// notes (after a blank line, so it is not read as a heading underline),
+    /// otherwise a row of `─` as wide as the ruler.
+    fn insert_rule(&mut self) {
+        self.buffer.extra_cursors.clear();
+        let markdown = self.is_markdown_note();
+        let rule = if markdown {
+            "---".to_string()
+        } else {
+            "─".repeat(self.ruler.unwrap_or(RULE_WIDTH))
+        };
+        let Cursor { line, col } = self.buffer.cursor;
+        let above = if col > 0 {
+            self.buffer.line_text(line).chars().take(col).collect()
+        } else if line > 0 {
+            self.buffer.line_text(line - 1)
+        } else {
+            String::new()
+        };
+        let mut prefix = if col > 0 { "\n" } else { "" }.to_string();
+        if markdown && !above.trim().is_empty() {
+            prefix.push('\n');
+        }
+        self.buffer.insert_str(&format!("{prefix}{rule}\n"));
+    }
+
+    fn open_journal(&mut self, now: &DateTime<Local>) -> Result<()> {
+        let dir = self.notes_root.join(JOURNAL_CATEGORY);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("creating category {JOURNAL_CATEGORY}"))?;
+        let date = now.format("%Y-%m-%d").to_string();
+        let existing = self
+            .extensions
+            .iter()
+            .map(|ext| dir.join(format!("{date}.{ext}")))
+            .find(|path| path.exists());
+        let existed = existing.is_some();
+        let path = existing.unwrap_or_else(|| dir.join(note_file_name(&date, &self.extensions)));
+        if !existed {
+            fs::write(&path, format!("# {date}\n"))
+                .with_context(|| format!("creating file {}", path.display()))?;
+        }
+        self.force_refresh_tree();
+        self.open_document(&path)?;
+
+        self.buffer.move_to_document_end();
+        if !existed {
+            return Ok(());
+        }
+        let separator = if self.buffer.as_string().ends_with('\n') {
+            "\n"
+        } else {
+            "\n\n"
+        };
+        self.buffer
+            .insert_str(&format!("{separator}## {}\n", now.format("%H:%M")));
+        Ok(())
+    }
+
     fn create_category(&mut self, name: &str) -> Result<()> {
         let trimmed = name.trim();
         if trimmed.is_empty() {
@@ -1050,6 +2687,9 @@ impl App {
         if trimmed.contains('/') || trimmed.contains('\\') {
             anyhow::bail!("category name contains invalid path characters");
         }
+        if trimmed.starts_with('.') {
+            anyhow::bail!("category name cannot start with '.' (hidden folders are not listed)");
+        }
         let path = self.notes_root.join(trimmed);
         if path.exists() {
             anyhow::bail!("category already exists: {}", trimmed);
@@ -1061,95 +2701,405 @@ impl App {
     }
 
     fn delete_note_path(&mut self, path: &Path) -> Result<()> {
+        let bytes = fs::read(path).with_context(|| format!("reading file {}", path.display()))?;
+        let sidecar = if self.no_style {
+            None
+        } else {
+            Some(
+                snapshot_sidecar(path, self.sidecar_storage)
+                    .with_context(|| format!("reading styles for {}", path.display()))?,
+            )
+        };
         fs::remove_file(path).with_context(|| format!("deleting file {}", path.display()))?;
+        self.last_deleted = Some(DeletedNote {
+            path: path.to_path_buf(),
+            bytes,
+            sidecar,
+        });
 
         if !self.no_style {
-            let sidecar = sidecar_path_for(path);
-            match fs::remove_file(&sidecar) {
-                Ok(()) => {}
-                Err(e) if e.kind() == ErrorKind::NotFound => {}
-                Err(e) => {
-                    return Err(e)
-                        .with_context(|| format!("deleting sidecar {}", sidecar.display()));
-                }
-            }
+            remove_colors(path, self.sidecar_storage)
+                .with_context(|| format!("deleting styles for {}", path.display()))?;
         }
 
         if self.buffer.path.as_deref() == Some(path) {
             let readonly = matches!(self.mode, AppMode::ReadOnly);
-            let mut new_buffer = TextBuffer::new(None, readonly);
-            new_buffer.set_viewport_size(self.buffer.viewport.width, self.buffer.viewport.height);
-            self.buffer = new_buffer;
+            self.replace_buffer(TextBuffer::new(None, readonly));
             self.eol = EolStyle::Lf;
+            self.encoding = UTF_8;
+            self.bom = false;
         }
 
         self.refresh_tree();
         self.file_tree.focus = true;
+        self.status_message = Some(format!(
+            "Deleted {} | Ctrl+Z to undo",
+            path.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default()
+        ));
+        Ok(())
+    }
+
+    fn undo_delete(&mut self) -> Result<()> {
+        let Some(deleted) = self.last_deleted.take() else {
+            self.status_message = Some("Nothing to restore".to_string());
+            return Ok(());
+        };
+        if deleted.path.exists() {
+            let path = deleted.path.clone();
+            self.last_deleted = Some(deleted);
+            anyhow::bail!("file already exists: {}", path.display());
+        }
+        if let Some(dir) = deleted.path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+        }
+        fs::write(&deleted.path, &deleted.bytes)
+            .with_context(|| format!("restoring file {}", deleted.path.display()))?;
+        if let Some(sidecar) = &deleted.sidecar {
+            restore_sidecar(&deleted.path, sidecar)
+                .with_context(|| format!("restoring styles for {}", deleted.path.display()))?;
+        }
+        self.refresh_tree();
+        if let Some(idx) = self
+            .file_tree
+            .nodes
+            .iter()
+            .position(|n| n.path.as_ref() == Some(&deleted.path))
+        {
+            self.file_tree.selected = idx;
+        }
+        self.status_message = Some(format!("Restored {}", deleted.path.display()));
         Ok(())
     }
 
     fn persist_to_path(&mut self, path: &Path) -> Result<()> {
-        save_document(path, &self.buffer.as_string(), self.eol)
-            .with_context(|| format!("saving document to {}", path.display()))?;
+        save_document_chunks(
+            path,
+            self.buffer.chunks(),
+            self.eol,
+            self.encoding,
+            self.bom,
+        )
+        .with_context(|| format!("saving document to {}", path.display()))?;
 
         if !self.no_style {
-            let sidecar = sidecar_path_for(path);
-            save_sidecar(&sidecar, &self.buffer.char_colors)
-                .with_context(|| format!("saving sidecar to {}", sidecar.display()))?;
+            save_colors(path, &self.buffer.char_colors, self.sidecar_storage)
+                .with_context(|| format!("saving styles for {}", path.display()))?;
+        }
+        if self.buffer.path.as_deref() != Some(path)
+            && load_meta(path, self.sidecar_storage).unwrap_or_default() != self.note_meta
+        {
+            self.write_note_meta(path, self.note_meta.clone())?;
         }
 
+        if self.buffer.path.is_none() || self.buffer.path.as_deref() == Some(path) {
+            self.discard_draft();
+        }
         self.buffer.path = Some(path.to_path_buf());
         self.buffer.mark_saved();
         self.refresh_tree();
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        self.status_message = Some(format!("Saved {}", name.to_string_lossy()));
+        self.status_expires_at = Some(Instant::now() + SAVED_MESSAGE_DURATION);
         Ok(())
     }
 
-    fn open_document(&mut self, path: &Path) -> Result<()> {
-        let doc =
-            load_document(path).with_context(|| format!("loading file {}", path.display()))?;
-        let readonly = matches!(self.mode, AppMode::ReadOnly);
-        let mut buffer = TextBuffer::from_text(doc.text, Some(path.to_path_buf()), readonly);
-        if !self.no_style {
-            let sidecar_path = sidecar_path_for(path);
-            if let Ok(colors) = load_sidecar(&sidecar_path) {
-                buffer.set_line_colors(colors);
-            }
+    fn clear_status_message(&mut self) {
+        self.status_message = None;
+        self.status_expires_at = None;
+    }
+
+    /// Redraws once per tick while the status bar shows something that
+    /// changes with time; otherwise idle loops skip drawing entirely.
+    fn tick(&mut self, now: Instant) {
+        if !self.clock {
+            return;
+        }
+        if self
+            .last_tick
+            .is_none_or(|at| now.duration_since(at) >= STATUS_TICK)
+        {
+            self.last_tick = Some(now);
+            self.needs_redraw = true;
         }
-        buffer.set_viewport_size(self.buffer.viewport.width, self.buffer.viewport.height);
-        self.buffer = buffer;
-        self.eol = doc.eol;
-        self.file_tree.focus = false;
-        Ok(())
     }
 
-    fn update_viewport_from_size(&mut self, width: u16, height: u16) {
-        let (editor_w, editor_h) = if width >= 100 {
-            let sidebar = 68.min(width.saturating_sub(20)).max(28);
-            (
-                width.saturating_sub(sidebar).saturating_sub(2),
-                height.saturating_sub(1).saturating_sub(2),
-            )
-        } else {
-            (
-                width.saturating_sub(2),
-                height.saturating_sub(2).saturating_sub(2),
-            )
-        };
-        self.buffer
-            .set_viewport_size(editor_w.max(1), editor_h.max(1));
+    /// How long the loop may block on input before a timed job is due:
+    /// search debounce, draft autosave, status expiry or the clock.
+    fn poll_timeout(&self, now: Instant) -> Duration {
+        let draft = self.draft_autosave_pending().then(|| {
+            self.draft_saved_at
+                .map_or(now, |at| at + DRAFT_AUTOSAVE_INTERVAL)
+        });
+        let clock = self
+            .clock
+            .then(|| self.last_tick.map_or(now, |at| at + STATUS_TICK));
+        [
+            self.search_pending_since.map(|at| at + SEARCH_DEBOUNCE),
+            self.status_expires_at,
+            draft,
+            clock,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .map_or(IDLE_POLL, |due| due.saturating_duration_since(now))
+        .min(IDLE_POLL)
     }
 
-    fn file_title(&self) -> String {
-        self.buffer
-            .path
-            .as_ref()
-            .map(|p| p.display().to_string())
+    fn clock_text(&self) -> Option<String> {
+        self.clock.then(|| Local::now().format("%H:%M").to_string())
+    }
+
+    fn expire_status_message(&mut self, now: Instant) {
+        if self.status_expires_at.is_some_and(|at| now >= at) {
+            self.clear_status_message();
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Where the open buffer's draft goes: a file keyed by the note's path, or
+    /// for untitled text the draft this session owns, else the first free
+    /// `untitled*.txt` so an unrecovered draft is never overwritten.
+    fn draft_path(&self) -> PathBuf {
+        match (&self.buffer.path, &self.untitled_draft) {
+            (Some(note), _) => self.note_draft_path(note),
+            (None, Some(draft)) => draft.clone(),
+            (None, None) => (1..)
+                .map(|n| self.untitled_draft_file(n))
+                .find(|path| !path.exists())
+                .unwrap_or_else(|| self.untitled_draft_file(1)),
+        }
+    }
+
+    fn untitled_draft_file(&self, n: usize) -> PathBuf {
+        let name = match n {
+            1 => format!("{DRAFT_STEM}.txt"),
+            n => format!("{DRAFT_STEM}-{n}.txt"),
+        };
+        self.notes_root.join(DRAFTS_DIR).join(name)
+    }
+
+    fn note_draft_path(&self, note: &Path) -> PathBuf {
+        let drafts = self.notes_root.join(DRAFTS_DIR);
+        match note.strip_prefix(&self.notes_root) {
+            Ok(relative) => drafts.join(NOTE_DRAFTS_DIR).join(relative),
+            Err(_) => {
+                let parts: Vec<String> = note
+                    .components()
+                    .filter_map(|part| match part {
+                        Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                        _ => None,
+                    })
+                    .collect();
+                drafts.join(EXTERNAL_DRAFTS_DIR).join(parts.join("%"))
+            }
+        }
+    }
+
+    fn pending_untitled_draft(&self) -> Option<PathBuf> {
+        fs::read_dir(self.notes_root.join(DRAFTS_DIR))
+            .ok()?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with(DRAFT_STEM) && name.ends_with(".txt"))
+            })
+            .min_by_key(|path| (path.as_os_str().len(), path.clone()))
+    }
+
+    fn draft_autosave_pending(&self) -> bool {
+        self.buffer.path.is_none()
+            && !self.buffer.readonly
+            && self.buffer.dirty
+            && self.draft_generation != Some(self.buffer.generation())
+    }
+
+    fn autosave_draft(&mut self) {
+        if !self.draft_autosave_pending()
+            || self
+                .draft_saved_at
+                .is_some_and(|at| at.elapsed() < DRAFT_AUTOSAVE_INTERVAL)
+        {
+            return;
+        }
+        self.draft_saved_at = Some(Instant::now());
+        match self.write_draft() {
+            Ok(()) => self.draft_generation = Some(self.buffer.generation()),
+            Err(e) => {
+                self.status_message = Some(format!("Draft autosave failed: {e:#}"));
+                self.needs_redraw = true;
+            }
+        }
+    }
+
+    fn write_draft(&mut self) -> Result<()> {
+        let path = self.draft_path();
+        if self.buffer.path.is_none() {
+            self.untitled_draft = Some(path.clone());
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+        }
+        save_document_chunks(&path, self.buffer.chunks(), EolStyle::Lf, UTF_8, false)
+            .with_context(|| format!("saving draft to {}", path.display()))?;
+        save_colors(&path, &self.buffer.char_colors, SidecarStorage::PerFile)
+            .with_context(|| format!("saving draft styles to {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Stops the loop after a termination signal. A dirty buffer goes to its
+    /// draft file, never over the open note, and is offered when it is next
+    /// opened (or on launch, for untitled text).
+    fn shutdown_on_signal(&mut self) {
+        if self.buffer.dirty && !self.buffer.readonly {
+            let _ = self.write_draft();
+        }
+        self.running = false;
+    }
+
+    fn discard_draft(&mut self) {
+        let path = match &self.buffer.path {
+            Some(note) => Some(self.note_draft_path(note)),
+            None => self.untitled_draft.take(),
+        };
+        if let Some(path) = path {
+            remove_draft(&path);
+        }
+        self.draft_generation = None;
+        self.draft_saved_at = None;
+    }
+
+    fn offer_draft_recovery(&mut self) {
+        if self.buffer.readonly {
+            return;
+        }
+        let path = match &self.buffer.path {
+            Some(note) => Some(self.note_draft_path(note)).filter(|path| path.exists()),
+            None => self.pending_untitled_draft(),
+        };
+        if let Some(path) = path {
+            self.overlay = Overlay::RecoverDraft {
+                path,
+                note: self.buffer.path.as_ref().map(|note| {
+                    note.file_name()
+                        .unwrap_or(note.as_os_str())
+                        .to_string_lossy()
+                        .into_owned()
+                }),
+                choice: ConfirmChoice::Yes,
+            };
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Loads a draft into the buffer as unsaved text. An untitled draft stays
+    /// this session's autosave target; a note's draft is dropped once loaded.
+    fn recover_draft(&mut self, path: &Path) -> Result<()> {
+        let doc = load_document(path, None)
+            .with_context(|| format!("loading draft {}", path.display()))?;
+        let note = self.buffer.path.clone();
+        let meta = std::mem::take(&mut self.note_meta);
+        let mut buffer = TextBuffer::from_text(doc.text, note.clone(), false);
+        if let Ok(colors) = load_colors(path, SidecarStorage::PerFile, buffer.len_chars()) {
+            buffer.set_line_colors(colors);
+        }
+        buffer.dirty = true;
+        self.replace_buffer(buffer);
+        self.note_meta = meta;
+        if note.is_some() {
+            remove_draft(path);
+        } else {
+            self.untitled_draft = Some(path.to_path_buf());
+        }
+        Ok(())
+    }
+
+    fn replace_buffer(&mut self, mut buffer: TextBuffer) {
+        buffer.set_viewport_size(self.buffer.viewport.width, self.buffer.viewport.height);
+        buffer.set_active_color(self.buffer.active_color());
+        buffer.overwrite = self.buffer.overwrite;
+        buffer.auto_pairs = self.buffer.auto_pairs;
+        buffer.smart_quotes = self.buffer.smart_quotes;
+        buffer.set_append_only(self.buffer.append_only());
+        self.buffer = buffer;
+        self.note_meta = NoteMeta::default();
+    }
+
+    fn open_document(&mut self, path: &Path) -> Result<()> {
+        let doc = load_document(path, self.forced_encoding)
+            .with_context(|| format!("loading file {}", path.display()))?;
+        let readonly = matches!(self.mode, AppMode::ReadOnly);
+        let mut buffer = TextBuffer::from_text(doc.text, Some(path.to_path_buf()), readonly);
+        if !self.no_style {
+            match load_colors(path, self.sidecar_storage, buffer.len_chars()) {
+                Ok(colors) => buffer.set_line_colors(colors),
+                Err(e) => self.status_message = Some(format!("Styles not loaded: {e}")),
+            }
+        }
+        self.replace_buffer(buffer);
+        self.note_meta = load_meta(path, self.sidecar_storage).unwrap_or_default();
+        self.eol = doc.eol;
+        self.encoding = doc.encoding;
+        self.bom = doc.bom;
+        self.file_tree.focus = false;
+        self.offer_draft_recovery();
+        Ok(())
+    }
+
+    fn update_viewport_from_size(&mut self, width: u16, height: u16) {
+        self.screen_height = height;
+        let (editor_w, editor_h) = if width >= 100 {
+            let sidebar = 68.min(width.saturating_sub(20)).max(28);
+            (
+                width.saturating_sub(sidebar).saturating_sub(2),
+                height.saturating_sub(1).saturating_sub(2),
+            )
+        } else {
+            (
+                width.saturating_sub(2),
+                height.saturating_sub(2).saturating_sub(2),
+            )
+        };
+        self.buffer
+            .set_viewport_size(editor_w.max(1), editor_h.max(1));
+    }
+
+    fn is_markdown_note(&self) -> bool {
+        self.buffer.path.as_deref().is_some_and(is_markdown_path)
+    }
+
+    fn file_title(&self) -> String {
+        if let Some(title) = &self.note_meta.title {
+            return title.clone();
+        }
+        self.buffer
+            .path
+            .as_ref()
+            .map(|p| p.display().to_string())
             .unwrap_or_else(|| "[No Name]".to_string())
     }
 
     fn status_hint(&self) -> String {
+        if let Some(message) = &self.status_message {
+            return message.clone();
+        }
+        if matches!(self.overlay, Overlay::Error { .. }) && !self.pending_errors.is_empty() {
+            return format!("Enter/Esc: next error ({} more)", self.pending_errors.len());
+        }
         if self.file_tree.focus {
-            return "TREE: Up/Down select | Enter open | N new | C category | Del delete | Esc back"
+            if self.file_tree.filtering {
+                return format!(
+                    "FILTER: {}_ | Up/Down select | Enter open | Esc clear",
+                    self.file_tree.filter
+                );
+            }
+            return "TREE: Up/Down select | Enter open | / filter | S sort | I info | N new | C category | Del delete | R reload | Esc back"
                 .to_string();
         }
         if self.buffer.readonly {
@@ -1180,20 +3130,42 @@ pub fn run() -> Result<()> {
     ensure_notes_root(&notes_root)?;
 
     let mut eol = EolStyle::Lf;
+    let mut encoding = cli.encoding.unwrap_or(UTF_8);
+    let mut bom = false;
+    let mut startup_error = None;
+    let sidecar_storage = if cli.sidecar_index {
+        SidecarStorage::Index
+    } else {
+        SidecarStorage::PerFile
+    };
 
     let mut buffer = if let Some(path) = &cli.file {
         if path.exists() {
-            let doc =
-                load_document(path).with_context(|| format!("loading file {}", path.display()))?;
-            eol = doc.eol;
-            let mut b = TextBuffer::from_text(doc.text, Some(path.clone()), cli.readonly);
-            if !cli.no_style {
-                let sidecar_path = sidecar_path_for(path);
-                if let Ok(colors) = load_sidecar(&sidecar_path) {
-                    b.set_line_colors(colors);
+            let doc = match load_document(path, cli.encoding) {
+                Ok(doc) => Some(doc),
+                Err(e @ IoError::BinaryFile { .. }) => {
+                    startup_error = Some(format!("{e}. Use --encoding to force a text decode."));
+                    None
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| format!("loading file {}", path.display()));
+                }
+            };
+            if let Some(doc) = doc {
+                eol = doc.eol;
+                encoding = doc.encoding;
+                bom = doc.bom;
+                let mut b = TextBuffer::from_text(doc.text, Some(path.clone()), cli.readonly);
+                if !cli.no_style {
+                    match load_colors(path, sidecar_storage, b.len_chars()) {
+                        Ok(colors) => b.set_line_colors(colors),
+                        Err(e) => startup_error = Some(format!("Styles not loaded: {e}")),
+                    }
                 }
+                b
+            } else {
+                TextBuffer::new(None, cli.readonly)
             }
-            b
         } else {
             TextBuffer::new(Some(path.clone()), cli.readonly)
         }
@@ -1205,36 +3177,91 @@ pub fn run() -> Result<()> {
         buffer.readonly = true;
     }
 
-    let mut app = App::new(buffer, eol, cli.no_style, notes_root);
-    let (_guard, mut terminal) = setup_terminal()?;
+    let mut app = App::new(buffer, eol, encoding, cli.no_style, notes_root);
+    app.forced_encoding = cli.encoding;
+    app.bom = bom;
+    app.sidecar_storage = sidecar_storage;
+    if let Some(path) = &app.buffer.path {
+        app.note_meta = load_meta(path, sidecar_storage).unwrap_or_default();
+    }
+    app.center_search = cli.center_search;
+    app.new_template = cli.new_template;
+    app.time_format = cli.time_format;
+    app.extensions = cli.extensions;
+    app.readonly_locked = cli.readonly;
+    app.force_refresh_tree();
+    app.load_ui_prefs(cli.file.is_none());
+    if let Some(name) = &cli.category
+        && !app.focus_category(name)
+    {
+        app.status_message = Some(format!("Unknown category: {name}"));
+    }
+    app.buffer.set_append_only(cli.append);
+    app.buffer.auto_pairs = cli.auto_pairs;
+    app.buffer.smart_quotes = cli.smart_quotes;
+    app.keymap = if cli.modal { Keymap::Vim } else { cli.keymap };
+    app.clock = cli.clock;
+    app.indent_guides = cli.indent_guides;
+    app.trailing_whitespace = cli.trailing_whitespace;
+    app.ruler = cli.ruler.map(usize::from);
+    if let Some(word_list) = &cli.spellcheck {
+        app.load_spellcheck(word_list)?;
+    }
+    app.normal_mode = app.keymap == Keymap::Vim;
+    if let Some(message) = startup_error {
+        app.open_error(message);
+    } else {
+        app.offer_draft_recovery();
+    }
+    let (_guard, mut terminal) = setup_terminal(!cli.no_mouse)?;
+    watch_termination_signals(app.shutdown.clone(), !cli.no_mouse)?;
+    run_loop(&mut app, &mut terminal, &mut CrosstermEvents)?;
+    let _ = app.save_ui_prefs();
+    terminal.show_cursor().context("show cursor")?;
+    Ok(())
+}
+
+pub trait EventSource {
+    fn next_event(&mut self, timeout: Duration) -> io::Result<Option<Event>>;
+
+    fn is_exhausted(&self) -> bool {
+        false
+    }
+}
+
+pub struct CrosstermEvents;
+
+impl EventSource for CrosstermEvents {
+    fn next_event(&mut self, timeout: Duration) -> io::Result<Option<Event>> {
+        if event::poll(timeout)? {
+            event::read().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+pub fn run_loop<B: Backend>(
+    app: &mut App,
+    terminal: &mut Terminal<B>,
+    events: &mut impl EventSource,
+) -> Result<()> {
     let size = terminal.size()?;
     app.update_viewport_from_size(size.width, size.height);
 
-    while app.running {
+    while app.running && !events.is_exhausted() {
         if app.needs_redraw {
-            terminal.draw(|f| {
-                draw(
-                    f,
-                    UiModel {
-                        buffer: &app.buffer,
-                        mode: app.mode,
-                        overlay: &app.overlay,
-                        file_title: app.file_title(),
-                        hint: app.status_hint(),
-                        no_style: app.no_style,
-                        file_tree: &app.file_tree,
-                        categories: &app.categories,
-                    },
-                );
-            })?;
-            app.needs_redraw = false;
+            draw_app(app, terminal)?;
         }
 
-        if event::poll(Duration::from_millis(120))? {
-            match event::read()? {
+        if let Some(event) = events.next_event(app.poll_timeout(Instant::now()))? {
+            match event {
                 Event::Key(key) => {
                     app.handle_key(key);
                 }
+                Event::Paste(text) => {
+                    app.handle_paste(&text);
+                }
                 Event::Resize(w, h) => {
                     app.update_viewport_from_size(w, h);
                     app.needs_redraw = true;
@@ -1242,47 +3269,1725 @@ pub fn run() -> Result<()> {
                 _ => {}
             }
         }
+        app.flush_pending_search();
+        app.autosave_draft();
+        app.expire_status_message(Instant::now());
+        app.tick(Instant::now());
+        if app.shutdown.load(Ordering::SeqCst) {
+            app.shutdown_on_signal();
+        }
     }
+    if app.running && app.needs_redraw {
+        draw_app(app, terminal)?;
+    }
+    Ok(())
+}
 
-    terminal.show_cursor().context("show cursor")?;
+fn draw_app<B: Backend>(app: &mut App, terminal: &mut Terminal<B>) -> Result<()> {
+    terminal.draw(|f| {
+        draw(
+            f,
+            UiModel {
+                buffer: &app.buffer,
+                mode: app.mode,
+                overlay: &app.overlay,
+                file_title: app.file_title(),
+                hint: app.status_hint(),
+                no_style: app.no_style,
+                encoding: app.encoding.name(),
+                eol: app.eol.label(),
+                file_tree: &app.file_tree,
+                categories: &app.categories,
+                name_issue: app.name_issue(),
+                keymap: app.keymap,
+                normal_mode: app.normal_mode,
+                clock: app.clock_text(),
+                indent_guides: app.indent_guides,
+                spell: app.spell.as_ref(),
+                markdown_preview: app.markdown_preview,
+                trailing_whitespace: app.trailing_whitespace,
+                ruler: app.ruler,
+                recording: app.recording.is_some(),
+            },
+        );
+    })?;
+    app.needs_redraw = false;
     Ok(())
 }
 
-struct TerminalGuard;
+struct TerminalGuard {
+    mouse: bool,
+}
 
 impl Drop for TerminalGuard {
     fn drop(&mut self) {
-        let _ = disable_raw_mode();
-        let _ = execute!(
-            io::stdout(),
-            LeaveAlternateScreen,
-            crossterm::event::DisableMouseCapture
-        );
+        restore_terminal(self.mouse);
+    }
+}
+
+/// Flags `shutdown` on SIGTERM/SIGINT/SIGHUP and raises SIGWINCH, which the
+/// crossterm poll listens for, so a blocked loop wakes up and exits cleanly.
+/// A second signal restores the terminal and exits right away.
+fn watch_termination_signals(shutdown: Arc<AtomicBool>, mouse: bool) -> Result<()> {
+    let mut signals =
+        Signals::new([SIGTERM, SIGINT, SIGHUP]).context("installing signal handlers")?;
+    thread::spawn(move || {
+        let mut received = signals.forever();
+        if let Some(signal) = received.next() {
+            shutdown.store(true, Ordering::SeqCst);
+            let _ = signal_hook::low_level::raise(SIGWINCH);
+            let signal = received.next().unwrap_or(signal);
+            restore_terminal(mouse);
+            process::exit(128 + signal);
+        }
+    });
+    Ok(())
+}
+
+fn restore_terminal(mouse: bool) {
+    let _ = disable_raw_mode();
+    if mouse {
+        let _ = execute!(io::stdout(), crossterm::event::DisableMouseCapture);
     }
+    let _ = execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        crossterm::event::DisableBracketedPaste
+    );
 }
 
-fn setup_terminal() -> Result<(TerminalGuard, Terminal<CrosstermBackend<io::Stdout>>)> {
+fn setup_terminal(mouse: bool) -> Result<(TerminalGuard, Terminal<CrosstermBackend<io::Stdout>>)> {
     enable_raw_mode().context("enabling raw mode")?;
     execute!(
         io::stdout(),
         EnterAlternateScreen,
-        crossterm::event::EnableMouseCapture
+        crossterm::event::EnableBracketedPaste
     )
     .context("enter alternate screen")?;
+    if mouse {
+        execute!(io::stdout(), crossterm::event::EnableMouseCapture)
+            .context("enabling mouse capture")?;
+    }
 
     let hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |panic_info| {
-        let _ = disable_raw_mode();
-        let _ = execute!(
-            io::stdout(),
-            LeaveAlternateScreen,
-            crossterm::event::DisableMouseCapture
-        );
+        restore_terminal(mouse);
         hook(panic_info);
     }));
 
-    let guard = TerminalGuard;
+    let guard = TerminalGuard { mouse };
     let backend = CrosstermBackend::new(io::stdout());
     let terminal = Terminal::new(backend).context("creating terminal")?;
     Ok((guard, terminal))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::atomic::Ordering;
+    use std::time::{Duration, Instant, SystemTime};
+
+    use chrono::{Local, TimeZone};
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+    use tempfile::tempdir;
+
+    use super::{
+        App, AppMode, ConfirmChoice, IDLE_POLL, NameIssue, NoteForm, Overlay,
+        SAVED_MESSAGE_DURATION, STATUS_TICK, TreeGroup, TreeNodeKind, TreeSort, UnsavedChoice,
+        estimate_line_count, goto_target, parse_extension, parse_time_format, render_note_template,
+        run_loop,
+    };
+    use crate::core::{Command, TextBuffer};
+    use crate::input::Keymap;
+    use crate::io::{EolStyle, NoteMeta, SidecarStorage, load_meta, save_meta};
+    use crate::testing::{self, Script, press, type_str};
+
+    #[test]
+    fn active_color_carries_over_when_opening_another_file() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let other = notes.join("work").join("other.txt");
+        fs::write(&other, "hello").expect("write");
+
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.buffer.set_active_color(Some(4));
+        app.open_document(&other).expect("open");
+        assert_eq!(app.buffer.path.as_deref(), Some(other.as_path()));
+        assert_eq!(app.buffer.active_color(), Some(4));
+
+        app.buffer.insert_char('x');
+        assert_eq!(app.buffer.char_color(0), Some(4));
+        assert_eq!(app.buffer.char_color(1), None);
+
+        app.delete_note_path(&other).expect("delete");
+        assert_eq!(app.buffer.path, None);
+        assert_eq!(app.buffer.active_color(), Some(4));
+    }
+
+    #[test]
+    fn auto_pairs_carry_over_to_the_next_note() {
+        let dir = tempdir().expect("tempdir");
+        let other = dir.path().join("other.txt");
+        fs::write(&other, "").expect("write");
+        let mut app = testing::app(TextBuffer::new(None, false), dir.path());
+        app.buffer.auto_pairs = true;
+        app.buffer.smart_quotes = true;
+        app.open_document(&other).expect("open");
+        assert!(app.buffer.smart_quotes);
+        for c in "{x}".chars() {
+            app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        assert_eq!(app.buffer.as_string(), "{x}");
+        app.handle_key(KeyEvent::new(KeyCode::Left, KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Left, KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Delete, KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        assert_eq!(app.buffer.as_string(), "");
+    }
+
+    #[test]
+    fn queued_errors_are_shown_one_after_another() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(TextBuffer::new(None, false), dir.path());
+        app.open_error("first");
+        app.open_error("second");
+        app.open_error("third");
+        assert_eq!(app.pending_errors.len(), 2);
+
+        let mut seen = Vec::new();
+        while let Overlay::Error { message } = &app.overlay {
+            seen.push(message.clone());
+            app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        }
+        assert_eq!(seen, ["first", "second", "third"]);
+        assert!(matches!(app.overlay, Overlay::None));
+        assert!(app.pending_errors.is_empty());
+    }
+
+    #[test]
+    fn save_as_onto_existing_file_asks_before_overwriting() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let existing = notes.join("work").join("taken.txt");
+        fs::write(&existing, "keep me").expect("write");
+
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.buffer.insert_str("new text");
+        app.overlay = Overlay::SaveAs {
+            form: NoteForm::new("taken", 0),
+        };
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+
+        app.handle_key(enter);
+        assert!(matches!(app.overlay, Overlay::ConfirmOverwrite { .. }));
+        app.handle_key(enter);
+        assert!(matches!(app.overlay, Overlay::SaveAs { .. }));
+        assert_eq!(fs::read_to_string(&existing).expect("read"), "keep me");
+
+        app.handle_key(enter);
+        app.handle_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
+        app.handle_key(enter);
+        assert!(matches!(app.overlay, Overlay::None));
+        assert_eq!(fs::read_to_string(&existing).expect("read"), "new text");
+        assert_eq!(app.buffer.path.as_deref(), Some(existing.as_path()));
+    }
+
+    #[test]
+    fn successful_save_flashes_a_message_that_expires() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("plan.txt");
+        fs::write(&path, "plan").expect("write");
+        let mut app = testing::app(
+            TextBuffer::from_text("plan".to_string(), Some(path), false),
+            dir.path(),
+        );
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+        assert_eq!(app.status_message.as_deref(), Some("Saved plan.txt"));
+        app.expire_status_message(Instant::now());
+        assert!(app.status_message.is_some());
+        app.expire_status_message(Instant::now() + SAVED_MESSAGE_DURATION);
+        assert!(app.status_message.is_none());
+        assert!(app.status_expires_at.is_none());
+    }
+
+    #[test]
+    fn status_tick_redraws_only_while_the_clock_is_shown() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(TextBuffer::new(None, false), dir.path());
+        let start = Instant::now();
+        app.needs_redraw = false;
+        app.tick(start);
+        assert!(!app.needs_redraw);
+        assert!(app.clock_text().is_none());
+
+        app.clock = true;
+        app.tick(start);
+        assert!(app.needs_redraw);
+        app.needs_redraw = false;
+        app.tick(start + Duration::from_millis(500));
+        assert!(!app.needs_redraw);
+        app.tick(start + Duration::from_secs(1));
+        assert!(app.needs_redraw);
+        assert!(app.clock_text().is_some());
+    }
+
+    #[test]
+    fn poll_blocks_until_the_next_timed_job_or_stays_idle() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("plan.txt");
+        let mut app = testing::app(
+            TextBuffer::from_text("plan".to_string(), Some(path), false),
+            dir.path(),
+        );
+        let now = Instant::now();
+        assert_eq!(app.poll_timeout(now), IDLE_POLL);
+
+        app.status_expires_at = Some(now + Duration::from_secs(2));
+        assert_eq!(app.poll_timeout(now), Duration::from_secs(2));
+        app.clock = true;
+        app.last_tick = Some(now);
+        assert_eq!(app.poll_timeout(now), STATUS_TICK);
+
+        app.buffer.path = None;
+        app.buffer.insert_char('!');
+        assert_eq!(app.poll_timeout(now), Duration::ZERO);
+    }
+
+    #[test]
+    fn plain_save_keeps_cursor_and_viewport() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        fs::write(notes.join("work").join("a.txt"), "a").expect("write");
+        let path = notes.join("work").join("long.txt");
+        let text: String = (0..300).map(|i| format!("line {i}\n")).collect();
+        fs::write(&path, text).expect("write");
+
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.open_document(&path).expect("open");
+        app.update_viewport_from_size(120, 40);
+        for _ in 0..5 {
+            app.buffer.page_down();
+        }
+        app.buffer.move_right();
+        app.buffer.insert_char('x');
+        app.file_tree.selected = 0;
+        let cursor = app.buffer.cursor;
+        let top_line = app.buffer.viewport.top_line;
+
+        let mut terminal = testing::terminal();
+        run_loop(&mut app, &mut terminal, &mut Script::new().ctrl('s')).expect("run loop");
+
+        assert!(!app.buffer.dirty);
+        assert_eq!(app.buffer.cursor, cursor);
+        assert_eq!(app.buffer.viewport.top_line, top_line);
+        assert!(top_line > 0);
+    }
+
+    #[test]
+    fn force_quit_skips_unsaved_confirmation() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(TextBuffer::new(None, false), dir.path());
+        app.buffer.insert_str("unsaved");
+        app.handle_key(KeyEvent::new(
+            KeyCode::Char('Q'),
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        ));
+        assert!(!app.running);
+        assert!(matches!(app.overlay, Overlay::None));
+    }
+
+    #[test]
+    fn unsaved_dialog_cancel_keeps_editing_and_discard_quits() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(TextBuffer::new(None, false), dir.path());
+        app.buffer.insert_str("unsaved");
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let ctrl_q = KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL);
+
+        app.handle_key(ctrl_q);
+        app.handle_key(key(KeyCode::Right));
+        app.handle_key(key(KeyCode::Right));
+        assert!(matches!(
+            app.overlay,
+            Overlay::ConfirmUnsaved {
+                choice: UnsavedChoice::Cancel,
+                ..
+            }
+        ));
+        app.handle_key(key(KeyCode::Enter));
+        assert!(matches!(app.overlay, Overlay::None));
+        assert!(app.running);
+        assert!(app.buffer.dirty);
+
+        app.handle_key(ctrl_q);
+        app.handle_key(key(KeyCode::Char('d')));
+        app.handle_key(key(KeyCode::Enter));
+        assert!(!app.running);
+    }
+
+    #[test]
+    fn note_template_formats_time_and_file_name() {
+        let now = Local
+            .with_ymd_and_hms(2024, 3, 9, 7, 5, 0)
+            .single()
+            .expect("valid time");
+        let template = parse_time_format("# {name}\\n%Y-%m-%d %H:%M\\n\\n").expect("format");
+        assert_eq!(
+            render_note_template(&template, "100%.txt", &now),
+            "# 100%.txt\n2024-03-09 07:05\n\n"
+        );
+        assert!(parse_time_format("%Q").is_err());
+    }
+
+    #[test]
+    fn invalid_new_file_names_are_flagged_and_enter_is_ignored() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        fs::write(notes.join("work").join("plan.txt"), "plan").expect("note");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+
+        app.overlay = Overlay::NewFile {
+            form: NoteForm::new("plan", 0),
+        };
+        assert_eq!(app.name_issue(), Some(NameIssue::Exists));
+        app.handle_key(enter);
+        assert!(matches!(app.overlay, Overlay::NewFile { .. }));
+        assert_eq!(
+            fs::read_to_string(notes.join("work").join("plan.txt")).expect("read"),
+            "plan"
+        );
+
+        app.overlay = Overlay::NewFile {
+            form: NoteForm::new("  ", 0),
+        };
+        assert_eq!(app.name_issue(), Some(NameIssue::Empty));
+        app.handle_key(enter);
+        assert!(matches!(app.overlay, Overlay::NewFile { .. }));
+
+        app.overlay = Overlay::SaveAs {
+            form: NoteForm::new("plan", 0),
+        };
+        assert_eq!(app.name_issue(), Some(NameIssue::Overwrites));
+        app.handle_key(enter);
+        assert!(matches!(app.overlay, Overlay::ConfirmOverwrite { .. }));
+    }
+
+    #[test]
+    fn new_note_overlay_defaults_to_the_last_used_category() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("home")).expect("category");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.overlay = Overlay::NewFile {
+            form: NoteForm::new("todo", 1),
+        };
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+
+        app.buffer = TextBuffer::new(None, false);
+        app.apply_command(Command::NewFile);
+        assert!(matches!(
+            app.overlay,
+            Overlay::NewFile { ref form } if form.category_index == 1
+        ));
+    }
+
+    #[test]
+    fn category_field_tab_completes_and_cycles_matches() {
+        let categories: Vec<String> = ["home", "work", "workshop", "writing"]
+            .map(String::from)
+            .to_vec();
+        let tab = KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE);
+        let mut form = NoteForm::new("a", 0);
+
+        form.handle_key(&tab, &categories);
+        assert!(form.category_focus);
+        form.handle_key(
+            &KeyEvent::new(KeyCode::Char('w'), KeyModifiers::NONE),
+            &categories,
+        );
+        form.handle_key(
+            &KeyEvent::new(KeyCode::Char('o'), KeyModifiers::NONE),
+            &categories,
+        );
+        form.handle_key(&tab, &categories);
+        assert_eq!(form.category.as_str(), "work");
+        assert_eq!(form.category_index, 1);
+        form.handle_key(&tab, &categories);
+        assert_eq!(form.category.as_str(), "workshop");
+        form.handle_key(&tab, &categories);
+        assert_eq!(form.category.as_str(), "work");
+
+        form.handle_key(&KeyEvent::new(KeyCode::Up, KeyModifiers::NONE), &categories);
+        assert_eq!(form.category.as_str(), "");
+        assert_eq!(form.category_index, 0);
+        form.handle_key(&tab, &categories);
+        assert!(!form.category_focus);
+    }
+
+    #[test]
+    fn typing_an_unknown_category_asks_before_creating_it() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.overlay = Overlay::NewFile {
+            form: NoteForm::new("idea", 0),
+        };
+        press(&mut app, KeyCode::Tab);
+        for c in "ideas".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        assert_eq!(app.name_issue(), Some(NameIssue::NewCategory));
+
+        press(&mut app, KeyCode::Enter);
+        assert!(matches!(app.overlay, Overlay::ConfirmCategory { .. }));
+        press(&mut app, KeyCode::Esc);
+        assert!(matches!(app.overlay, Overlay::NewFile { .. }));
+        assert!(!notes.join("ideas").exists());
+
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Enter);
+        let path = notes.join("ideas").join("idea.txt");
+        assert!(path.exists());
+        assert_eq!(app.buffer.path.as_deref(), Some(path.as_path()));
+        assert!(matches!(app.overlay, Overlay::None));
+        assert!(!app.file_tree.focus);
+    }
+
+    #[test]
+    fn extreme_terminal_sizes_keep_a_usable_viewport_and_render() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(
+            TextBuffer::from_text("hello\nworld".to_string(), None, false),
+            dir.path(),
+        );
+        for (w, h) in [
+            (0, 0),
+            (1, 1),
+            (2, 3),
+            (99, 1),
+            (100, 2),
+            (u16::MAX, u16::MAX),
+        ] {
+            app.update_viewport_from_size(w, h);
+            assert!(app.buffer.viewport.width >= 1 && app.buffer.viewport.height >= 1);
+        }
+
+        for (w, h) in [(1, 1), (5, 2), (19, 40), (120, 4)] {
+            let mut terminal = Terminal::new(TestBackend::new(w, h)).expect("terminal");
+            app.needs_redraw = true;
+            run_loop(
+                &mut app,
+                &mut terminal,
+                &mut Script::new().event(Event::FocusGained),
+            )
+            .expect("run loop");
+            let screen = testing::screen(&terminal);
+            if w * h > 20 {
+                assert!(screen.contains("Terminal too small"), "{w}x{h}");
+            }
+        }
+    }
+
+    #[test]
+    fn key_releases_do_not_type_twice_in_overlays() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(TextBuffer::new(None, false), dir.path());
+        app.apply_command(Command::OpenSearch);
+        let mut key = KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE);
+        app.handle_key(key);
+        key.kind = KeyEventKind::Release;
+        app.handle_key(key);
+        assert!(matches!(&app.overlay, Overlay::Search { input, .. } if input.as_str() == "a"));
+    }
+
+    #[test]
+    fn vim_keymap_switches_between_normal_and_insert_mode() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(
+            TextBuffer::from_text("ab\ncd".to_string(), None, false),
+            dir.path(),
+        );
+        app.keymap = Keymap::Vim;
+        app.normal_mode = true;
+
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Char('l'));
+        press(&mut app, KeyCode::Char('z'));
+        assert_eq!(app.buffer.as_string(), "ab\ncd");
+        assert_eq!((app.buffer.cursor.line, app.buffer.cursor.col), (1, 1));
+
+        press(&mut app, KeyCode::Char('i'));
+        press(&mut app, KeyCode::Char('j'));
+        assert!(!app.normal_mode);
+        assert_eq!(app.buffer.as_string(), "ab\ncjd");
+        press(&mut app, KeyCode::Esc);
+        assert!(app.normal_mode);
+    }
+
+    #[test]
+    fn modal_normal_mode_edits_and_runs_command_lines() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(
+            TextBuffer::from_text("one\ntwo\nthree".to_string(), None, false),
+            dir.path(),
+        );
+        app.keymap = Keymap::Vim;
+        app.normal_mode = true;
+
+        type_str(&mut app, "x");
+        assert_eq!(app.buffer.as_string(), "ne\ntwo\nthree");
+        type_str(&mut app, "jdd");
+        assert_eq!(app.buffer.as_string(), "ne\nthree");
+        type_str(&mut app, "djd");
+        assert_eq!(app.buffer.as_string(), "ne\nthree");
+
+        type_str(&mut app, ":1");
+        assert!(matches!(app.overlay, Overlay::CommandLine { .. }));
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.buffer.cursor.line, 0);
+        type_str(&mut app, ":nope");
+        press(&mut app, KeyCode::Enter);
+        assert!(
+            app.status_message
+                .as_deref()
+                .is_some_and(|m| m.contains("nope"))
+        );
+
+        type_str(&mut app, "$a!");
+        assert_eq!(app.buffer.as_string(), "ne!\nthree");
+        assert!(!app.normal_mode);
+
+        press(&mut app, KeyCode::Esc);
+        type_str(&mut app, ":q");
+        press(&mut app, KeyCode::Enter);
+        assert!(matches!(app.overlay, Overlay::ConfirmUnsaved { .. }));
+        press(&mut app, KeyCode::Esc);
+        type_str(&mut app, ":q!");
+        press(&mut app, KeyCode::Enter);
+        assert!(!app.running);
+    }
+
+    #[test]
+    fn dot_repeats_the_last_edit_with_a_count() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(
+            TextBuffer::from_text("abcdef\n1\n2\n3\n4".to_string(), None, false),
+            dir.path(),
+        );
+        app.keymap = Keymap::Vim;
+        app.normal_mode = true;
+
+        type_str(&mut app, ".");
+        assert_eq!(app.status_message.as_deref(), Some("No edit to repeat"));
+        type_str(&mut app, "xl.");
+        assert_eq!(app.buffer.as_string(), "bdef\n1\n2\n3\n4");
+        type_str(&mut app, "2.");
+        assert_eq!(app.buffer.as_string(), "bf\n1\n2\n3\n4");
+
+        type_str(&mut app, "jdd2.");
+        assert_eq!(app.buffer.as_string(), "bf\n4");
+
+        app.handle_key(KeyEvent::new(KeyCode::F(3), KeyModifiers::NONE));
+        app.buffer.set_current_char_color(None);
+        app.keymap = Keymap::Default;
+        app.handle_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::CONTROL));
+        assert_eq!(app.buffer.current_char_color(), Some(2));
+    }
+
+    #[test]
+    fn macro_records_edits_and_motions_and_replays_with_a_count() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(
+            TextBuffer::from_text("a\nb\nc\nd".to_string(), None, false),
+            dir.path(),
+        );
+        app.keymap = Keymap::Vim;
+        app.normal_mode = true;
+
+        type_str(&mut app, "@");
+        assert_eq!(app.status_message.as_deref(), Some("No macro recorded"));
+        type_str(&mut app, "qi-");
+        assert!(app.recording.is_some());
+        app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        type_str(&mut app, "j0q");
+        assert!(app.recording.is_none());
+        assert_eq!(
+            app.last_macro,
+            [
+                Command::InsertMode,
+                Command::Insert('-'),
+                Command::NormalMode,
+                Command::MoveDown,
+                Command::MoveHome,
+            ]
+        );
+
+        type_str(&mut app, "2@");
+        assert_eq!(app.buffer.as_string(), "-a\n-b\n-c\nd");
+        assert!(app.normal_mode);
+        assert_eq!(app.buffer.cursor.line, 3);
+    }
+
+    #[test]
+    fn count_prefix_repeats_the_next_motion_in_normal_mode() {
+        let dir = tempdir().expect("tempdir");
+        let text = (1..=30)
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut app = testing::app(TextBuffer::from_text(text, None, false), dir.path());
+        app.keymap = Keymap::Vim;
+        app.normal_mode = true;
+
+        press(&mut app, KeyCode::Char('1'));
+        press(&mut app, KeyCode::Char('2'));
+        assert_eq!(app.status_message.as_deref(), Some("Count: 12"));
+        press(&mut app, KeyCode::Char('j'));
+        assert_eq!(app.buffer.cursor.line, 12);
+        press(&mut app, KeyCode::Char('5'));
+        press(&mut app, KeyCode::Down);
+        assert_eq!(app.buffer.cursor.line, 17);
+
+        press(&mut app, KeyCode::Char('l'));
+        press(&mut app, KeyCode::Char('0'));
+        assert_eq!(app.buffer.cursor.col, 0);
+        press(&mut app, KeyCode::Char('3'));
+        press(&mut app, KeyCode::Char('x'));
+        assert_eq!(app.buffer.line_text(17), "8");
+        press(&mut app, KeyCode::Char('k'));
+        assert_eq!(app.buffer.cursor.line, 16);
+    }
+
+    #[test]
+    fn emacs_keymap_kills_to_line_end() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(
+            TextBuffer::from_text("hello world\nnext".to_string(), None, false),
+            dir.path(),
+        );
+        app.keymap = Keymap::Emacs;
+        let ctrl = |app: &mut App, c| {
+            app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL))
+        };
+
+        ctrl(&mut app, 'e');
+        ctrl(&mut app, 'b');
+        ctrl(&mut app, 'b');
+        ctrl(&mut app, 'k');
+        assert_eq!(app.buffer.as_string(), "hello wor\nnext");
+        ctrl(&mut app, 'k');
+        assert_eq!(app.buffer.as_string(), "hello wornext");
+        ctrl(&mut app, 'a');
+        assert_eq!(app.buffer.cursor.col, 0);
+    }
+
+    #[test]
+    fn new_file_name_can_be_edited_in_the_middle() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.overlay = Overlay::NewFile {
+            form: NoteForm::new("idea", 0),
+        };
+        press(&mut app, KeyCode::Home);
+        press(&mut app, KeyCode::Char('/'));
+        for c in "my-".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        press(&mut app, KeyCode::End);
+        press(&mut app, KeyCode::Left);
+        press(&mut app, KeyCode::Delete);
+        press(&mut app, KeyCode::Enter);
+
+        assert!(notes.join("work").join("my-ide.txt").exists());
+    }
+
+    #[test]
+    fn new_file_starts_with_template_and_cursor_after_it() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.new_template = Some("# {name}\n\n".to_string());
+        app.overlay = Overlay::NewFile {
+            form: NoteForm::new("idea", 0),
+        };
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+
+        let path = notes.join("work").join("idea.txt");
+        assert_eq!(fs::read_to_string(&path).expect("read"), "# idea.txt\n\n");
+        assert_eq!(app.buffer.path.as_deref(), Some(path.as_path()));
+        assert_eq!(app.buffer.cursor_char_index(), "# idea.txt\n\n".len());
+    }
+
+    #[test]
+    fn journal_creates_todays_note_then_appends_headings() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(&notes).expect("notes root");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        let morning = Local
+            .with_ymd_and_hms(2024, 3, 9, 7, 5, 0)
+            .single()
+            .expect("valid time");
+        app.open_journal(&morning).expect("journal");
+
+        let path = notes.join("journal").join("2024-03-09.txt");
+        assert_eq!(app.buffer.path.as_deref(), Some(path.as_path()));
+        assert_eq!(app.buffer.as_string(), "# 2024-03-09\n");
+        assert!(app.categories.iter().any(|c| c == "journal"));
+        app.buffer.insert_str("coffee");
+        app.apply_command(Command::Save);
+
+        let evening = Local
+            .with_ymd_and_hms(2024, 3, 9, 21, 30, 0)
+            .single()
+            .expect("valid time");
+        app.open_journal(&evening).expect("journal");
+        assert_eq!(app.buffer.as_string(), "# 2024-03-09\ncoffee\n\n## 21:30\n");
+        assert_eq!(
+            app.buffer.cursor_char_index(),
+            app.buffer.as_string().chars().count()
+        );
+
+        app.apply_command(Command::Save);
+        app.extensions = vec!["md".to_string(), "txt".to_string()];
+        app.open_journal(&evening).expect("journal");
+        assert_eq!(app.buffer.path.as_deref(), Some(path.as_path()));
+        let next_day = Local
+            .with_ymd_and_hms(2024, 3, 10, 8, 0, 0)
+            .single()
+            .expect("valid time");
+        app.open_journal(&next_day).expect("journal");
+        let md = notes.join("journal").join("2024-03-10.md");
+        assert_eq!(app.buffer.path.as_deref(), Some(md.as_path()));
+        assert_eq!(app.buffer.as_string(), "# 2024-03-10\n");
+    }
+
+    #[test]
+    fn insert_date_time_uses_configured_format_at_cursor() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(
+            TextBuffer::from_text("ab".to_string(), None, false),
+            dir.path(),
+        );
+        app.buffer.move_right();
+        app.time_format = "[%d/%m %H:%M]".to_string();
+        let now = Local
+            .with_ymd_and_hms(2024, 3, 9, 7, 5, 0)
+            .single()
+            .expect("valid time");
+        app.insert_date_time(&now);
+        assert_eq!(app.buffer.as_string(), "a[09/03 07:05]b");
+        assert_eq!(app.buffer.cursor_char_index(), 14);
+    }
+
+    #[test]
+    fn separator_line_always_lands_on_its_own_line() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(
+            TextBuffer::from_text("abcd\n".to_string(), None, false),
+            dir.path(),
+        );
+        app.ruler = Some(3);
+        let ctrl_shift_l = KeyEvent::new(
+            KeyCode::Char('L'),
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        );
+        app.buffer.move_right();
+        app.buffer.move_right();
+        app.handle_key(ctrl_shift_l);
+        assert_eq!(app.buffer.as_string(), "ab\n───\ncd\n");
+        assert_eq!((app.buffer.cursor.line, app.buffer.cursor.col), (2, 0));
+        app.handle_key(ctrl_shift_l);
+        assert_eq!(app.buffer.as_string(), "ab\n───\n───\ncd\n");
+
+        let path = dir.path().join("plan.md");
+        fs::write(&path, "# Plan\n\nintro\n").expect("write");
+        app.open_document(&path).expect("open");
+        app.buffer.move_to_document_end();
+        app.handle_key(ctrl_shift_l);
+        app.buffer.goto_line(2);
+        app.handle_key(ctrl_shift_l);
+        assert_eq!(app.buffer.as_string(), "# Plan\n\n---\n\nintro\n\n---\n");
+    }
+
+    #[test]
+    fn append_mode_snaps_cursor_back_to_the_end() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(
+            TextBuffer::from_text("log\n".to_string(), None, false),
+            dir.path(),
+        );
+        app.buffer.set_append_only(true);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+        app.handle_key(key(KeyCode::Up));
+        assert_eq!(app.buffer.cursor_char_index(), 4);
+        app.handle_key(key(KeyCode::Char('x')));
+        app.handle_key(key(KeyCode::Home));
+        app.handle_key(key(KeyCode::Backspace));
+        app.handle_key(key(KeyCode::Backspace));
+        assert_eq!(app.buffer.as_string(), "log\n");
+    }
+
+    #[test]
+    fn untitled_draft_is_autosaved_and_recovered() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        fs::create_dir_all(notes.join("drafts")).expect("category");
+        let new_app = || testing::app(TextBuffer::new(None, false), &notes);
+
+        let mut app = new_app();
+        app.buffer.set_active_color(Some(3));
+        app.buffer.insert_str("lost\nthought");
+        app.autosave_draft();
+        let draft = notes.join(".drafts").join("untitled.txt");
+        assert_eq!(fs::read_to_string(&draft).expect("draft"), "lost\nthought");
+        assert_eq!(app.categories, ["drafts", "work"]);
+        assert!(app.create_category(".drafts").is_err());
+
+        let mut app = new_app();
+        app.offer_draft_recovery();
+        assert!(matches!(app.overlay, Overlay::RecoverDraft { .. }));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.buffer.as_string(), "lost\nthought");
+        assert_eq!(app.buffer.char_color(0), Some(3));
+        assert!(app.buffer.dirty);
+        assert_eq!(app.buffer.path, None);
+
+        app.persist_to_path(&notes.join("work").join("kept.txt"))
+            .expect("save");
+        assert!(!draft.exists());
+    }
+
+    #[test]
+    fn discarding_an_untitled_buffer_removes_its_draft() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(&notes).expect("notes");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        type_str(&mut app, "scratch");
+        app.autosave_draft();
+        let draft = notes.join(".drafts").join("untitled.txt");
+        assert!(draft.exists());
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL));
+        assert!(matches!(app.overlay, Overlay::ConfirmUnsaved { .. }));
+        type_str(&mut app, "d");
+        press(&mut app, KeyCode::Enter);
+        assert!(!app.running);
+        assert!(!draft.exists());
+
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.offer_draft_recovery();
+        assert!(matches!(app.overlay, Overlay::None));
+    }
+
+    #[test]
+    fn termination_signal_stops_the_loop_and_keeps_edits_as_a_draft() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        let path = notes.join("work").join("plan.txt");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        fs::write(&path, "plan").expect("write");
+        let mut app = testing::app(
+            TextBuffer::from_text("plan".to_string(), Some(path.clone()), false),
+            &notes,
+        );
+        let mut terminal = testing::terminal();
+
+        let mut events = Script::new().type_str("!");
+        run_loop(&mut app, &mut terminal, &mut events).expect("run loop");
+        assert!(app.running);
+        app.shutdown.store(true, Ordering::SeqCst);
+        let mut events = Script::new().event(Event::Resize(120, 40)).type_str("?");
+        run_loop(&mut app, &mut terminal, &mut events).expect("run loop");
+
+        assert!(!app.running);
+        assert_eq!(events.remaining(), 1);
+        assert_eq!(fs::read_to_string(&path).expect("note"), "plan");
+        let draft = notes
+            .join(".drafts")
+            .join("notes")
+            .join("work")
+            .join("plan.txt");
+        assert_eq!(fs::read_to_string(&draft).expect("draft"), "!plan");
+
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.offer_draft_recovery();
+        assert!(matches!(app.overlay, Overlay::None));
+        app.open_document(&path).expect("open");
+        assert!(matches!(
+            &app.overlay,
+            Overlay::RecoverDraft { note: Some(name), .. } if name == "plan.txt"
+        ));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.buffer.as_string(), "!plan");
+        assert_eq!(app.buffer.path.as_deref(), Some(path.as_path()));
+        assert!(app.buffer.dirty);
+        assert!(!draft.exists());
+    }
+
+    #[test]
+    fn signal_never_overwrites_an_unrecovered_untitled_draft() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        let drafts = notes.join(".drafts");
+        fs::create_dir_all(&drafts).expect("drafts");
+        fs::write(drafts.join("untitled.txt"), "older").expect("draft");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.buffer.insert_str("newer");
+
+        app.shutdown_on_signal();
+        assert_eq!(
+            fs::read_to_string(drafts.join("untitled.txt")).expect("draft"),
+            "older"
+        );
+        assert_eq!(
+            fs::read_to_string(drafts.join("untitled-2.txt")).expect("draft"),
+            "newer"
+        );
+
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.offer_draft_recovery();
+        app.handle_key(KeyEvent::new(KeyCode::Right, KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(!drafts.join("untitled.txt").exists());
+        app.offer_draft_recovery();
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.buffer.as_string(), "newer");
+    }
+
+    #[test]
+    fn tree_filter_narrows_selection_and_esc_clears_it() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        for name in ["alpha.txt", "beta.txt", "gamma.txt"] {
+            fs::write(notes.join("work").join(name), "").expect("note");
+        }
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.file_tree.focus = true;
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+        for c in ['/', 'A', 'm'] {
+            app.handle_key(key(KeyCode::Char(c)));
+        }
+        let gamma = notes.join("work").join("gamma.txt");
+        assert_eq!(app.file_tree.selected_path(), Some(gamma.clone()));
+        app.handle_key(key(KeyCode::Down));
+        assert_eq!(app.file_tree.selected_path(), Some(gamma));
+        let visible = app
+            .file_tree
+            .nodes
+            .iter()
+            .filter(|n| matches!(n.kind, TreeNodeKind::File) && app.file_tree.is_visible(n))
+            .count();
+        assert_eq!(visible, 1);
+
+        app.handle_key(key(KeyCode::Esc));
+        assert!(app.file_tree.filter.is_empty());
+        assert!(app.file_tree.focus);
+        app.handle_key(key(KeyCode::Down));
+        assert_eq!(
+            app.file_tree.selected_path(),
+            Some(notes.join("work").join("alpha.txt"))
+        );
+        app.handle_key(key(KeyCode::Esc));
+        assert!(!app.file_tree.focus);
+    }
+
+    #[test]
+    fn tree_can_sort_by_modification_time() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let base = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for (i, name) in ["a.txt", "b.txt", "c.txt"].iter().enumerate() {
+            let path = notes.join("work").join(name);
+            fs::write(&path, "").expect("note");
+            let offset = Duration::from_secs([30, 10, 20][i]);
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|f| f.set_modified(base + offset))
+                .expect("mtime");
+        }
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        let files = |app: &App| -> Vec<String> {
+            app.file_tree
+                .nodes
+                .iter()
+                .filter(|n| matches!(n.kind, TreeNodeKind::File))
+                .map(|n| n.label.trim().to_string())
+                .collect()
+        };
+        assert_eq!(files(&app), ["a.txt", "b.txt", "c.txt"]);
+
+        app.file_tree.focus = true;
+        app.handle_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::NONE));
+        assert_eq!(app.file_tree.sort, TreeSort::Modified);
+        assert_eq!(files(&app), ["a.txt", "c.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn ctrl_keys_in_the_tree_run_their_editor_commands() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let path = notes.join("work").join("plan.txt");
+        fs::write(&path, "plan").expect("note");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.open_document(&path).expect("open");
+        app.buffer.insert_str("my ");
+        app.file_tree.focus = true;
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+        assert_eq!(fs::read_to_string(&path).expect("read"), "my plan");
+        assert!(!app.buffer.dirty);
+        assert_eq!(app.file_tree.sort, TreeSort::Name);
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL));
+        assert_eq!(app.file_tree.group, TreeGroup::Category);
+        assert!(matches!(app.overlay, Overlay::Goto { .. }));
+    }
+
+    #[test]
+    fn launch_category_focuses_its_first_note() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("home")).expect("category");
+        fs::create_dir_all(notes.join("Work")).expect("category");
+        fs::write(notes.join("Work").join("plan.txt"), "").expect("note");
+        fs::write(notes.join("Work").join("todo.txt"), "").expect("note");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+
+        assert!(app.focus_category("work"));
+        assert!(app.file_tree.focus);
+        let node = &app.file_tree.nodes[app.file_tree.selected];
+        assert_eq!(
+            node.path.as_deref(),
+            Some(notes.join("Work").join("plan.txt").as_path())
+        );
+
+        assert!(app.focus_category("home"));
+        let node = &app.file_tree.nodes[app.file_tree.selected];
+        assert!(matches!(node.kind, TreeNodeKind::Category));
+        assert!(!app.focus_category("missing"));
+    }
+
+    #[test]
+    fn ui_prefs_survive_a_restart() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let new_app = || testing::app(TextBuffer::new(None, false), &notes);
+
+        let mut app = new_app();
+        app.load_ui_prefs(true);
+        assert!(!app.file_tree.focus);
+        press(&mut app, KeyCode::F(12));
+        press(&mut app, KeyCode::Insert);
+        app.file_tree.focus = true;
+        app.file_tree.sort = TreeSort::Modified;
+        app.file_tree.group = TreeGroup::Tag;
+        app.save_ui_prefs().expect("save prefs");
+
+        let mut app = new_app();
+        app.load_ui_prefs(true);
+        assert!(app.file_tree.focus);
+        assert_eq!(app.file_tree.sort, TreeSort::Modified);
+        assert_eq!(app.file_tree.group, TreeGroup::Tag);
+        assert!(app.markdown_preview);
+        assert!(app.buffer.overwrite);
+        assert_eq!(app.categories, ["work"]);
+
+        let mut app = new_app();
+        app.load_ui_prefs(false);
+        assert!(!app.file_tree.focus);
+        assert_eq!(app.file_tree.sort, TreeSort::Modified);
+        assert!(app.markdown_preview);
+
+        fs::write(notes.join(".ui.json"), "{ not json").expect("write");
+        let mut app = new_app();
+        app.load_ui_prefs(true);
+        assert!(!app.file_tree.focus);
+        assert!(!app.markdown_preview);
+    }
+
+    #[test]
+    fn line_count_is_exact_for_small_files_and_estimated_for_large_ones() {
+        let dir = tempdir().expect("tempdir");
+        let small = dir.path().join("small.txt");
+        fs::write(&small, "a\nb\nc").expect("write");
+        assert_eq!(estimate_line_count(&small, 5).expect("count"), (3, false));
+
+        let large = dir.path().join("large.txt");
+        let line = "x".repeat(99) + "\n";
+        let text = line.repeat(30_000);
+        fs::write(&large, &text).expect("write");
+        let (lines, estimated) = estimate_line_count(&large, text.len() as u64).expect("count");
+        assert!(estimated);
+        assert!((29_900..=30_100).contains(&lines));
+    }
+
+    #[test]
+    fn recognized_extensions_are_listed_and_kept_on_save() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        for name in ["a.txt", "b.md", "c.rs"] {
+            fs::write(notes.join("work").join(name), "").expect("note");
+        }
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        let listed: Vec<_> = app
+            .file_tree
+            .nodes
+            .iter()
+            .filter_map(|n| n.path.clone())
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                notes.join("work").join("a.txt"),
+                notes.join("work").join("b.md")
+            ]
+        );
+
+        assert_eq!(
+            app.note_path(&NoteForm::new("readme.MD", 0)).expect("path"),
+            notes.join("work").join("readme.MD")
+        );
+        assert_eq!(
+            app.note_path(&NoteForm::new("plain", 0)).expect("path"),
+            notes.join("work").join("plain.txt")
+        );
+        assert_eq!(
+            app.create_new_file_in_category("draft.md", 0)
+                .expect("create"),
+            notes.join("work").join("draft.md")
+        );
+        assert_eq!(parse_extension(".org"), Ok("org".to_string()));
+        assert!(parse_extension(".").is_err());
+    }
+
+    #[test]
+    fn goto_accepts_line_numbers_and_percentages() {
+        assert_eq!(goto_target("12", 200), Some(12));
+        assert_eq!(goto_target("50%", 200), Some(100));
+        assert_eq!(goto_target("0%", 200), Some(1));
+        assert_eq!(goto_target("250%", 200), Some(200));
+        assert_eq!(goto_target("%", 200), None);
+
+        let dir = tempdir().expect("tempdir");
+        let text = (1..=40)
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut app = testing::app(TextBuffer::from_text(text, None, false), dir.path());
+        app.handle_key(KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL));
+        for c in "7%5%".chars() {
+            app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        assert!(matches!(&app.overlay, Overlay::Goto { input } if input == "7%"));
+        app.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        app.handle_paste("5%");
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.buffer.cursor.line, 29);
+    }
+
+    #[test]
+    fn ctrl_shift_n_opens_new_category_from_the_editor() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(TextBuffer::new(None, false), dir.path());
+        app.handle_key(KeyEvent::new(
+            KeyCode::Char('N'),
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        ));
+        assert!(matches!(app.overlay, Overlay::NewCategory { .. }));
+        for c in "ideas".chars() {
+            app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(dir.path().join("ideas").is_dir());
+        assert_eq!(app.categories, ["ideas"]);
+    }
+
+    #[test]
+    fn category_order_file_is_honored_and_rewritten_on_move() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        for category in ["alpha", "beta", "gamma", "delta"] {
+            fs::create_dir_all(notes.join(category)).expect("category");
+        }
+        fs::write(notes.join("gamma").join("g.txt"), "").expect("note");
+        fs::write(notes.join(".order"), "gamma\nmissing\nbeta\n").expect("order");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        assert_eq!(app.categories, ["gamma", "beta", "alpha", "delta"]);
+
+        app.file_tree.focus = true;
+        assert_eq!(app.file_tree.selected_category_index(), Some(0));
+        app.handle_key(KeyEvent::new(KeyCode::Down, KeyModifiers::SHIFT));
+        assert_eq!(app.categories, ["beta", "gamma", "alpha", "delta"]);
+        assert_eq!(
+            app.file_tree.selected_path(),
+            Some(notes.join("gamma").join("g.txt"))
+        );
+        assert_eq!(
+            fs::read_to_string(notes.join(".order")).expect("order"),
+            "beta\ngamma\nalpha\ndelta\n"
+        );
+    }
+
+    #[test]
+    fn ctrl_z_in_tree_restores_the_last_deleted_note() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let path = notes.join("work").join("oops.txt");
+        fs::write(&path, b"caf\xe9").expect("write");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.open_document(&path).expect("open");
+        app.buffer.set_current_char_color(Some(5));
+        app.apply_command(Command::Save);
+        let sidecar = fs::read_to_string(crate::io::sidecar_path_for(&path)).expect("sidecar");
+
+        app.delete_note_path(&path).expect("delete");
+        assert!(!path.exists());
+        assert!(app.file_tree.focus);
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL));
+        assert_eq!(fs::read(&path).expect("restored"), b"caf\xe9");
+        assert_eq!(
+            fs::read_to_string(crate::io::sidecar_path_for(&path)).expect("sidecar"),
+            sidecar
+        );
+        assert_eq!(app.file_tree.selected_path(), Some(path));
+    }
+
+    #[test]
+    fn undo_delete_puts_back_sidecars_it_cannot_parse() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let path = notes.join("work").join("future.txt");
+        fs::write(&path, "text").expect("write");
+        let sidecar = crate::io::sidecar_path_for(&path);
+        let raw = r#"{ "version": 99, "char_runs": "new shape" }"#;
+        fs::write(&sidecar, raw).expect("sidecar");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+
+        app.delete_note_path(&path).expect("delete");
+        assert!(!sidecar.exists());
+        app.undo_delete().expect("undo");
+        assert_eq!(fs::read_to_string(&sidecar).expect("sidecar"), raw);
+
+        let index = crate::io::index_path_for(&path);
+        fs::remove_file(&sidecar).expect("remove sidecar");
+        fs::write(
+            &index,
+            r#"{ "version": 2, "files": { "future.txt": { "char_runs": 7 }, "other.txt": {} } }"#,
+        )
+        .expect("index");
+        app.sidecar_storage = SidecarStorage::Index;
+        app.delete_note_path(&path).expect("delete");
+        fs::write(&index, r#"{ "version": 2, "files": { "other.txt": {} } }"#).expect("index");
+        app.undo_delete().expect("undo");
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&index).expect("index")).expect("json");
+        assert_eq!(json["files"]["future.txt"]["char_runs"], 7);
+        assert!(json["files"]["other.txt"].is_object());
+    }
+
+    #[test]
+    fn note_title_and_tags_are_edited_shown_and_kept() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let path = notes.join("work").join("plan.txt");
+        fs::write(&path, "plan").expect("write");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+
+        app.file_tree.focus = true;
+        press(&mut app, KeyCode::Char('t'));
+        type_str(&mut app, "Weekly plan");
+        press(&mut app, KeyCode::Tab);
+        type_str(&mut app, "work, #todo,,work");
+        press(&mut app, KeyCode::Enter);
+        let meta = load_meta(&path, SidecarStorage::PerFile).expect("meta");
+        assert_eq!(meta.title.as_deref(), Some("Weekly plan"));
+        assert_eq!(meta.tags, ["work", "todo"]);
+        assert!(
+            app.file_tree
+                .nodes
+                .iter()
+                .any(|n| n.label == "  Weekly plan")
+        );
+
+        app.open_document(&path).expect("open");
+        assert_eq!(app.file_title(), "Weekly plan");
+        app.persist_to_path(&notes.join("work").join("copy.txt"))
+            .expect("save as");
+        let copied = load_meta(
+            &notes.join("work").join("copy.txt"),
+            SidecarStorage::PerFile,
+        )
+        .expect("meta");
+        assert_eq!(copied, meta);
+
+        app.delete_note_path(&path).expect("delete");
+        app.undo_delete().expect("undo");
+        assert_eq!(
+            load_meta(&path, SidecarStorage::PerFile).expect("meta"),
+            meta
+        );
+    }
+
+    #[test]
+    fn tag_view_lists_a_note_under_each_of_its_tags() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("home")).expect("category");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let tagged = |path: std::path::PathBuf, title: Option<&str>, tags: &[&str]| {
+            fs::write(&path, "").expect("write");
+            let meta = NoteMeta {
+                title: title.map(str::to_string),
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+            };
+            save_meta(&path, &meta, SidecarStorage::PerFile).expect("meta");
+        };
+        tagged(
+            notes.join("work").join("plan.txt"),
+            Some("Plan"),
+            &["todo", "q3"],
+        );
+        tagged(notes.join("home").join("shop.txt"), None, &["todo"]);
+        tagged(notes.join("home").join("misc.txt"), None, &[]);
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        let labels = |app: &App| -> Vec<String> {
+            app.file_tree
+                .nodes
+                .iter()
+                .map(|n| n.label.trim().to_string())
+                .collect()
+        };
+
+        app.file_tree.focus = true;
+        app.handle_key(KeyEvent::new(KeyCode::Char('g'), KeyModifiers::NONE));
+        assert_eq!(app.file_tree.group, TreeGroup::Tag);
+        assert_eq!(
+            labels(&app),
+            [
+                "[#q3]",
+                "work/Plan",
+                "[#todo]",
+                "home/shop.txt",
+                "work/Plan",
+                "[no tag]",
+                "home/misc.txt",
+            ]
+        );
+        assert_eq!(app.file_tree.selected, 6);
+        app.handle_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        assert_eq!(app.file_tree.selected, 1);
+        assert_eq!(app.file_tree.selected_category_index(), Some(1));
+
+        app.handle_key(KeyEvent::new(KeyCode::Down, KeyModifiers::SHIFT));
+        assert_eq!(app.categories, ["home", "work"]);
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('g'), KeyModifiers::NONE));
+        assert_eq!(app.file_tree.group, TreeGroup::Category);
+        assert_eq!(labels(&app)[0], "[home]");
+        assert_eq!(
+            app.file_tree.selected_path(),
+            Some(notes.join("work").join("plan.txt"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn filter_replaces_the_note_or_shows_stderr() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(
+            TextBuffer::from_text("pear\napple\nfig".into(), None, false),
+            dir.path(),
+        );
+        let run = |app: &mut App, command: &str| {
+            app.handle_key(KeyEvent::new(
+                KeyCode::Char('P'),
+                KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+            ));
+            for c in command.chars() {
+                app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+            }
+            app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        };
+
+        run(&mut app, "sort");
+        assert_eq!(app.buffer.as_string(), "apple\nfig\npear");
+        assert!(app.buffer.dirty);
+
+        run(&mut app, "echo broken >&2; exit 3");
+        assert_eq!(app.buffer.as_string(), "apple\nfig\npear");
+        match &app.overlay {
+            Overlay::Error { message } => {
+                assert!(message.contains("exited with status 3"));
+                assert!(message.contains("broken"));
+            }
+            other => panic!("expected error overlay, got {other:?}"),
+        }
+
+        app.overlay = Overlay::None;
+        run(&mut app, "cat");
+        assert_eq!(app.status_message.as_deref(), Some("`cat` made no changes"));
+    }
+
+    #[test]
+    fn added_words_persist_in_the_personal_dictionary() {
+        let dir = tempdir().expect("tempdir");
+        let word_list = dir.path().join("words");
+        fs::write(&word_list, "hello\n").expect("word list");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(&notes).expect("notes");
+        let new_app = || {
+            testing::app(
+                TextBuffer::from_text("hello ropey".into(), None, false),
+                &notes,
+            )
+        };
+        let add_word = KeyEvent::new(
+            KeyCode::Char('D'),
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        );
+
+        let mut app = new_app();
+        app.handle_key(add_word);
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Spellcheck is off (start with --spellcheck)")
+        );
+
+        app.load_spellcheck(&word_list).expect("load");
+        app.buffer.move_end();
+        app.handle_key(add_word);
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Added \"ropey\" to the dictionary")
+        );
+        assert_eq!(
+            fs::read_to_string(notes.join(".dictionary")).expect("dictionary"),
+            "ropey\n"
+        );
+
+        let mut app = new_app();
+        app.load_spellcheck(&word_list).expect("load");
+        let spell = app.spell.as_ref().expect("spell");
+        assert!(spell.misspelled("hello ropey").is_empty());
+        assert!(app.load_spellcheck(&dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn open_folder_reports_a_missing_file_manager() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let path = notes.join("work").join("plan.txt");
+        fs::write(&path, "plan").expect("write");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.open_document(&path).expect("open");
+
+        app.file_manager = "dopepad-no-such-file-manager";
+        app.handle_key(KeyEvent::new(
+            KeyCode::Char('O'),
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        ));
+        match &app.overlay {
+            Overlay::Error { message } => {
+                assert!(message.contains("dopepad-no-such-file-manager"));
+                assert!(message.contains(&notes.join("work").display().to_string()));
+            }
+            other => panic!("expected error overlay, got {other:?}"),
+        }
+
+        app.overlay = Overlay::None;
+        app.file_manager = "true";
+        app.file_tree.focus = true;
+        app.handle_key(KeyEvent::new(KeyCode::Char('o'), KeyModifiers::NONE));
+        assert!(matches!(app.overlay, Overlay::None));
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some(format!("Opened {}", notes.join("work").display()).as_str())
+        );
+    }
+
+    #[test]
+    fn deleting_a_dirty_open_note_still_asks_with_no_preselected() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let path = notes.join("work").join("open.txt");
+        fs::write(&path, "text").expect("write");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.open_document(&path).expect("open");
+        app.buffer.insert_char('x');
+        app.file_tree.focus = true;
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+        app.handle_key(key(KeyCode::Delete));
+        assert!(matches!(app.overlay, Overlay::ConfirmUnsaved { .. }));
+        app.handle_key(key(KeyCode::Char('d')));
+        app.handle_key(key(KeyCode::Enter));
+        assert!(matches!(
+            app.overlay,
+            Overlay::ConfirmDelete {
+                choice: ConfirmChoice::No,
+                ..
+            }
+        ));
+        app.handle_key(key(KeyCode::Enter));
+        assert!(path.exists());
+        assert!(matches!(app.overlay, Overlay::None));
+    }
+
+    #[test]
+    fn readonly_toggles_at_runtime_unless_locked_by_the_cli() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(TextBuffer::new(None, false), dir.path());
+        let ctrl_r = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL);
+        let x = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE);
+
+        app.handle_key(ctrl_r);
+        assert!(matches!(app.mode, AppMode::ReadOnly));
+        app.handle_key(x);
+        assert_eq!(app.buffer.as_string(), "");
+        app.handle_key(ctrl_r);
+        assert!(matches!(app.mode, AppMode::Edit));
+        app.handle_key(x);
+        assert_eq!(app.buffer.as_string(), "x");
+
+        app.readonly_locked = true;
+        app.handle_key(ctrl_r);
+        app.handle_key(ctrl_r);
+        assert!(app.buffer.readonly);
+        assert!(app.status_message.is_some());
+    }
+
+    #[test]
+    fn readonly_edit_attempts_only_flash_a_status_message() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(
+            TextBuffer::from_text("text".to_string(), None, true),
+            dir.path(),
+        );
+        let attempts = [
+            KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::F(3), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL),
+            KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL),
+            KeyEvent::new(
+                KeyCode::Char('S'),
+                KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+            ),
+        ];
+        for key in attempts {
+            app.handle_key(key);
+            assert!(matches!(app.overlay, Overlay::None), "{key:?}");
+            assert!(app.status_message.is_some(), "{key:?}");
+        }
+        assert_eq!(app.buffer.as_string(), "text");
+        assert!(app.buffer.char_colors.is_empty());
+
+        app.handle_key(KeyEvent::new(KeyCode::Right, KeyModifiers::NONE));
+        assert_eq!(app.buffer.cursor.col, 1);
+        assert!(app.status_message.is_none());
+    }
+
+    #[test]
+    fn eol_toggle_converts_line_endings_on_save() {
+        let dir = tempdir().expect("tempdir");
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(notes.join("work")).expect("category");
+        let path = notes.join("work").join("dos.txt");
+        fs::write(&path, "a\r\nb\r\n").expect("write");
+        let mut app = testing::app(TextBuffer::new(None, false), &notes);
+        app.open_document(&path).expect("open");
+        assert_eq!(app.eol, EolStyle::Crlf);
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('e'), KeyModifiers::CONTROL));
+        assert_eq!(app.eol, EolStyle::Lf);
+        assert!(app.buffer.dirty);
+        app.apply_command(Command::Save);
+        assert_eq!(fs::read_to_string(&path).expect("read"), "a\nb\n");
+    }
+
+    #[test]
+    fn help_overlay_scrolls_within_bounds() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(TextBuffer::new(None, false), dir.path());
+        app.update_viewport_from_size(80, 20);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        app.handle_key(key(KeyCode::F(1)));
+        app.handle_key(key(KeyCode::Down));
+        assert!(matches!(app.overlay, Overlay::Help { scroll: 1 }));
+
+        let max = crate::ui::help_max_scroll(20, Keymap::Default);
+        assert!(max > 0);
+        for _ in 0..10 {
+            app.handle_key(key(KeyCode::PageDown));
+        }
+        assert!(matches!(app.overlay, Overlay::Help { scroll } if scroll == max));
+        app.handle_key(key(KeyCode::Up));
+        assert!(matches!(app.overlay, Overlay::Help { scroll } if scroll == max - 1));
+        app.handle_key(key(KeyCode::Esc));
+        assert!(matches!(app.overlay, Overlay::None));
+    }
+
+    #[test]
+    fn outline_starts_at_the_current_section_and_jumps_to_headings() {
+        let dir = tempdir().expect("tempdir");
+        let mut app = testing::app(
+            TextBuffer::from_text(
+                "# One\ntext\n## Two\nmore\n# Three\n".to_string(),
+                None,
+                false,
+            ),
+            dir.path(),
+        );
+        app.update_viewport_from_size(80, 20);
+        app.buffer.goto_line(4);
+        let ctrl_shift_h = KeyEvent::new(
+            KeyCode::Char('H'),
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        );
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+        app.handle_key(ctrl_shift_h);
+        assert!(matches!(app.overlay, Overlay::Outline { selected: 1, .. }));
+        app.handle_key(key(KeyCode::Down));
+        app.handle_key(key(KeyCode::Enter));
+        assert!(matches!(app.overlay, Overlay::None));
+        assert_eq!(app.buffer.cursor.line, 4);
+        assert_eq!(app.buffer.cursor.col, 0);
+
+        app.handle_key(ctrl_shift_h);
+        app.handle_key(key(KeyCode::Home));
+        app.handle_key(key(KeyCode::Esc));
+        assert!(matches!(app.overlay, Overlay::None));
+        assert_eq!(app.buffer.cursor.line, 4);
+    }
+}
@@ -1,4 +1,5 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
 use std::path::PathBuf;
 
 use ropey::Rope;
@@ -26,10 +27,20 @@ pub struct SearchState {
     pub current: Option<usize>,
 }
 
+/// A heading found by [`TextBuffer::outline`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineItem {
+    pub level: usize,
+    pub text: String,
+    pub line: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct TextBuffer {
     rope: Rope,
     pub cursor: Cursor,
+    /// Secondary cursors; edits and motions also run at each of these.
+    pub extra_cursors: Vec<Cursor>,
     pub viewport: Viewport,
     preferred_col: usize,
     pub dirty: bool,
@@ -37,6 +48,13 @@ pub struct TextBuffer {
     pub path: Option<PathBuf>,
     pub char_colors: BTreeMap<usize, ColorId>,
     pub active_color: Option<ColorId>,
+    pub overwrite: bool,
+    pub auto_pairs: bool,
+    pub smart_quotes: bool,
+    word_starts: HashMap<usize, Vec<usize>>,
+    generation: u64,
+    saved_generation: u64,
+    append_floor: Option<usize>,
 }
 
 impl TextBuffer {
@@ -60,6 +78,14 @@ impl TextBuffer {
             path,
             char_colors: BTreeMap::new(),
             active_color: None,
+            overwrite: false,
+            auto_pairs: false,
+            smart_quotes: false,
+            extra_cursors: Vec::new(),
+            word_starts: HashMap::new(),
+            generation: 0,
+            saved_generation: 0,
+            append_floor: None,
         }
     }
 
@@ -67,6 +93,10 @@ impl TextBuffer {
         self.rope.len_lines().max(1)
     }
 
+    pub fn len_chars(&self) -> usize {
+        self.rope.len_chars()
+    }
+
     pub fn line_len_chars(&self, line: usize) -> usize {
         if line >= self.line_count() {
             return 0;
@@ -92,10 +122,23 @@ impl TextBuffer {
         s
     }
 
+    pub fn line_slice(&self, line: usize, start_col: usize, max_chars: usize) -> String {
+        let len = self.line_len_chars(line);
+        if line >= self.line_count() || start_col >= len {
+            return String::new();
+        }
+        let end = (start_col + max_chars).min(len);
+        self.rope.line(line).slice(start_col..end).to_string()
+    }
+
     pub fn as_string(&self) -> String {
         self.rope.to_string()
     }
 
+    pub fn chunks(&self) -> impl Iterator<Item = &str> {
+        self.rope.chunks()
+    }
+
     fn line_col_to_char_idx(&self, line: usize, col: usize) -> usize {
         let l = line.min(self.line_count().saturating_sub(1));
         let c = col.min(self.line_len_chars(l));
@@ -107,6 +150,21 @@ impl TextBuffer {
         self.rope.line_to_char(l)
     }
 
+    pub fn line_start_char_indices(&self, top: usize, count: usize) -> Vec<usize> {
+        let line_count = self.line_count();
+        if top >= line_count {
+            return Vec::new();
+        }
+        let count = count.min(line_count - top);
+        let mut out = Vec::with_capacity(count);
+        let mut idx = self.rope.line_to_char(top);
+        for line in self.rope.lines_at(top).take(count) {
+            out.push(idx);
+            idx += line.len_chars();
+        }
+        out
+    }
+
     pub fn cursor_char_index(&self) -> usize {
         self.line_col_to_char_idx(self.cursor.line, self.cursor.col)
     }
@@ -154,6 +212,90 @@ impl TextBuffer {
         }
     }
 
+    pub fn move_word_left(&mut self) {
+        let col = self.cursor.col;
+        let prev = self
+            .word_starts_for(self.cursor.line)
+            .iter()
+            .rev()
+            .find(|&&start| start < col)
+            .copied();
+        match prev {
+            Some(start) => self.cursor.col = start,
+            None if col > 0 => self.cursor.col = 0,
+            None if self.cursor.line > 0 => {
+                self.cursor.line -= 1;
+                self.cursor.col = self.line_len_chars(self.cursor.line);
+            }
+            None => {}
+        }
+        self.preferred_col = self.cursor.col;
+        self.ensure_cursor_visible();
+    }
+
+    pub fn move_word_right(&mut self) {
+        let col = self.cursor.col;
+        let next = self
+            .word_starts_for(self.cursor.line)
+            .iter()
+            .find(|&&start| start > col)
+            .copied();
+        let len = self.line_len_chars(self.cursor.line);
+        match next {
+            Some(start) => self.cursor.col = start,
+            None if col < len => self.cursor.col = len,
+            None if self.cursor.line + 1 < self.line_count() => {
+                self.cursor.line += 1;
+                self.cursor.col = 0;
+            }
+            None => {}
+        }
+        self.preferred_col = self.cursor.col;
+        self.ensure_cursor_visible();
+    }
+
+    fn word_starts_for(&mut self, line: usize) -> &[usize] {
+        if !self.word_starts.contains_key(&line) {
+            let mut starts = Vec::new();
+            let mut prev_word = false;
+            for (col, c) in self.line_text(line).chars().enumerate() {
+                let is_word = c.is_alphanumeric() || c == '_';
+                if is_word && !prev_word {
+                    starts.push(col);
+                }
+                prev_word = is_word;
+            }
+            self.word_starts.insert(line, starts);
+        }
+        &self.word_starts[&line]
+    }
+
+    fn text_changed(&mut self) {
+        self.bump_generation();
+        self.word_starts.clear();
+    }
+
+    fn bump_generation(&mut self) {
+        self.generation += 1;
+        self.dirty = true;
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn restore_generation(&mut self, generation: u64) {
+        self.generation = generation;
+        self.dirty = generation != self.saved_generation;
+    }
+
+    pub fn center_viewport(&mut self) {
+        self.clamp_cursor();
+        let half = self.viewport.height as usize / 2;
+        self.viewport.top_line = self.cursor.line.saturating_sub(half);
+        self.ensure_cursor_visible();
+    }
+
     pub fn move_left(&mut self) {
         if self.cursor.col > 0 {
             self.cursor.col -= 1;
@@ -198,11 +340,28 @@ impl TextBuffer {
     }
 
     pub fn move_home(&mut self) {
-        self.cursor.col = 0;
-        self.preferred_col = 0;
+        let first_non_blank = self.first_non_blank_col(self.cursor.line);
+        self.cursor.col = if self.cursor.col == first_non_blank {
+            0
+        } else {
+            first_non_blank
+        };
+        self.preferred_col = self.cursor.col;
         self.ensure_cursor_visible();
     }
 
+    fn first_non_blank_col(&self, line: usize) -> usize {
+        if line >= self.line_count() {
+            return 0;
+        }
+        self.rope
+            .line(line)
+            .chars()
+            .take(self.line_len_chars(line))
+            .take_while(|c| c.is_whitespace())
+            .count()
+    }
+
     pub fn move_end(&mut self) {
         self.cursor.col = self.line_len_chars(self.cursor.line);
         self.preferred_col = self.cursor.col;
@@ -211,19 +370,43 @@ impl TextBuffer {
 
     pub fn page_up(&mut self) {
         let amount = self.viewport.height.saturating_sub(1) as usize;
+        self.scroll_cursor_up(amount);
+    }
+
+    pub fn page_down(&mut self) {
+        let amount = self.viewport.height.saturating_sub(1) as usize;
+        self.scroll_cursor_down(amount);
+    }
+
+    pub fn half_page_up(&mut self) {
+        let amount = (self.viewport.height as usize / 2).max(1);
+        self.scroll_cursor_up(amount);
+    }
+
+    pub fn half_page_down(&mut self) {
+        let amount = (self.viewport.height as usize / 2).max(1);
+        self.scroll_cursor_down(amount);
+    }
+
+    fn scroll_cursor_up(&mut self, amount: usize) {
+        self.extra_cursors.clear();
+        let row = self.cursor.line.saturating_sub(self.viewport.top_line);
         self.cursor.line = self.cursor.line.saturating_sub(amount);
         self.cursor.col = self
             .preferred_col
             .min(self.line_len_chars(self.cursor.line));
+        self.viewport.top_line = self.cursor.line.saturating_sub(row);
         self.ensure_cursor_visible();
     }
 
-    pub fn page_down(&mut self) {
-        let amount = self.viewport.height.saturating_sub(1) as usize;
+    fn scroll_cursor_down(&mut self, amount: usize) {
+        self.extra_cursors.clear();
+        let row = self.cursor.line.saturating_sub(self.viewport.top_line);
         self.cursor.line = (self.cursor.line + amount).min(self.line_count().saturating_sub(1));
         self.cursor.col = self
             .preferred_col
             .min(self.line_len_chars(self.cursor.line));
+        self.viewport.top_line = self.cursor.line.saturating_sub(row);
         self.ensure_cursor_visible();
     }
 
@@ -231,17 +414,78 @@ impl TextBuffer {
         let target = line_1based
             .saturating_sub(1)
             .min(self.line_count().saturating_sub(1));
+        self.extra_cursors.clear();
         self.cursor.line = target;
         self.cursor.col = self.cursor.col.min(self.line_len_chars(target));
         self.preferred_col = self.cursor.col;
         self.ensure_cursor_visible();
     }
 
+    pub fn append_only(&self) -> bool {
+        self.append_floor.is_some()
+    }
+
+    pub fn set_append_only(&mut self, enabled: bool) {
+        self.append_floor = enabled.then(|| self.rope.len_chars());
+        if enabled {
+            self.move_to_document_end();
+        }
+    }
+
+    fn prepare_append(&mut self) {
+        let Some(floor) = self.append_floor else {
+            return;
+        };
+        let len = self.rope.len_chars();
+        if len == floor && len > 0 && self.rope.char(len - 1) != '\n' {
+            self.rope.insert_char(len, '\n');
+            self.append_floor = Some(len + 1);
+            self.text_changed();
+        }
+        self.move_to_document_end();
+    }
+
+    /// Inserts `c`. With [`smart_quotes`](Self::smart_quotes), straight quotes
+    /// become typographic ones. With [`auto_pairs`](Self::auto_pairs), an
+    /// opening bracket or quote also inserts its closer, and typing a closer
+    /// that is already under the cursor steps over it.
     pub fn insert_char(&mut self, c: char) {
         if self.readonly {
             return;
         }
+        let c = if self.smart_quotes && matches!(c, '"' | '\'') {
+            self.prepare_append();
+            smart_quote(c, self.char_before_cursor())
+        } else {
+            c
+        };
+        if self.pairing() {
+            let prev = self.char_before_cursor();
+            let next = self.char_at_cursor();
+            if next == Some(c) && (is_closer(c) || pair_for(c) == Some(c)) {
+                self.move_right();
+                return;
+            }
+            if let Some(close) = pair_for(c)
+                && should_pair(c, prev, next)
+            {
+                self.insert_one(c);
+                self.insert_one(close);
+                self.cursor.col -= 1;
+                self.preferred_col = self.cursor.col;
+                return;
+            }
+        }
+        self.insert_one(c);
+    }
+
+    fn insert_one(&mut self, c: char) {
+        self.prepare_append();
         let idx = self.line_col_to_char_idx(self.cursor.line, self.cursor.col);
+        if self.overwrite && self.cursor.col < self.line_len_chars(self.cursor.line) {
+            self.rope.remove(idx..idx + 1);
+            self.shift_char_colors_after_remove(idx, 1);
+        }
         self.rope.insert_char(idx, c);
         self.shift_char_colors_after_insert(idx, 1);
         if let Some(color) = self.active_color {
@@ -249,21 +493,52 @@ impl TextBuffer {
         }
         self.cursor.col += 1;
         self.preferred_col = self.cursor.col;
-        self.dirty = true;
+        self.text_changed();
         self.ensure_cursor_visible();
     }
 
+    pub fn insert_str(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n");
+        let colors = match self.active_color {
+            Some(color) => text
+                .chars()
+                .enumerate()
+                .filter(|(_, c)| *c != '\n')
+                .map(|(i, _)| (i, color))
+                .collect(),
+            None => BTreeMap::new(),
+        };
+        self.insert_colored_str(&text, &colors);
+    }
+
+    pub fn insert_colored_str(&mut self, text: &str, colors: &BTreeMap<usize, ColorId>) {
+        if self.readonly || text.is_empty() {
+            return;
+        }
+        self.prepare_append();
+        let count = text.chars().count();
+        let idx = self.line_col_to_char_idx(self.cursor.line, self.cursor.col);
+        self.rope.insert(idx, text);
+        self.shift_char_colors_after_insert(idx, count);
+        for (&offset, &color) in colors.range(..count) {
+            self.char_colors.insert(idx + offset, color);
+        }
+        self.text_changed();
+        self.set_cursor_char_index(idx + count);
+    }
+
     pub fn insert_newline(&mut self) {
         if self.readonly {
             return;
         }
+        self.prepare_append();
         let idx = self.line_col_to_char_idx(self.cursor.line, self.cursor.col);
         self.rope.insert_char(idx, '\n');
         self.shift_char_colors_after_insert(idx, 1);
         self.cursor.line += 1;
         self.cursor.col = 0;
         self.preferred_col = 0;
-        self.dirty = true;
+        self.text_changed();
         self.ensure_cursor_visible();
     }
 
@@ -271,13 +546,32 @@ impl TextBuffer {
         if self.readonly {
             return;
         }
+        if self.pairing()
+            && let (Some(prev), Some(next)) = (self.char_before_cursor(), self.char_at_cursor())
+            && pair_for(prev) == Some(next)
+        {
+            let idx = self.cursor_char_index();
+            self.rope.remove(idx - 1..idx + 1);
+            self.shift_char_colors_after_remove(idx - 1, 2);
+            self.cursor.col -= 1;
+            self.preferred_col = self.cursor.col;
+            self.text_changed();
+            self.ensure_cursor_visible();
+            return;
+        }
+        if let Some(floor) = self.append_floor {
+            self.move_to_document_end();
+            if self.cursor_char_index() <= floor {
+                return;
+            }
+        }
         if self.cursor.col > 0 {
             let idx = self.line_col_to_char_idx(self.cursor.line, self.cursor.col);
             self.rope.remove(idx - 1..idx);
             self.shift_char_colors_after_remove(idx - 1, 1);
             self.cursor.col -= 1;
             self.preferred_col = self.cursor.col;
-            self.dirty = true;
+            self.text_changed();
         } else if self.cursor.line > 0 {
             let prev_len = self.line_len_chars(self.cursor.line - 1);
             let idx = self.line_col_to_char_idx(self.cursor.line, self.cursor.col);
@@ -286,22 +580,105 @@ impl TextBuffer {
             self.cursor.line -= 1;
             self.cursor.col = prev_len;
             self.preferred_col = self.cursor.col;
-            self.dirty = true;
+            self.text_changed();
         }
         self.ensure_cursor_visible();
     }
 
-    pub fn delete(&mut self) {
+    /// Deletes back to the previous word start, like readline's Alt+Backspace.
+    pub fn delete_word_back(&mut self) {
         if self.readonly {
             return;
         }
+        if self.append_floor.is_some() {
+            self.move_to_document_end();
+        }
+        let end = self.cursor_char_index();
+        self.move_word_left();
+        let start = self
+            .cursor_char_index()
+            .max(self.append_floor.unwrap_or(0))
+            .min(end);
+        if start < end {
+            self.rope.remove(start..end);
+            self.shift_char_colors_after_remove(start, end - start);
+            self.text_changed();
+        }
+        self.set_cursor_char_index(start);
+    }
+
+    /// Deletes to the end of the line, or joins the next line when already
+    /// there (Emacs `Ctrl+K`).
+    pub fn delete_to_line_end(&mut self) {
+        if self.readonly || self.append_only() {
+            return;
+        }
+        let start = self.cursor_char_index();
+        let len = self.line_len_chars(self.cursor.line);
+        let end = if self.cursor.col < len {
+            start + (len - self.cursor.col)
+        } else {
+            (start + 1).min(self.rope.len_chars())
+        };
+        if start < end {
+            self.rope.remove(start..end);
+            self.shift_char_colors_after_remove(start, end - start);
+            self.text_changed();
+        }
+        self.ensure_cursor_visible();
+    }
+
+    /// Removes the cursor line together with its line break.
+    pub fn delete_line(&mut self) {
+        if self.readonly || self.append_only() {
+            return;
+        }
+        let line = self.cursor.line;
+        let total = self.rope.len_chars();
+        let mut start = self.rope.line_to_char(line);
+        let end = if line + 1 < self.rope.len_lines() {
+            self.rope.line_to_char(line + 1)
+        } else {
+            total
+        };
+        if end == total && start > 0 && end == start + self.line_len_chars(line) {
+            start -= 1;
+        }
+        if start < end {
+            self.rope.remove(start..end);
+            self.shift_char_colors_after_remove(start, end - start);
+            self.text_changed();
+        }
+        self.cursor.line = line.min(self.line_count().saturating_sub(1));
+        self.cursor.col = 0;
+        self.preferred_col = 0;
+        self.ensure_cursor_visible();
+    }
+
+    /// Swaps the whole text for `text`, keeping the cursor where it still fits.
+    /// Character colors are dropped since their offsets no longer apply.
+    pub fn replace_text(&mut self, text: &str) {
+        if self.readonly || self.append_only() {
+            return;
+        }
+        self.rope = Rope::from_str(text);
+        self.char_colors.clear();
+        self.extra_cursors.clear();
+        self.text_changed();
+        self.ensure_cursor_visible();
+    }
+
+    pub fn delete(&mut self) {
+        if self.readonly || self.append_only() {
+            return;
+        }
         let idx = self.line_col_to_char_idx(self.cursor.line, self.cursor.col);
         if idx >= self.rope.len_chars() {
             return;
         }
         self.rope.remove(idx..idx + 1);
         self.shift_char_colors_after_remove(idx, 1);
-        self.dirty = true;
+        self.text_changed();
         self.ensure_cursor_visible();
     }
 
@@ -347,18 +724,16 @@ impl TextBuffer {
     pub fn set_current_char_color(&mut self, color: Option<ColorId>) {
         self.active_color = color;
         let idx = self.cursor_char_index();
-        if idx < self.rope.len_chars() {
-            if self.rope.char(idx) != '\n' {
-                match color {
-                    Some(id) => {
-                        self.char_colors.insert(idx, id);
-                    }
-                    None => {
-                        self.char_colors.remove(&idx);
-                    }
+        if idx < self.rope.len_chars() && self.rope.char(idx) != '\n' {
+            match color {
+                Some(id) => {
+                    self.char_colors.insert(idx, id);
+                }
+                None => {
+                    self.char_colors.remove(&idx);
                 }
-                self.dirty = true;
             }
+            self.bump_generation();
         }
     }
 
@@ -367,7 +742,189 @@ impl TextBuffer {
         self.char_colors.get(&idx).copied().or(self.active_color)
     }
 
-    pub fn set_char_colors(&mut self, colors: BTreeMap<usize, ColorId>) {
+    /// Lines starting with one to six `#` and a space, outside fenced code
+    /// blocks, in document order.
+    pub fn outline(&self) -> Vec<OutlineItem> {
+        let mut items = Vec::new();
+        let mut fenced = false;
+        for (line, text) in self.rope.lines().enumerate() {
+            let text = text.to_string();
+            let trimmed = text.trim_start();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                fenced = !fenced;
+                continue;
+            }
+            let level = text.chars().take_while(|c| *c == '#').count();
+            if fenced || !(1..=6).contains(&level) {
+                continue;
+            }
+            let rest = &text[level..];
+            if !rest.is_empty() && !rest.starts_with([' ', '\t', '\n', '\r']) {
+                continue;
+            }
+            items.push(OutlineItem {
+                level,
+                text: rest.trim().trim_end_matches('#').trim_end().to_string(),
+                line,
+            });
+        }
+        items
+    }
+
+    /// The bracket at the cursor (or just before it) and its partner, as char
+    /// indices. Only `within` is searched, so off-screen or unbalanced
+    /// brackets give `None`.
+    pub fn matching_bracket(&self, within: Range<usize>) -> Option<(usize, usize)> {
+        let at = self.cursor_char_index();
+        let candidates = [Some(at), at.checked_sub(1)];
+        candidates.into_iter().flatten().find_map(|idx| {
+            if !within.contains(&idx) || idx >= self.rope.len_chars() {
+                return None;
+            }
+            let c = self.rope.char(idx);
+            let partner = bracket_partner(c)?;
+            let forward = pair_for(c) == Some(partner);
+            let mut depth = 0usize;
+            let mut scan = |i: usize| {
+                let ch = self.rope.char(i);
+                if ch == c {
+                    depth += 1;
+                } else if ch == partner {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some((idx, i));
+                    }
+                }
+                None
+            };
+            if forward {
+                (idx..within.end.min(self.rope.len_chars())).find_map(&mut scan)
+            } else {
+                (within.start..=idx).rev().find_map(&mut scan)
+            }
+        })
+    }
+
+    fn pairing(&self) -> bool {
+        self.auto_pairs && !self.overwrite && !self.append_only()
+    }
+
+    fn char_before_cursor(&self) -> Option<char> {
+        (self.cursor.col > 0).then(|| self.rope.char(self.cursor_char_index() - 1))
+    }
+
+    fn char_at_cursor(&self) -> Option<char> {
+        (self.cursor.col < self.line_len_chars(self.cursor.line))
+            .then(|| self.rope.char(self.cursor_char_index()))
+    }
+
+    /// Flips the `[ ]`/`[x]` checkbox that follows the line's indent and
+    /// bullet (`-`, `*`, `+` or `1.`), or inserts `[ ] ` there when there is none.
+    pub fn toggle_checkbox(&mut self) {
+        if self.readonly || self.append_only() {
+            return;
+        }
+        let line = self.cursor.line;
+        let chars: Vec<char> = self.line_text(line).chars().collect();
+        let at = checkbox_column(&chars);
+        let start = self.line_start_char_idx(line) + at;
+        match chars.get(at..at + 3) {
+            Some(['[', mark @ (' ' | 'x' | 'X'), ']']) => {
+                let flipped = if *mark == ' ' { 'x' } else { ' ' };
+                self.rope.remove(start + 1..start + 2);
+                self.rope.insert_char(start + 1, flipped);
+            }
+            _ => {
+                self.rope.insert(start, "[ ] ");
+                self.shift_char_colors_after_insert(start, 4);
+                if self.cursor.col >= at {
+                    self.cursor.col += 4;
+                    self.preferred_col = self.cursor.col;
+                }
+            }
+        }
+        self.text_changed();
+        self.ensure_cursor_visible();
+    }
+
+    /// Adds a cursor on the line below the lowest cursor, at the primary
+    /// cursor's column or that line's end when it is shorter.
+    pub fn add_cursor_below(&mut self) {
+        if self.append_only() {
+            return;
+        }
+        let lowest = self
+            .extra_cursors
+            .iter()
+            .map(|c| c.line)
+            .fold(self.cursor.line, usize::max);
+        let line = lowest + 1;
+        if line < self.line_count() {
+            let col = self.cursor.col.min(self.line_len_chars(line));
+            self.extra_cursors.push(Cursor { line, col });
+        }
+    }
+
+    /// Runs `edit` at the primary cursor and at every extra cursor, from the
+    /// highest char index to the lowest so an edit never shifts a position
+    /// that is still to be visited. Cursors that land on the same spot merge.
+    pub fn at_each_cursor(&mut self, mut edit: impl FnMut(&mut Self)) {
+        if self.extra_cursors.is_empty() {
+            edit(self);
+            return;
+        }
+        let viewport = self.viewport;
+        let extras = std::mem::take(&mut self.extra_cursors);
+        let mut pending: Vec<(usize, bool)> = extras
+            .iter()
+            .map(|c| (self.line_col_to_char_idx(c.line, c.col), false))
+            .chain([(self.cursor_char_index(), true)])
+            .collect();
+        pending.sort_unstable_by(|a, b| b.cmp(a));
+        pending.dedup_by_key(|(idx, _)| *idx);
+
+        let mut done: Vec<(usize, bool)> = Vec::with_capacity(pending.len());
+        for (idx, primary) in pending {
+            let before = self.rope.len_chars();
+            self.set_cursor_char_index(idx);
+            edit(self);
+            let delta = self.rope.len_chars() as isize - before as isize;
+            for (pos, _) in &mut done {
+                *pos = pos.saturating_add_signed(delta);
+            }
+            done.push((self.cursor_char_index(), primary));
+        }
+
+        let len = self.rope.len_chars();
+        let primary = done.iter().find(|(_, p)| *p).map_or(0, |(pos, _)| *pos);
+        let mut extras: Vec<usize> = done
+            .into_iter()
+            .filter(|&(_, p)| !p)
+            .map(|(pos, _)| pos.min(len))
+            .filter(|&pos| pos != primary)
+            .collect();
+        extras.sort_unstable();
+        extras.dedup();
+        self.extra_cursors = extras
+            .into_iter()
+            .map(|pos| {
+                let line = self.rope.char_to_line(pos);
+                Cursor {
+                    line,
+                    col: pos - self.rope.line_to_char(line),
+                }
+            })
+            .collect();
+        self.viewport = viewport;
+        self.set_cursor_char_index(primary);
+    }
+
+    pub fn toggle_overwrite(&mut self) {
+        self.overwrite = !self.overwrite;
+    }
+
+    pub fn set_char_colors(&mut self, mut colors: BTreeMap<usize, ColorId>) {
+        colors.split_off(&self.rope.len_chars());
         self.char_colors = colors;
     }
 
@@ -415,25 +972,174 @@ impl TextBuffer {
         out
     }
 
+    pub fn next_match_after(&self, pos: usize, query: &str) -> Option<Range<usize>> {
+        let needle: Vec<char> = query.chars().map(fold_char).collect();
+        let len = self.rope.len_chars();
+        if needle.is_empty() || needle.len() > len {
+            return None;
+        }
+        let first = needle[0];
+        let mut chars = self.rope.chars_at(pos.min(len));
+        let mut start = pos.min(len);
+        while start + needle.len() <= len {
+            let c = chars.next()?;
+            if fold_char(c) == first
+                && self
+                    .rope
+                    .chars_at(start + 1)
+                    .zip(&needle[1..])
+                    .all(|(c, &n)| fold_char(c) == n)
+            {
+                return Some(start..start + needle.len());
+            }
+            start += 1;
+        }
+        None
+    }
+
+    pub fn set_cursor_char_index(&mut self, char_idx: usize) {
+        let idx = char_idx.min(self.rope.len_chars());
+        self.cursor.line = self.rope.char_to_line(idx);
+        self.cursor.col = idx - self.rope.line_to_char(self.cursor.line);
+        self.preferred_col = self.cursor.col;
+        self.ensure_cursor_visible();
+    }
+
+    pub fn move_to_document_end(&mut self) {
+        self.set_cursor_char_index(self.rope.len_chars());
+    }
+
+    pub fn count_occurrences(&self, query: &str) -> usize {
+        if query.is_empty() {
+            return 0;
+        }
+        let query_lower = query.to_lowercase();
+        (0..self.line_count())
+            .map(|line| {
+                self.line_text(line)
+                    .to_lowercase()
+                    .matches(&query_lower)
+                    .count()
+            })
+            .sum()
+    }
+
+    pub fn word_at_cursor(&self) -> Option<String> {
+        let chars: Vec<char> = self.line_text(self.cursor.line).chars().collect();
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let col = self.cursor.col.min(chars.len());
+        let mut start = col;
+        while start > 0 && is_word(chars[start - 1]) {
+            start -= 1;
+        }
+        let mut end = col;
+        while end < chars.len() && is_word(chars[end]) {
+            end += 1;
+        }
+        if start == end {
+            None
+        } else {
+            Some(chars[start..end].iter().collect())
+        }
+    }
+
     pub fn set_text_from_string(&mut self, text: String) {
         self.rope = Rope::from_str(&text);
         self.cursor = Cursor { line: 0, col: 0 };
+        self.extra_cursors.clear();
         self.viewport.top_line = 0;
         self.viewport.left_col = 0;
         self.preferred_col = 0;
         self.char_colors.clear();
         self.active_color = None;
+        self.word_starts.clear();
+        self.saved_generation = self.generation;
         self.dirty = false;
     }
 
+    pub fn mark_dirty(&mut self) {
+        self.bump_generation();
+    }
+
     pub fn mark_saved(&mut self) {
+        self.saved_generation = self.generation;
         self.dirty = false;
     }
 }
 
+fn pair_for(open: char) -> Option<char> {
+    match open {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '"' | '\'' | '`' => Some(open),
+        '“' => Some('”'),
+        '‘' => Some('’'),
+        _ => None,
+    }
+}
+
+/// Column just past a line's indent and list bullet.
+fn checkbox_column(chars: &[char]) -> usize {
+    let indent = chars.iter().take_while(|c| c.is_whitespace()).count();
+    let rest = &chars[indent..];
+    let digits = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+    let bullet = match rest {
+        ['-' | '*' | '+', ' ', ..] => 2,
+        _ if digits > 0 && matches!(rest.get(digits..digits + 2), Some(['.' | ')', ' '])) => {
+            digits + 2
+        }
+        _ => 0,
+    };
+    indent + bullet
+}
+
+fn bracket_partner(c: char) -> Option<char> {
+    match c {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        ')' => Some('('),
+        ']' => Some('['),
+        '}' => Some('{'),
+        _ => None,
+    }
+}
+
+fn is_closer(c: char) -> bool {
+    matches!(c, ')' | ']' | '}' | '”' | '’')
+}
+
+/// Typographic form of a straight quote: opening at the line start or after
+/// whitespace, an opening bracket or a dash, closing (or an apostrophe)
+/// otherwise.
+fn smart_quote(c: char, prev: Option<char>) -> char {
+    let opening = prev.is_none_or(|p| {
+        p.is_whitespace() || matches!(p, '(' | '[' | '{' | '-' | '–' | '—' | '“' | '‘')
+    });
+    match (c, opening) {
+        ('"', true) => '“',
+        ('"', false) => '”',
+        (_, true) => '‘',
+        (_, false) => '’',
+    }
+}
+
+/// Pairs only before whitespace, a closer or the line end; quotes also not
+/// right after a word character, so `don't` stays a single quote.
+fn should_pair(open: char, prev: Option<char>, next: Option<char>) -> bool {
+    let next_ok = next.is_none_or(|n| n.is_whitespace() || is_closer(n));
+    let is_quote = pair_for(open) == Some(open);
+    next_ok && !(is_quote && prev.is_some_and(|p| p.is_alphanumeric() || p == open))
+}
+
+fn fold_char(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::TextBuffer;
+    use super::{Cursor, TextBuffer};
 
     #[test]
     fn insert_and_backspace_work() {
@@ -470,6 +1176,498 @@ mod tests {
         assert_eq!(b.char_color(1), Some(5));
     }
 
+    #[test]
+    fn overwrite_replaces_char_under_cursor() {
+        let mut b = TextBuffer::from_text("abc\nd".into(), None, false);
+        b.toggle_overwrite();
+        b.insert_char('x');
+        b.insert_char('y');
+        assert_eq!(b.as_string(), "xyc\nd");
+
+        b.move_end();
+        b.insert_char('z');
+        assert_eq!(b.as_string(), "xycz\nd");
+        assert_eq!(b.line_count(), 2);
+    }
+
+    #[test]
+    fn home_toggles_between_first_non_blank_and_column_zero() {
+        let mut b = TextBuffer::from_text("    abc".into(), None, false);
+        b.move_end();
+        b.move_home();
+        assert_eq!(b.cursor.col, 4);
+        b.move_home();
+        assert_eq!(b.cursor.col, 0);
+        b.move_home();
+        assert_eq!(b.cursor.col, 4);
+    }
+
+    #[test]
+    fn page_down_and_up_keep_cursor_row() {
+        let text = (0..100)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut b = TextBuffer::from_text(text, None, false);
+        b.set_viewport_size(80, 10);
+        b.goto_line(4);
+        assert_eq!(b.viewport.top_line, 0);
+
+        b.page_down();
+        assert_eq!(b.cursor.line, 12);
+        assert_eq!(b.cursor.line - b.viewport.top_line, 3);
+
+        b.page_up();
+        assert_eq!(b.cursor.line, 3);
+        assert_eq!(b.viewport.top_line, 0);
+    }
+
+    #[test]
+    fn half_page_moves_by_half_the_viewport() {
+        let text = (0..100)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut b = TextBuffer::from_text(text, None, false);
+        b.set_viewport_size(80, 10);
+        b.half_page_down();
+        assert_eq!(b.cursor.line, 5);
+        assert_eq!(b.viewport.top_line, 5);
+        b.half_page_up();
+        assert_eq!(b.cursor.line, 0);
+        assert_eq!(b.viewport.top_line, 0);
+    }
+
+    #[test]
+    fn center_viewport_puts_cursor_mid_screen() {
+        let text = (0..100)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut b = TextBuffer::from_text(text, None, false);
+        b.set_viewport_size(80, 10);
+        b.goto_line(51);
+        b.center_viewport();
+        assert_eq!(b.viewport.top_line, 45);
+
+        b.goto_line(2);
+        b.center_viewport();
+        assert_eq!(b.viewport.top_line, 0);
+    }
+
+    #[test]
+    fn counts_word_under_cursor_case_insensitively() {
+        let mut b = TextBuffer::from_text("Todo: todo\nnothing\nTODO todo".into(), None, false);
+        b.move_right();
+        assert_eq!(b.word_at_cursor().as_deref(), Some("Todo"));
+        assert_eq!(b.count_occurrences("Todo"), 4);
+        assert_eq!(b.cursor.col, 1);
+
+        b.move_end();
+        b.move_left();
+        b.move_left();
+        b.move_left();
+        b.move_left();
+        b.move_left();
+        assert_eq!(b.word_at_cursor(), None);
+    }
+
+    #[test]
+    fn next_match_after_returns_char_ranges() {
+        let b = TextBuffer::from_text("ção Foo\nfoo bar".into(), None, false);
+        assert_eq!(b.next_match_after(0, "foo"), Some(4..7));
+        assert_eq!(b.next_match_after(5, "FOO"), Some(8..11));
+        assert_eq!(b.next_match_after(9, "foo"), None);
+        assert_eq!(b.next_match_after(0, "ÇÃO"), Some(0..3));
+        assert_eq!(b.next_match_after(0, ""), None);
+    }
+
+    #[test]
+    fn batched_line_starts_match_single_lookups() {
+        let b = TextBuffer::from_text("ab\nçde\n\nf\n".into(), None, false);
+        let starts = b.line_start_char_indices(1, 10);
+        let expected: Vec<usize> = (1..b.line_count())
+            .map(|l| b.line_start_char_idx(l))
+            .collect();
+        assert_eq!(starts, expected);
+        assert!(b.line_start_char_indices(b.line_count(), 3).is_empty());
+    }
+
+    #[test]
+    fn word_motions_jump_between_word_starts() {
+        let mut b = TextBuffer::from_text("foo  bar_baz, qux\nnext".into(), None, false);
+        b.move_word_right();
+        assert_eq!(b.cursor.col, 5);
+        b.move_word_right();
+        assert_eq!(b.cursor.col, 14);
+        b.move_word_right();
+        assert_eq!(b.cursor.col, 17);
+        b.move_word_right();
+        assert_eq!((b.cursor.line, b.cursor.col), (1, 0));
+
+        b.move_word_left();
+        assert_eq!((b.cursor.line, b.cursor.col), (0, 17));
+        b.move_word_left();
+        assert_eq!(b.cursor.col, 14);
+    }
+
+    #[test]
+    fn delete_word_back_removes_to_previous_word_start() {
+        let mut b = TextBuffer::from_text("one two\nthree".into(), None, false);
+        b.set_cursor_char_index(7);
+        b.delete_word_back();
+        assert_eq!(b.as_string(), "one \nthree");
+        assert_eq!(b.cursor.col, 4);
+
+        b.set_cursor_char_index(5);
+        b.delete_word_back();
+        assert_eq!(b.as_string(), "one three");
+        assert_eq!(b.cursor_char_index(), 4);
+
+        b.set_append_only(true);
+        b.delete_word_back();
+        assert_eq!(b.as_string(), "one three");
+    }
+
+    #[test]
+    fn delete_line_removes_the_line_and_its_break() {
+        let mut b = TextBuffer::from_text("one\ntwo\nthree".into(), None, false);
+        b.goto_line(2);
+        b.delete_line();
+        assert_eq!(b.as_string(), "one\nthree");
+        assert_eq!((b.cursor.line, b.cursor.col), (1, 0));
+
+        b.delete_line();
+        assert_eq!(b.as_string(), "one");
+        assert_eq!(b.cursor.line, 0);
+
+        b.delete_line();
+        assert_eq!(b.as_string(), "");
+    }
+
+    #[test]
+    fn extra_cursors_edit_every_line_and_merge_when_they_meet() {
+        let mut b = TextBuffer::from_text("a = 1\nb = 2\nc\nd = 4".into(), None, false);
+        b.set_viewport_size(40, 10);
+        b.move_end();
+        b.add_cursor_below();
+        b.add_cursor_below();
+        assert_eq!(
+            b.extra_cursors,
+            [Cursor { line: 1, col: 5 }, Cursor { line: 2, col: 1 }]
+        );
+
+        b.at_each_cursor(|b| b.insert_char(';'));
+        assert_eq!(b.as_string(), "a = 1;\nb = 2;\nc;\nd = 4");
+        b.at_each_cursor(TextBuffer::insert_newline);
+        assert_eq!(b.as_string(), "a = 1;\n\nb = 2;\n\nc;\n\nd = 4");
+        assert_eq!((b.cursor.line, b.cursor.col), (1, 0));
+        assert_eq!(
+            b.extra_cursors,
+            [Cursor { line: 3, col: 0 }, Cursor { line: 5, col: 0 }]
+        );
+
+        b.at_each_cursor(TextBuffer::backspace);
+        b.at_each_cursor(TextBuffer::move_home);
+        assert_eq!(b.as_string(), "a = 1;\nb = 2;\nc;\nd = 4");
+        assert_eq!(b.extra_cursors.len(), 2);
+        b.at_each_cursor(TextBuffer::delete_line);
+        assert_eq!(b.as_string(), "d = 4");
+        assert!(b.extra_cursors.is_empty());
+    }
+
+    #[test]
+    fn checkbox_toggles_after_indent_and_bullets() {
+        let mut b = TextBuffer::from_text("  - milk\n3. [x] eggs\nplain".into(), None, false);
+        b.set_viewport_size(40, 10);
+        b.set_char_colors([(4, 2)].into());
+        b.move_end();
+        b.toggle_checkbox();
+        assert_eq!(b.line_text(0), "  - [ ] milk");
+        assert_eq!(b.cursor.col, 12);
+        assert_eq!(b.char_color(8), Some(2));
+        b.toggle_checkbox();
+        assert_eq!(b.line_text(0), "  - [x] milk");
+        b.toggle_checkbox();
+        assert_eq!(b.line_text(0), "  - [ ] milk");
+
+        b.move_down();
+        b.toggle_checkbox();
+        assert_eq!(b.line_text(1), "3. [ ] eggs");
+        b.move_down();
+        b.move_home();
+        b.toggle_checkbox();
+        assert_eq!(b.line_text(2), "[ ] plain");
+        assert_eq!(b.cursor.col, 4);
+    }
+
+    #[test]
+    fn replace_text_clamps_the_cursor_and_drops_colors() {
+        let mut b = TextBuffer::from_text("one\ntwo\nthree".into(), None, false);
+        b.goto_line(3);
+        b.move_end();
+        b.set_current_char_color(Some(2));
+        b.insert_char('!');
+        b.replace_text("a\nbb");
+        assert_eq!(b.as_string(), "a\nbb");
+        assert_eq!((b.cursor.line, b.cursor.col), (1, 2));
+        assert!(b.dirty);
+        assert!(b.char_colors.is_empty());
+    }
+
+    #[test]
+    fn auto_pairs_insert_skip_and_delete_closers() {
+        let mut b = TextBuffer::new(None, false);
+        b.auto_pairs = true;
+        for c in "f(\"a".chars() {
+            b.insert_char(c);
+        }
+        assert_eq!(b.as_string(), "f(\"a\")");
+        assert_eq!(b.cursor.col, 4);
+        b.insert_char('"');
+        b.insert_char(')');
+        assert_eq!(b.as_string(), "f(\"a\")");
+        assert_eq!(b.cursor.col, 6);
+
+        b.insert_char(' ');
+        b.insert_char('[');
+        assert_eq!(b.as_string(), "f(\"a\") []");
+        b.backspace();
+        assert_eq!(b.as_string(), "f(\"a\") ");
+
+        b.insert_str("don");
+        b.insert_char('\'');
+        assert_eq!(b.as_string(), "f(\"a\") don'");
+        b.move_home();
+        b.insert_char('(');
+        assert_eq!(b.as_string(), "(f(\"a\") don'");
+    }
+
+    #[test]
+    fn smart_quotes_open_after_space_and_close_after_words() {
+        let mut b = TextBuffer::new(None, false);
+        b.smart_quotes = true;
+        for c in "\"Don't,\" she said ('quote')".chars() {
+            b.insert_char(c);
+        }
+        assert_eq!(b.as_string(), "“Don’t,” she said (‘quote’)");
+        b.backspace();
+        b.backspace();
+        assert_eq!(b.as_string(), "“Don’t,” she said (‘quote");
+
+        b.auto_pairs = true;
+        b.insert_char(' ');
+        b.insert_char('"');
+        assert_eq!(b.as_string(), "“Don’t,” she said (‘quote “”");
+        b.insert_char('a');
+        b.insert_char('"');
+        assert_eq!(b.as_string(), "“Don’t,” she said (‘quote “a”");
+        assert_eq!(b.cursor.col, b.line_len_chars(0));
+    }
+
+    #[test]
+    fn matching_bracket_pairs_nested_brackets_within_bounds() {
+        let mut b = TextBuffer::from_text("f(a[1], (b))\n{ (".into(), None, false);
+        let all = 0..b.as_string().chars().count();
+        b.cursor.col = 1;
+        assert_eq!(b.matching_bracket(all.clone()), Some((1, 11)));
+        b.cursor.col = 12;
+        assert_eq!(b.matching_bracket(all.clone()), Some((11, 1)));
+        b.cursor.col = 4;
+        assert_eq!(b.matching_bracket(all.clone()), Some((3, 5)));
+        b.cursor.col = 1;
+        assert_eq!(b.matching_bracket(0..8), None);
+        b.cursor = Cursor { line: 1, col: 0 };
+        assert_eq!(b.matching_bracket(all.clone()), None);
+        b.cursor.col = 3;
+        assert_eq!(b.matching_bracket(all), None);
+    }
+
+    #[test]
+    fn outline_lists_headings_outside_code_fences() {
+        let b = TextBuffer::from_text(
+            "# Title\ntext\n## Part ##\n```\n# not a heading\n```\n#tag\n### Deep\n".into(),
+            None,
+            false,
+        );
+        let items = b.outline();
+        let outline: Vec<(usize, &str, usize)> = items
+            .iter()
+            .map(|h| (h.level, h.text.as_str(), h.line))
+            .collect();
+        assert_eq!(outline, [(1, "Title", 0), (2, "Part", 2), (3, "Deep", 7)]);
+    }
+
+    #[test]
+    fn word_index_is_invalidated_by_edits() {
+        let mut b = TextBuffer::from_text("ab cd".into(), None, false);
+        b.move_word_right();
+        assert_eq!(b.cursor.col, 3);
+        b.move_home();
+        b.insert_char('x');
+        b.insert_char(' ');
+        b.move_home();
+        b.move_word_right();
+        assert_eq!(b.cursor.col, 2);
+    }
+
+    #[test]
+    fn line_slice_reads_only_the_visible_window_of_long_lines() {
+        let long: String = (0..100_000)
+            .map(|i| char::from(b'a' + (i % 26) as u8))
+            .collect();
+        let mut b = TextBuffer::from_text(format!("{long}\nshort"), None, false);
+        b.set_viewport_size(80, 10);
+        b.move_end();
+        assert_eq!(b.viewport.left_col, 100_000 - 79);
+
+        let slice = b.line_slice(0, b.viewport.left_col, 80);
+        assert_eq!(slice.chars().count(), 79);
+        assert_eq!(slice, long[100_000 - 79..]);
+        assert_eq!(b.line_slice(0, 99_990, 5), long[99_990..99_995]);
+        assert_eq!(b.line_slice(1, 10, 80), "");
+    }
+
+    #[test]
+    fn loaded_colors_past_document_end_are_dropped() {
+        let mut b = TextBuffer::from_text("abc".into(), None, false);
+        let colors = [(0, 1), (2, 2), (3, 3), (40, 4)].into_iter().collect();
+        b.set_line_colors(colors);
+        assert_eq!(b.char_colors.len(), 2);
+        assert_eq!(b.char_color(2), Some(2));
+        assert_eq!(b.char_color(3), None);
+    }
+
+    #[test]
+    fn trailing_newline_adds_one_editable_empty_line() {
+        let mut with = TextBuffer::from_text("a\nb\n".into(), None, false);
+        assert_eq!(with.line_count(), 3);
+        assert_eq!(with.line_text(2), "");
+        with.goto_line(99);
+        assert_eq!((with.cursor.line, with.cursor.col), (2, 0));
+        with.insert_char('c');
+        assert_eq!(with.as_string(), "a\nb\nc");
+
+        let mut without = TextBuffer::from_text("a\nb".into(), None, false);
+        assert_eq!(without.line_count(), 2);
+        without.goto_line(99);
+        assert_eq!(without.cursor.line, 1);
+        assert_eq!(without.line_start_char_indices(0, 10).len(), 2);
+        assert_eq!(with.line_start_char_indices(0, 10), vec![0, 2, 4]);
+
+        let empty = TextBuffer::new(None, false);
+        assert_eq!(empty.line_count(), 1);
+    }
+
+    #[test]
+    fn cursor_stays_within_content_around_final_newline() {
+        let mut b = TextBuffer::from_text("abc\n".into(), None, false);
+        b.move_end();
+        b.move_down();
+        assert_eq!((b.cursor.line, b.cursor.col), (1, 0));
+        b.move_down();
+        assert_eq!((b.cursor.line, b.cursor.col), (1, 0));
+
+        b.backspace();
+        assert_eq!(b.as_string(), "abc");
+        assert_eq!((b.cursor.line, b.cursor.col), (0, 3));
+        b.move_down();
+        b.goto_line(2);
+        assert_eq!((b.cursor.line, b.cursor.col), (0, 3));
+        b.delete();
+        assert_eq!(b.as_string(), "abc");
+    }
+
+    #[test]
+    fn only_line_feed_breaks_lines() {
+        let mut b = TextBuffer::from_text("a\rb\u{2028}c\nd".into(), None, false);
+        assert_eq!(b.line_count(), 2);
+        assert_eq!(b.line_len_chars(0), 5);
+        b.move_end();
+        b.move_right();
+        assert_eq!((b.cursor.line, b.cursor.col), (1, 0));
+    }
+
+    #[test]
+    fn inserting_at_a_colored_index_does_not_steal_its_color() {
+        let mut b = TextBuffer::from_text("abc".into(), None, false);
+        b.move_right();
+        b.set_current_char_color(Some(2));
+        b.set_active_color(None);
+
+        b.insert_char('x');
+        assert_eq!(b.as_string(), "axbc");
+        assert_eq!(b.char_color(1), None);
+        assert_eq!(b.char_color(2), Some(2));
+
+        b.set_active_color(Some(7));
+        b.insert_char('y');
+        assert_eq!(b.as_string(), "axybc");
+        assert_eq!(b.char_color(2), Some(7));
+        assert_eq!(b.char_color(3), Some(2));
+        assert_eq!(b.char_colors.len(), 2);
+    }
+
+    #[test]
+    fn removing_a_line_break_keeps_colors_on_both_sides() {
+        let mut b = TextBuffer::from_text("ab\ncd".into(), None, false);
+        let colors = [(0, 1), (1, 2), (3, 3), (4, 4)].into_iter().collect();
+        b.set_char_colors(colors);
+
+        b.goto_line(2);
+        b.move_home();
+        b.backspace();
+        assert_eq!(b.as_string(), "abcd");
+        let merged: Vec<_> = (0..4).map(|i| b.char_color(i)).collect();
+        assert_eq!(merged, [Some(1), Some(2), Some(3), Some(4)]);
+
+        b.insert_newline();
+        assert_eq!(b.char_color(2), None);
+        assert_eq!(b.char_color(3), Some(3));
+
+        b.move_left();
+        b.delete();
+        assert_eq!(b.as_string(), "abcd");
+        let merged: Vec<_> = (0..4).map(|i| b.char_color(i)).collect();
+        assert_eq!(merged, [Some(1), Some(2), Some(3), Some(4)]);
+        assert_eq!(b.char_colors.len(), 4);
+    }
+
+    #[test]
+    fn pasting_a_colored_span_shifts_existing_colors_once() {
+        let mut b = TextBuffer::from_text("abcd".into(), None, false);
+        let colors = [(0, 1), (1, 1), (2, 2), (3, 2)].into_iter().collect();
+        b.set_char_colors(colors);
+        b.move_right();
+        b.move_right();
+
+        let pasted = [(0, 5), (2, 6)].into_iter().collect();
+        b.insert_colored_str("xy\nz", &pasted);
+        assert_eq!(b.as_string(), "abxy\nzcd");
+        assert_eq!((b.cursor.line, b.cursor.col), (1, 1));
+        let all: Vec<_> = (0..8).map(|i| b.char_color(i)).collect();
+        assert_eq!(
+            all,
+            [
+                Some(1),
+                Some(1),
+                Some(5),
+                None,
+                Some(6),
+                None,
+                Some(2),
+                Some(2)
+            ]
+        );
+
+        b.set_active_color(Some(3));
+        b.insert_str("q\r\n");
+        assert_eq!(b.as_string(), "abxy\nzq\ncd");
+        assert_eq!(b.char_color(6), Some(3));
+        assert_eq!(b.char_color(7), None);
+        assert_eq!(b.char_color(8), Some(2));
+    }
+
     #[test]
     fn stress_insertions_keep_valid_text() {
         let mut b = TextBuffer::new(None, false);
@@ -484,4 +1682,43 @@ mod tests {
         assert_eq!(b.line_count(), 2);
         assert_eq!(b.line_len_chars(0), 20000);
     }
+
+    #[test]
+    fn returning_to_saved_generation_clears_dirty() {
+        let mut b = TextBuffer::from_text("abc".to_string(), None, false);
+        b.move_end();
+        b.insert_char('d');
+        b.mark_saved();
+        let saved = b.generation();
+
+        b.insert_char('e');
+        let edited = b.generation();
+        assert!(b.dirty);
+
+        b.restore_generation(saved);
+        assert!(!b.dirty);
+        b.restore_generation(edited);
+        assert!(b.dirty);
+    }
+
+    #[test]
+    fn append_only_pins_edits_to_the_end() {
+        let mut b = TextBuffer::from_text("old entry".to_string(), None, false);
+        b.set_append_only(true);
+        b.cursor = Cursor { line: 0, col: 2 };
+        b.insert_char('n');
+        b.insert_char('x');
+        assert_eq!(b.as_string(), "old entry\nnx");
+
+        b.backspace();
+        b.backspace();
+        b.backspace();
+        b.cursor = Cursor { line: 0, col: 0 };
+        b.delete();
+        assert_eq!(b.as_string(), "old entry\n");
+
+        b.insert_str("second");
+        assert_eq!(b.as_string(), "old entry\nsecond");
+        assert_eq!(b.cursor_char_index(), b.as_string().chars().count());
+    }
 }
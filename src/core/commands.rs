@@ -1,35 +1,236 @@
 use std::path::PathBuf;
 
+use super::TextBuffer;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Command {
     Insert(char),
     NewLine,
     Backspace,
+    DeleteWordBack,
+    DeleteToLineEnd,
+    DeleteLine,
     Delete,
     MoveLeft,
     MoveRight,
+    MoveWordLeft,
+    MoveWordRight,
     MoveUp,
     MoveDown,
     MoveHome,
     MoveEnd,
     PageUp,
     PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    CenterCursor,
+    ToggleOverwrite,
     Save,
     SaveAs(PathBuf),
     Quit,
+    ForceQuit,
     ConfirmQuit,
     Cancel,
     OpenHelp,
     OpenSearch,
     OpenGoto,
+    OpenOutline,
+    EditNoteMeta,
+    AddWordToDictionary,
+    ToggleMarkdownPreview,
     OpenFileTree,
+    OpenFolder,
     NewFile,
+    NewCategory,
+    ToggleReadonly,
+    ToggleEol,
+    InsertMode,
+    AppendMode,
+    NormalMode,
+    OpenCommandLine,
+    OpenFilter,
+    OpenJournal,
+    InsertDateTime,
+    InsertRule,
+    ToggleCheckbox,
     SearchNext,
     SearchPrev,
+    CountOccurrences,
     UpdateSearch(String),
     CloseOverlay,
     SetLineColor(u8),
     ResetLineColor,
     GotoLine(usize),
+    RepeatLastEdit,
+    AddCursorBelow,
+    ToggleMacroRecording,
+    PlayMacro,
     Noop,
 }
+
+impl Command {
+    /// Commands that keep firing while their key is held down.
+    pub fn is_repeatable(&self) -> bool {
+        matches!(
+            self,
+            Command::Insert(_)
+                | Command::NewLine
+                | Command::Backspace
+                | Command::DeleteWordBack
+                | Command::DeleteToLineEnd
+                | Command::DeleteLine
+                | Command::Delete
+                | Command::MoveLeft
+                | Command::MoveRight
+                | Command::MoveWordLeft
+                | Command::MoveWordRight
+                | Command::MoveUp
+                | Command::MoveDown
+                | Command::MoveHome
+                | Command::MoveEnd
+                | Command::PageUp
+                | Command::PageDown
+                | Command::HalfPageUp
+                | Command::HalfPageDown
+                | Command::SearchNext
+                | Command::SearchPrev
+                | Command::RepeatLastEdit
+                | Command::AddCursorBelow
+        )
+    }
+
+    /// Single-key edits that `RepeatLastEdit` replays as they were.
+    pub fn is_replayable(&self) -> bool {
+        matches!(
+            self,
+            Command::Insert(_)
+                | Command::NewLine
+                | Command::Backspace
+                | Command::DeleteWordBack
+                | Command::DeleteToLineEnd
+                | Command::DeleteLine
+                | Command::Delete
+                | Command::SetLineColor(_)
+                | Command::ResetLineColor
+                | Command::ToggleCheckbox
+        )
+    }
+
+    /// Cursor movements that a count prefix repeats.
+    pub fn is_motion(&self) -> bool {
+        matches!(
+            self,
+            Command::MoveLeft
+                | Command::MoveRight
+                | Command::MoveWordLeft
+                | Command::MoveWordRight
+                | Command::MoveUp
+                | Command::MoveDown
+                | Command::PageUp
+                | Command::PageDown
+                | Command::HalfPageUp
+                | Command::HalfPageDown
+        )
+    }
+
+    /// Editing, movement and vim mode switches, which a macro records.
+    /// Commands that open overlays or touch files are left out.
+    pub fn is_recordable(&self) -> bool {
+        self.is_replayable()
+            || self.is_motion()
+            || matches!(
+                self,
+                Command::MoveHome
+                    | Command::MoveEnd
+                    | Command::CenterCursor
+                    | Command::ToggleOverwrite
+                    | Command::AddCursorBelow
+                    | Command::InsertMode
+                    | Command::AppendMode
+                    | Command::NormalMode
+            )
+    }
+
+    pub fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            Command::Insert(_)
+                | Command::NewLine
+                | Command::Backspace
+                | Command::DeleteWordBack
+                | Command::DeleteToLineEnd
+                | Command::DeleteLine
+                | Command::Delete
+                | Command::SetLineColor(_)
+                | Command::ResetLineColor
+                | Command::InsertDateTime
+                | Command::InsertRule
+                | Command::ToggleCheckbox
+                | Command::Save
+                | Command::SaveAs(_)
+                | Command::NewFile
+                | Command::OpenJournal
+                | Command::ToggleEol
+                | Command::OpenFilter
+                | Command::RepeatLastEdit
+        )
+    }
+}
+
+/// Applies an editing or navigation command to `buffer`.
+///
+/// Returns `false` for commands that need an application around the buffer
+/// (save, quit, overlays, file tree), which are left untouched.
+///
+/// ```
+/// use dopepad::core::{Command, TextBuffer, apply_command};
+///
+/// let mut buffer = TextBuffer::new(None, false);
+/// apply_command(&mut buffer, Command::Insert('h'));
+/// apply_command(&mut buffer, Command::Insert('i'));
+/// apply_command(&mut buffer, Command::MoveHome);
+/// assert!(!apply_command(&mut buffer, Command::Save));
+/// assert_eq!(buffer.as_string(), "hi");
+/// assert_eq!(buffer.cursor.col, 0);
+/// ```
+pub fn apply_command(buffer: &mut TextBuffer, cmd: Command) -> bool {
+    match cmd {
+        Command::Insert(c) => buffer.at_each_cursor(|b| b.insert_char(c)),
+        Command::NewLine => buffer.at_each_cursor(TextBuffer::insert_newline),
+        Command::Backspace => buffer.at_each_cursor(TextBuffer::backspace),
+        Command::DeleteWordBack => buffer.at_each_cursor(TextBuffer::delete_word_back),
+        Command::DeleteToLineEnd => buffer.at_each_cursor(TextBuffer::delete_to_line_end),
+        Command::DeleteLine => buffer.at_each_cursor(TextBuffer::delete_line),
+        Command::Delete => buffer.at_each_cursor(TextBuffer::delete),
+        Command::MoveLeft => buffer.at_each_cursor(TextBuffer::move_left),
+        Command::MoveRight => buffer.at_each_cursor(TextBuffer::move_right),
+        Command::MoveWordLeft => buffer.at_each_cursor(TextBuffer::move_word_left),
+        Command::MoveWordRight => buffer.at_each_cursor(TextBuffer::move_word_right),
+        Command::MoveUp => buffer.at_each_cursor(TextBuffer::move_up),
+        Command::MoveDown => buffer.at_each_cursor(TextBuffer::move_down),
+        Command::MoveHome => buffer.at_each_cursor(TextBuffer::move_home),
+        Command::MoveEnd => buffer.at_each_cursor(TextBuffer::move_end),
+        Command::ToggleCheckbox => buffer.at_each_cursor(TextBuffer::toggle_checkbox),
+        Command::AddCursorBelow => buffer.add_cursor_below(),
+        Command::CloseOverlay => buffer.extra_cursors.clear(),
+        Command::PageUp => buffer.page_up(),
+        Command::PageDown => buffer.page_down(),
+        Command::HalfPageUp => buffer.half_page_up(),
+        Command::HalfPageDown => buffer.half_page_down(),
+        Command::CenterCursor => buffer.center_viewport(),
+        Command::ToggleOverwrite => buffer.toggle_overwrite(),
+        Command::GotoLine(line) => buffer.goto_line(line),
+        Command::SetLineColor(cid) => {
+            if !buffer.readonly {
+                buffer.set_current_char_color(Some(cid));
+            }
+        }
+        Command::ResetLineColor => {
+            if !buffer.readonly {
+                buffer.set_current_char_color(None);
+            }
+        }
+        _ => return false,
+    }
+    true
+}
@@ -1,5 +1,9 @@
+//! Terminal-agnostic editing core: a rope-backed [`TextBuffer`] driven by [`Command`]s.
+
 mod buffer;
 mod commands;
+mod spell;
 
-pub use buffer::{ColorId, Cursor, SearchState, TextBuffer, Viewport};
-pub use commands::Command;
+pub use buffer::{ColorId, Cursor, OutlineItem, SearchState, TextBuffer, Viewport};
+pub use commands::{Command, apply_command};
+pub use spell::Dictionary;
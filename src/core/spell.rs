@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// Known words for spellchecking: a base word list plus the user's own words.
+#[derive(Debug, Clone, Default)]
+pub struct Dictionary {
+    words: HashSet<String>,
+    personal: HashSet<String>,
+}
+
+impl Dictionary {
+    /// Reads one word per line. Hunspell `.dic` files work too: a leading
+    /// entry count is skipped and `/FLAGS` suffixes are dropped.
+    pub fn from_word_list(text: &str) -> Self {
+        let words = text
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                let word = line.split('/').next().unwrap_or("").trim();
+                let is_count = i == 0 && word.chars().all(|c| c.is_ascii_digit());
+                (!word.is_empty() && !is_count).then(|| word.to_string())
+            })
+            .collect();
+        Self {
+            words,
+            personal: HashSet::new(),
+        }
+    }
+
+    pub fn set_personal_words(&mut self, text: &str) {
+        self.personal = text
+            .lines()
+            .map(str::trim)
+            .filter(|w| !w.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+
+    /// Adds `word` to the personal list; `false` when it was already known.
+    pub fn add_personal(&mut self, word: &str) -> bool {
+        if self.is_known(word) {
+            return false;
+        }
+        self.personal.insert(word.to_string())
+    }
+
+    /// Personal words, sorted, one per line.
+    pub fn personal_list(&self) -> String {
+        let mut words: Vec<&str> = self.personal.iter().map(String::as_str).collect();
+        words.sort_unstable();
+        words.iter().map(|w| format!("{w}\n")).collect()
+    }
+
+    /// Exact match, or the lowercase form for capitalized words.
+    pub fn is_known(&self, word: &str) -> bool {
+        let known = |w: &str| self.words.contains(w) || self.personal.contains(w);
+        known(word) || known(&word.to_lowercase())
+    }
+
+    /// Char ranges of unknown words in `line`. Single letters and words with
+    /// digits are never flagged.
+    pub fn misspelled(&self, line: &str) -> Vec<Range<usize>> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            if !chars[i].is_alphanumeric() {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric()
+                    || (chars[i] == '\'' && chars.get(i + 1).is_some_and(|c| c.is_alphabetic())))
+            {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if i - start > 1 && !word.chars().any(|c| c.is_numeric()) && !self.is_known(&word) {
+                out.push(start..i);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dictionary;
+
+    #[test]
+    fn hunspell_lists_and_capitalized_words_are_understood() {
+        let dict = Dictionary::from_word_list("3\nhello/MS\nworld\ndon't\n");
+        assert!(dict.is_known("hello"));
+        assert!(dict.is_known("Hello"));
+        assert!(!dict.is_known("3"));
+        assert_eq!(
+            dict.misspelled("Hello wrld, don't 42nd a helo"),
+            [6..10, 25..29]
+        );
+    }
+
+    #[test]
+    fn personal_words_are_known_and_listed_sorted() {
+        let mut dict = Dictionary::from_word_list("hello\n");
+        dict.set_personal_words("zed\n\nrope\n");
+        assert!(dict.is_known("rope"));
+        assert!(dict.add_personal("dopepad"));
+        assert!(!dict.add_personal("hello"));
+        assert_eq!(dict.personal_list(), "dopepad\nrope\nzed\n");
+    }
+}
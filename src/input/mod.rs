@@ -3,12 +3,21 @@ use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use crate::core::Command;
 
 pub fn map_key_event(key: KeyEvent, search_mode: bool) -> Option<Command> {
-    if key.kind != KeyEventKind::Press {
-        return None;
+    match key.kind {
+        KeyEventKind::Press => map_press(key, search_mode),
+        KeyEventKind::Repeat => map_press(key, search_mode).filter(Command::is_repeatable),
+        KeyEventKind::Release => None,
     }
+}
 
+fn map_press(key: KeyEvent, search_mode: bool) -> Option<Command> {
     let mods = key.modifiers;
     match (key.code, mods) {
+        (KeyCode::Char('q'), m) | (KeyCode::Char('Q'), m)
+            if m.contains(KeyModifiers::CONTROL) && m.contains(KeyModifiers::SHIFT) =>
+        {
+            Some(Command::ForceQuit)
+        }
         (KeyCode::Char('q'), KeyModifiers::CONTROL)
         | (KeyCode::Char('Q'), KeyModifiers::CONTROL) => Some(Command::Quit),
         (KeyCode::Char('s'), KeyModifiers::CONTROL)
@@ -22,31 +31,105 @@ pub fn map_key_event(key: KeyEvent, search_mode: bool) -> Option<Command> {
         (KeyCode::Esc, _) => Some(Command::CloseOverlay),
         (KeyCode::Char('f'), KeyModifiers::CONTROL)
         | (KeyCode::Char('F'), KeyModifiers::CONTROL) => Some(Command::OpenSearch),
+        (KeyCode::Char('k'), KeyModifiers::CONTROL)
+        | (KeyCode::Char('K'), KeyModifiers::CONTROL) => Some(Command::CountOccurrences),
         (KeyCode::Char('g'), KeyModifiers::CONTROL)
         | (KeyCode::Char('G'), KeyModifiers::CONTROL) => Some(Command::OpenGoto),
+        (KeyCode::Char('o'), m) | (KeyCode::Char('O'), m)
+            if m.contains(KeyModifiers::CONTROL) && m.contains(KeyModifiers::SHIFT) =>
+        {
+            Some(Command::OpenFolder)
+        }
         (KeyCode::Char('o'), KeyModifiers::CONTROL)
         | (KeyCode::Char('O'), KeyModifiers::CONTROL) => Some(Command::OpenFileTree),
+        (KeyCode::Char('n'), m) | (KeyCode::Char('N'), m)
+            if m.contains(KeyModifiers::CONTROL) && m.contains(KeyModifiers::SHIFT) =>
+        {
+            Some(Command::NewCategory)
+        }
         (KeyCode::Char('n'), KeyModifiers::CONTROL)
         | (KeyCode::Char('N'), KeyModifiers::CONTROL) => Some(Command::NewFile),
+        (KeyCode::Char('t'), m) | (KeyCode::Char('T'), m)
+            if m.contains(KeyModifiers::CONTROL) && m.contains(KeyModifiers::SHIFT) =>
+        {
+            Some(Command::EditNoteMeta)
+        }
+        (KeyCode::Char('p'), m) | (KeyCode::Char('P'), m)
+            if m.contains(KeyModifiers::CONTROL) && m.contains(KeyModifiers::SHIFT) =>
+        {
+            Some(Command::OpenFilter)
+        }
+        (KeyCode::Char('h'), m) | (KeyCode::Char('H'), m)
+            if m.contains(KeyModifiers::CONTROL) && m.contains(KeyModifiers::SHIFT) =>
+        {
+            Some(Command::OpenOutline)
+        }
+        (KeyCode::Char('d'), m) | (KeyCode::Char('D'), m)
+            if m.contains(KeyModifiers::CONTROL) && m.contains(KeyModifiers::SHIFT) =>
+        {
+            Some(Command::AddWordToDictionary)
+        }
+        (KeyCode::Char('x'), m) | (KeyCode::Char('X'), m)
+            if m.contains(KeyModifiers::CONTROL) && m.contains(KeyModifiers::SHIFT) =>
+        {
+            Some(Command::ToggleCheckbox)
+        }
+        (KeyCode::Char('j'), KeyModifiers::CONTROL)
+        | (KeyCode::Char('J'), KeyModifiers::CONTROL) => Some(Command::OpenJournal),
+        (KeyCode::Char('e'), KeyModifiers::CONTROL)
+        | (KeyCode::Char('E'), KeyModifiers::CONTROL) => Some(Command::ToggleEol),
+        (KeyCode::Char('r'), m) | (KeyCode::Char('R'), m)
+            if m.contains(KeyModifiers::CONTROL) && m.contains(KeyModifiers::SHIFT) =>
+        {
+            Some(Command::ToggleMacroRecording)
+        }
+        (KeyCode::Char('r'), KeyModifiers::CONTROL)
+        | (KeyCode::Char('R'), KeyModifiers::CONTROL) => Some(Command::ToggleReadonly),
+        (KeyCode::Char('t'), KeyModifiers::CONTROL)
+        | (KeyCode::Char('T'), KeyModifiers::CONTROL) => Some(Command::InsertDateTime),
+        (KeyCode::Char('u'), KeyModifiers::CONTROL)
+        | (KeyCode::Char('U'), KeyModifiers::CONTROL) => Some(Command::HalfPageUp),
+        (KeyCode::Char('d'), KeyModifiers::CONTROL)
+        | (KeyCode::Char('D'), KeyModifiers::CONTROL) => Some(Command::HalfPageDown),
+        (KeyCode::Char('l'), m) | (KeyCode::Char('L'), m)
+            if m.contains(KeyModifiers::CONTROL) && m.contains(KeyModifiers::SHIFT) =>
+        {
+            Some(Command::InsertRule)
+        }
+        (KeyCode::Char('l'), KeyModifiers::CONTROL)
+        | (KeyCode::Char('L'), KeyModifiers::CONTROL) => Some(Command::CenterCursor),
+        (KeyCode::Char('y'), m) | (KeyCode::Char('Y'), m)
+            if m.contains(KeyModifiers::CONTROL) && m.contains(KeyModifiers::SHIFT) =>
+        {
+            Some(Command::PlayMacro)
+        }
+        (KeyCode::Char('y'), KeyModifiers::CONTROL)
+        | (KeyCode::Char('Y'), KeyModifiers::CONTROL) => Some(Command::RepeatLastEdit),
         (KeyCode::Enter, m) if search_mode && m.contains(KeyModifiers::SHIFT) => {
             Some(Command::SearchPrev)
         }
         (KeyCode::Enter, _) if search_mode => Some(Command::SearchNext),
+        (KeyCode::Left, m) if m.contains(KeyModifiers::CONTROL) => Some(Command::MoveWordLeft),
+        (KeyCode::Right, m) if m.contains(KeyModifiers::CONTROL) => Some(Command::MoveWordRight),
         (KeyCode::Left, _) => Some(Command::MoveLeft),
         (KeyCode::Right, _) => Some(Command::MoveRight),
+        (KeyCode::Down, m) if m.contains(KeyModifiers::ALT) => Some(Command::AddCursorBelow),
         (KeyCode::Up, _) => Some(Command::MoveUp),
         (KeyCode::Down, _) => Some(Command::MoveDown),
         (KeyCode::Home, _) => Some(Command::MoveHome),
         (KeyCode::End, _) => Some(Command::MoveEnd),
         (KeyCode::PageUp, _) => Some(Command::PageUp),
         (KeyCode::PageDown, _) => Some(Command::PageDown),
+        (KeyCode::Backspace, m) if m.contains(KeyModifiers::ALT) => Some(Command::DeleteWordBack),
         (KeyCode::Backspace, _) => Some(Command::Backspace),
         (KeyCode::Delete, _) => Some(Command::Delete),
+        (KeyCode::Insert, KeyModifiers::NONE) => Some(Command::ToggleOverwrite),
         (KeyCode::Enter, _) => Some(Command::NewLine),
         (KeyCode::Tab, _) => Some(Command::Insert('\t')),
         (KeyCode::Char(c), KeyModifiers::NONE) => Some(Command::Insert(c)),
         (KeyCode::Char(c), KeyModifiers::SHIFT) => Some(Command::Insert(c)),
         (KeyCode::F(10), KeyModifiers::NONE) => Some(Command::ResetLineColor),
+        (KeyCode::F(12), KeyModifiers::NONE) => Some(Command::ToggleMarkdownPreview),
         (KeyCode::F(2), KeyModifiers::NONE) => Some(Command::SetLineColor(1)),
         (KeyCode::F(3), KeyModifiers::NONE) => Some(Command::SetLineColor(2)),
         (KeyCode::F(4), KeyModifiers::NONE) => Some(Command::SetLineColor(3)),
@@ -58,3 +141,593 @@ pub fn map_key_event(key: KeyEvent, search_mode: bool) -> Option<Command> {
         _ => None,
     }
 }
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Keymap {
+    #[default]
+    Default,
+    Vim,
+    Emacs,
+}
+
+impl Keymap {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "default" => Some(Self::Default),
+            "vim" => Some(Self::Vim),
+            "emacs" => Some(Self::Emacs),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Vim => "vim",
+            Self::Emacs => "emacs",
+        }
+    }
+}
+
+/// Maps `key` through the preset table of `keymap` first, then the default
+/// bindings. With the vim preset, `normal` selects normal mode, where plain
+/// characters never insert text.
+pub fn map_key_for(
+    keymap: Keymap,
+    normal: bool,
+    key: KeyEvent,
+    search_mode: bool,
+) -> Option<Command> {
+    if key.kind == KeyEventKind::Release {
+        return None;
+    }
+    let preset = match keymap {
+        Keymap::Default => Vec::new(),
+        Keymap::Vim if normal => vim_normal_bindings(),
+        Keymap::Vim => vim_insert_bindings(),
+        Keymap::Emacs => emacs_bindings(),
+    };
+    if let Some(binding) = preset.into_iter().find(|b| binding_matches(b, &key)) {
+        return Some(binding.command)
+            .filter(|cmd| key.kind == KeyEventKind::Press || cmd.is_repeatable());
+    }
+    let cmd = map_key_event(key, search_mode)?;
+    if keymap == Keymap::Vim && normal && matches!(cmd, Command::Insert(_)) {
+        return None;
+    }
+    Some(cmd)
+}
+
+fn binding_matches(binding: &KeyBinding, key: &KeyEvent) -> bool {
+    let mods = match key.code {
+        KeyCode::Char(_) => key.modifiers.difference(KeyModifiers::SHIFT),
+        _ => key.modifiers,
+    };
+    binding.code == key.code && binding.modifiers == mods
+}
+
+pub struct KeyBinding {
+    pub keys: &'static str,
+    pub description: &'static str,
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+    pub command: Command,
+}
+
+pub fn key_bindings() -> Vec<KeyBinding> {
+    use KeyCode::{Char, F};
+    const NONE: KeyModifiers = KeyModifiers::NONE;
+    const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+    const CTRL_SHIFT: KeyModifiers = KeyModifiers::CONTROL.union(KeyModifiers::SHIFT);
+    let bind = |keys, description, code, modifiers, command| KeyBinding {
+        keys,
+        description,
+        code,
+        modifiers,
+        command,
+    };
+    let mut bindings = vec![
+        bind("F1", "help", F(1), NONE, Command::OpenHelp),
+        bind("Ctrl+F", "search", Char('f'), CTRL, Command::OpenSearch),
+        bind("Ctrl+G", "go to line", Char('g'), CTRL, Command::OpenGoto),
+        bind(
+            "Ctrl+Shift+H",
+            "outline (# headings)",
+            Char('H'),
+            CTRL_SHIFT,
+            Command::OpenOutline,
+        ),
+        bind(
+            "Ctrl+K",
+            "count word under cursor",
+            Char('k'),
+            CTRL,
+            Command::CountOccurrences,
+        ),
+        bind(
+            "Ctrl+O",
+            "toggle tree focus",
+            Char('o'),
+            CTRL,
+            Command::OpenFileTree,
+        ),
+        bind(
+            "Ctrl+Shift+O",
+            "open folder in file manager",
+            Char('O'),
+            CTRL_SHIFT,
+            Command::OpenFolder,
+        ),
+        bind("Ctrl+N", "new note", Char('n'), CTRL, Command::NewFile),
+        bind(
+            "Ctrl+Shift+N",
+            "new category",
+            Char('N'),
+            CTRL_SHIFT,
+            Command::NewCategory,
+        ),
+        bind(
+            "Ctrl+J",
+            "today's journal note",
+            Char('j'),
+            CTRL,
+            Command::OpenJournal,
+        ),
+        bind("Ctrl+S", "save", Char('s'), CTRL, Command::Save),
+        bind("Ctrl+Q", "quit", Char('q'), CTRL, Command::Quit),
+        bind(
+            "Ctrl+Shift+Q",
+            "quit without saving",
+            Char('Q'),
+            CTRL_SHIFT,
+            Command::ForceQuit,
+        ),
+        bind(
+            "Ctrl+Shift+T",
+            "note title and tags",
+            Char('T'),
+            CTRL_SHIFT,
+            Command::EditNoteMeta,
+        ),
+        bind(
+            "Ctrl+Shift+P",
+            "pipe note through a shell command",
+            Char('P'),
+            CTRL_SHIFT,
+            Command::OpenFilter,
+        ),
+        bind(
+            "F12",
+            "Markdown preview",
+            F(12),
+            NONE,
+            Command::ToggleMarkdownPreview,
+        ),
+        bind(
+            "Ctrl+Shift+D",
+            "add word to dictionary",
+            Char('D'),
+            CTRL_SHIFT,
+            Command::AddWordToDictionary,
+        ),
+        bind(
+            "Ctrl+T",
+            "insert date/time",
+            Char('t'),
+            CTRL,
+            Command::InsertDateTime,
+        ),
+        bind(
+            "Ctrl+Shift+X",
+            "toggle checkbox",
+            Char('X'),
+            CTRL_SHIFT,
+            Command::ToggleCheckbox,
+        ),
+        bind(
+            "Ctrl+Shift+L",
+            "insert separator line",
+            Char('L'),
+            CTRL_SHIFT,
+            Command::InsertRule,
+        ),
+        bind(
+            "Ctrl+E",
+            "toggle LF/CRLF",
+            Char('e'),
+            CTRL,
+            Command::ToggleEol,
+        ),
+        bind(
+            "Ctrl+R",
+            "toggle readonly",
+            Char('r'),
+            CTRL,
+            Command::ToggleReadonly,
+        ),
+        bind(
+            "Ctrl+Left",
+            "previous word",
+            KeyCode::Left,
+            CTRL,
+            Command::MoveWordLeft,
+        ),
+        bind(
+            "Ctrl+Right",
+            "next word",
+            KeyCode::Right,
+            CTRL,
+            Command::MoveWordRight,
+        ),
+        bind(
+            "Home",
+            "line start / first non-blank",
+            KeyCode::Home,
+            NONE,
+            Command::MoveHome,
+        ),
+        bind("End", "line end", KeyCode::End, NONE, Command::MoveEnd),
+        bind(
+            "Alt+Down",
+            "add cursor on next line",
+            KeyCode::Down,
+            KeyModifiers::ALT,
+            Command::AddCursorBelow,
+        ),
+        bind(
+            "Alt+Backspace",
+            "delete previous word",
+            KeyCode::Backspace,
+            KeyModifiers::ALT,
+            Command::DeleteWordBack,
+        ),
+        bind("PgUp", "page up", KeyCode::PageUp, NONE, Command::PageUp),
+        bind(
+            "PgDn",
+            "page down",
+            KeyCode::PageDown,
+            NONE,
+            Command::PageDown,
+        ),
+        bind(
+            "Ctrl+U",
+            "half page up",
+            Char('u'),
+            CTRL,
+            Command::HalfPageUp,
+        ),
+        bind(
+            "Ctrl+D",
+            "half page down",
+            Char('d'),
+            CTRL,
+            Command::HalfPageDown,
+        ),
+        bind(
+            "Ctrl+L",
+            "center cursor line",
+            Char('l'),
+            CTRL,
+            Command::CenterCursor,
+        ),
+        bind(
+            "Ctrl+Y",
+            "repeat last edit",
+            Char('y'),
+            CTRL,
+            Command::RepeatLastEdit,
+        ),
+        bind(
+            "Ctrl+Shift+R",
+            "start / stop recording a macro",
+            Char('R'),
+            CTRL_SHIFT,
+            Command::ToggleMacroRecording,
+        ),
+        bind(
+            "Ctrl+Shift+Y",
+            "play the macro",
+            Char('Y'),
+            CTRL_SHIFT,
+            Command::PlayMacro,
+        ),
+        bind(
+            "Insert",
+            "toggle overwrite",
+            KeyCode::Insert,
+            NONE,
+            Command::ToggleOverwrite,
+        ),
+        bind(
+            "F10",
+            "reset char color",
+            F(10),
+            NONE,
+            Command::ResetLineColor,
+        ),
+    ];
+    const COLOR_KEYS: [&str; 8] = ["F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9"];
+    for (i, keys) in COLOR_KEYS.into_iter().enumerate() {
+        let id = i as u8 + 1;
+        bindings.push(bind(
+            keys,
+            "set char color",
+            F(id + 1),
+            NONE,
+            Command::SetLineColor(id),
+        ));
+    }
+    bindings
+}
+
+pub fn vim_normal_bindings() -> Vec<KeyBinding> {
+    use KeyCode::Char;
+    const NONE: KeyModifiers = KeyModifiers::NONE;
+    let bind = |keys, description, code, command| KeyBinding {
+        keys,
+        description,
+        code,
+        modifiers: NONE,
+        command,
+    };
+    vec![
+        bind("h", "left", Char('h'), Command::MoveLeft),
+        bind("j", "down", Char('j'), Command::MoveDown),
+        bind("k", "up", Char('k'), Command::MoveUp),
+        bind("l", "right", Char('l'), Command::MoveRight),
+        bind("w", "next word", Char('w'), Command::MoveWordRight),
+        bind("b", "previous word", Char('b'), Command::MoveWordLeft),
+        bind("0", "line start", Char('0'), Command::MoveHome),
+        bind("$", "line end", Char('$'), Command::MoveEnd),
+        bind("Enter", "down", KeyCode::Enter, Command::MoveDown),
+        bind("Backspace", "left", KeyCode::Backspace, Command::MoveLeft),
+        bind("i", "insert mode", Char('i'), Command::InsertMode),
+        bind("a", "append after cursor", Char('a'), Command::AppendMode),
+        bind("x", "delete char", Char('x'), Command::Delete),
+        bind(".", "repeat last edit", Char('.'), Command::RepeatLastEdit),
+        bind(
+            "q",
+            "start / stop recording a macro",
+            Char('q'),
+            Command::ToggleMacroRecording,
+        ),
+        bind("@", "play the macro", Char('@'), Command::PlayMacro),
+        bind("/", "search", Char('/'), Command::OpenSearch),
+        bind(":", "command line", Char(':'), Command::OpenCommandLine),
+    ]
+}
+
+pub fn vim_insert_bindings() -> Vec<KeyBinding> {
+    vec![KeyBinding {
+        keys: "Esc",
+        description: "normal mode",
+        code: KeyCode::Esc,
+        modifiers: KeyModifiers::NONE,
+        command: Command::NormalMode,
+    }]
+}
+
+pub fn emacs_bindings() -> Vec<KeyBinding> {
+    use KeyCode::Char;
+    const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+    const ALT: KeyModifiers = KeyModifiers::ALT;
+    let bind = |keys, description, code, modifiers, command| KeyBinding {
+        keys,
+        description,
+        code,
+        modifiers,
+        command,
+    };
+    vec![
+        bind("Ctrl+A", "line start", Char('a'), CTRL, Command::MoveHome),
+        bind("Ctrl+E", "line end", Char('e'), CTRL, Command::MoveEnd),
+        bind(
+            "Ctrl+K",
+            "kill to line end",
+            Char('k'),
+            CTRL,
+            Command::DeleteToLineEnd,
+        ),
+        bind("Ctrl+B", "left", Char('b'), CTRL, Command::MoveLeft),
+        bind("Ctrl+P", "up", Char('p'), CTRL, Command::MoveUp),
+        bind(
+            "Alt+B",
+            "previous word",
+            Char('b'),
+            ALT,
+            Command::MoveWordLeft,
+        ),
+        bind("Alt+F", "next word", Char('f'), ALT, Command::MoveWordRight),
+        bind("Ctrl+V", "page down", Char('v'), CTRL, Command::PageDown),
+        bind("Alt+V", "page up", Char('v'), ALT, Command::PageUp),
+    ]
+}
+
+pub const VIM_NORMAL_KEYS: &[(&str, &str)] = &[
+    ("dd", "delete line"),
+    (":w / :q / :q!", "save / quit / quit without saving"),
+    (":wq / :x", "save and quit"),
+    (":<n>", "go to line n"),
+    (":!<cmd>", "pipe the note through a shell command"),
+];
+
+pub const APP_KEYS: &[(&str, &str)] = &[
+    ("Ctrl+Shift+S", "save as"),
+    ("Esc", "close overlay"),
+    ("Enter / Shift+Enter", "next / previous match (search)"),
+];
+
+pub const TREE_KEYS: &[(&str, &str)] = &[
+    ("Up/Down", "select file"),
+    ("Enter", "open"),
+    ("/", "filter by name"),
+    ("S", "sort by name / modified"),
+    ("G", "group by category / tag"),
+    ("I", "file info"),
+    ("O", "open folder in file manager"),
+    ("T", "title and tags"),
+    ("Shift+Up/Down", "move category"),
+    ("N", "new note"),
+    ("C", "new category"),
+    ("Del / D", "delete"),
+    ("Ctrl+Z", "undo delete"),
+    ("R", "reload"),
+    ("Esc", "clear filter / back to editor"),
+];
+
+pub fn help_lines(keymap: Keymap) -> Vec<String> {
+    let row = |keys: &str, description: &str| format!("  {keys:<20} {description}");
+    let mut lines = Vec::new();
+    let preset = match keymap {
+        Keymap::Default => Vec::new(),
+        Keymap::Vim => {
+            let mut bindings = vim_insert_bindings();
+            bindings.extend(vim_normal_bindings());
+            bindings
+        }
+        Keymap::Emacs => emacs_bindings(),
+    };
+    if !preset.is_empty() {
+        lines.push(format!("Keymap: {} (takes precedence)", keymap.label()));
+        lines.extend(preset.iter().map(|b| row(b.keys, b.description)));
+        if keymap == Keymap::Vim {
+            lines.extend(VIM_NORMAL_KEYS.iter().map(|(k, d)| row(k, d)));
+        }
+        lines.push(String::new());
+    }
+    lines.push("Editor".to_string());
+    lines.extend(key_bindings().iter().map(|b| row(b.keys, b.description)));
+    lines.extend(APP_KEYS.iter().map(|(k, d)| row(k, d)));
+    lines.push(String::new());
+    lines.push("Tree".to_string());
+    lines.extend(TREE_KEYS.iter().map(|(k, d)| row(k, d)));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers};
+
+    use super::{
+        APP_KEYS, Keymap, emacs_bindings, key_bindings, map_key_event, map_key_for,
+        vim_insert_bindings, vim_normal_bindings,
+    };
+    use crate::core::Command;
+
+    #[test]
+    fn preset_tables_take_precedence_over_default_bindings() {
+        let presets = [
+            (Keymap::Vim, true, vim_normal_bindings()),
+            (Keymap::Vim, false, vim_insert_bindings()),
+            (Keymap::Emacs, false, emacs_bindings()),
+        ];
+        for (keymap, normal, bindings) in presets {
+            for binding in bindings {
+                let key = KeyEvent::new(binding.code, binding.modifiers);
+                assert_eq!(
+                    map_key_for(keymap, normal, key, false),
+                    Some(binding.command.clone()),
+                    "{}",
+                    binding.keys
+                );
+            }
+        }
+
+        let z = KeyEvent::new(KeyCode::Char('z'), KeyModifiers::NONE);
+        assert_eq!(map_key_for(Keymap::Vim, true, z, false), None);
+        assert_eq!(
+            map_key_for(Keymap::Vim, false, z, false),
+            Some(Command::Insert('z'))
+        );
+        let dollar = KeyEvent::new(KeyCode::Char('$'), KeyModifiers::SHIFT);
+        assert_eq!(
+            map_key_for(Keymap::Vim, true, dollar, false),
+            Some(Command::MoveEnd)
+        );
+        let save = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(
+            map_key_for(Keymap::Emacs, false, save, false),
+            Some(Command::Save)
+        );
+        let ctrl_e = KeyEvent::new(KeyCode::Char('e'), KeyModifiers::CONTROL);
+        assert_eq!(
+            map_key_for(Keymap::Default, false, ctrl_e, false),
+            Some(Command::ToggleEol)
+        );
+    }
+
+    #[test]
+    fn repeats_only_drive_motion_and_editing_and_releases_are_ignored() {
+        let event = |code, modifiers, kind| KeyEvent {
+            code,
+            modifiers,
+            kind,
+            state: KeyEventState::NONE,
+        };
+        let none = KeyModifiers::NONE;
+
+        let held_down = event(KeyCode::Down, none, KeyEventKind::Repeat);
+        assert_eq!(map_key_event(held_down, false), Some(Command::MoveDown));
+        let held_x = event(KeyCode::Char('x'), none, KeyEventKind::Repeat);
+        assert_eq!(map_key_event(held_x, false), Some(Command::Insert('x')));
+        let held_save = event(
+            KeyCode::Char('s'),
+            KeyModifiers::CONTROL,
+            KeyEventKind::Repeat,
+        );
+        assert_eq!(map_key_event(held_save, false), None);
+        let released = event(KeyCode::Down, none, KeyEventKind::Release);
+        assert_eq!(map_key_event(released, false), None);
+    }
+
+    #[test]
+    fn registry_matches_key_mapping() {
+        for binding in key_bindings() {
+            let key = KeyEvent::new(binding.code, binding.modifiers);
+            assert_eq!(
+                map_key_event(key, false),
+                Some(binding.command.clone()),
+                "{}",
+                binding.keys
+            );
+        }
+    }
+
+    #[test]
+    fn every_mapped_ctrl_and_function_key_is_listed() {
+        let ctrl_shift = KeyModifiers::CONTROL | KeyModifiers::SHIFT;
+        let mut keys = Vec::new();
+        for c in 'a'..='z' {
+            let upper = c.to_ascii_uppercase();
+            keys.push((
+                format!("Ctrl+{upper}"),
+                KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL),
+            ));
+            keys.push((
+                format!("Ctrl+Shift+{upper}"),
+                KeyEvent::new(KeyCode::Char(upper), ctrl_shift),
+            ));
+        }
+        for n in 1..=12 {
+            keys.push((
+                format!("F{n}"),
+                KeyEvent::new(KeyCode::F(n), KeyModifiers::NONE),
+            ));
+        }
+
+        let registry = key_bindings();
+        for (label, key) in keys {
+            if map_key_event(key, false).is_none() {
+                continue;
+            }
+            let listed = registry
+                .iter()
+                .any(|b| b.code == key.code && b.modifiers == key.modifiers)
+                || APP_KEYS.iter().any(|(keys, _)| *keys == label);
+            assert!(
+                listed,
+                "{label} is mapped but missing from the help registry"
+            );
+        }
+    }
+}
@@ -1,8 +1,11 @@
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::ffi::OsString;
-use std::fs;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 
+use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE, WINDOWS_1252};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -14,10 +17,29 @@ pub enum EolStyle {
     Crlf,
 }
 
+impl EolStyle {
+    pub fn label(self) -> &'static str {
+        match self {
+            EolStyle::Lf => "LF",
+            EolStyle::Crlf => "CRLF",
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            EolStyle::Lf => EolStyle::Crlf,
+            EolStyle::Crlf => EolStyle::Lf,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FileData {
     pub text: String,
     pub eol: EolStyle,
+    pub encoding: &'static Encoding,
+    /// The file started with a byte order mark, which saving writes back.
+    pub bom: bool,
 }
 
 #[derive(Debug, Error)]
@@ -34,12 +56,30 @@ pub enum IoError {
         #[source]
         source: std::io::Error,
     },
+    #[error("cannot save {path} as {encoding}: it has no {ch:?} (U+{code:04X})", code = *ch as u32)]
+    Unencodable {
+        path: String,
+        encoding: &'static str,
+        ch: char,
+    },
+    #[error("refusing to open binary file {path}")]
+    BinaryFile { path: String },
+    #[error(
+        "unsupported sidecar version {version} in {path} (this build reads up to v{SIDECAR_VERSION})"
+    )]
+    UnsupportedSidecarVersion { path: String, version: u64 },
     #[error("failed parsing sidecar {path}: {source}")]
     SidecarParse {
         path: String,
         #[source]
         source: serde_json::Error,
     },
+    #[error("color run at {start} (length {len}) in {path} runs past the end of any document")]
+    BadColorRun {
+        path: String,
+        start: usize,
+        len: usize,
+    },
     #[error("failed serializing sidecar {path}: {source}")]
     SidecarSerialize {
         path: String,
@@ -48,34 +88,239 @@ pub enum IoError {
     },
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SidecarStorage {
+    #[default]
+    PerFile,
+    Index,
+}
+
+const INDEX_FILE_NAME: &str = ".dopepad-index.json";
+
+pub const SIDECAR_VERSION: u32 = 2;
+
+fn default_sidecar_version() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct ColorRun {
+    start: usize,
+    len: usize,
+    color: u8,
+}
+
+/// Human title and tags kept next to a note's colors in its sidecar.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NoteMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl NoteMeta {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.tags.is_empty()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct Sidecar {
-    #[serde(default)]
+    #[serde(default = "default_sidecar_version")]
+    version: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    char_runs: Vec<ColorRun>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     char_colors: ColorMap,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     line_colors: ColorMap,
+    #[serde(flatten)]
+    meta: NoteMeta,
+}
+
+impl Sidecar {
+    fn from_colors(colors: &ColorMap, meta: NoteMeta) -> Self {
+        let mut runs: Vec<ColorRun> = Vec::new();
+        for (&idx, &color) in colors {
+            match runs.last_mut() {
+                Some(run) if run.color == color && run.start + run.len == idx => run.len += 1,
+                _ => runs.push(ColorRun {
+                    start: idx,
+                    len: 1,
+                    color,
+                }),
+            }
+        }
+        Self {
+            version: SIDECAR_VERSION,
+            char_runs: runs,
+            char_colors: BTreeMap::new(),
+            line_colors: BTreeMap::new(),
+            meta,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.char_runs.is_empty() && self.meta.is_empty()
+    }
+
+    /// Rewrites version 1 color maps as runs, leaving stored runs untouched.
+    fn upgraded(mut self) -> Self {
+        let legacy = if self.char_colors.is_empty() {
+            std::mem::take(&mut self.line_colors)
+        } else {
+            std::mem::take(&mut self.char_colors)
+        };
+        self.line_colors.clear();
+        let runs = Sidecar::from_colors(&legacy, NoteMeta::default()).char_runs;
+        self.char_runs.extend(runs);
+        self.version = SIDECAR_VERSION;
+        self
+    }
 }
 
-pub fn load_document(path: &Path) -> Result<FileData, IoError> {
+pub fn load_document(path: &Path, forced: Option<&'static Encoding>) -> Result<FileData, IoError> {
     let bytes = fs::read(path).map_err(|source| IoError::Read {
         path: path.display().to_string(),
         source,
     })?;
-    let raw = String::from_utf8_lossy(&bytes).to_string();
+    if forced.is_none() && looks_binary(&bytes) {
+        return Err(IoError::BinaryFile {
+            path: path.display().to_string(),
+        });
+    }
+    let (raw, encoding) = decode_bytes(&bytes, forced);
+    let bom = Encoding::for_bom(&bytes).is_some_and(|(bom_encoding, _)| bom_encoding == encoding);
     let eol = detect_eol(&raw);
     let text = raw.replace("\r\n", "\n");
-    Ok(FileData { text, eol })
+    Ok(FileData {
+        text,
+        eol,
+        encoding,
+        bom,
+    })
 }
 
-pub fn save_document(path: &Path, text: &str, eol: EolStyle) -> Result<(), IoError> {
-    let out = match eol {
-        EolStyle::Lf => text.to_string(),
-        EolStyle::Crlf => text.replace('\n', "\r\n"),
-    };
-    fs::write(path, out).map_err(|source| IoError::Write {
+const BINARY_SNIFF_LEN: usize = 8192;
+
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    if Encoding::for_bom(bytes).is_some() {
+        return false;
+    }
+    bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+pub fn decode_bytes(
+    bytes: &[u8],
+    forced: Option<&'static Encoding>,
+) -> (String, &'static Encoding) {
+    if let Some(encoding) = forced {
+        let (text, _) = encoding.decode_without_bom_handling(bytes);
+        return (text.into_owned(), encoding);
+    }
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return (text.into_owned(), encoding);
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_string(), UTF_8),
+        Err(_) => {
+            let (text, _) = WINDOWS_1252.decode_without_bom_handling(bytes);
+            (text.into_owned(), WINDOWS_1252)
+        }
+    }
+}
+
+pub fn save_document(
+    path: &Path,
+    text: &str,
+    eol: EolStyle,
+    encoding: &'static Encoding,
+    bom: bool,
+) -> Result<(), IoError> {
+    save_document_chunks(path, std::iter::once(text), eol, encoding, bom)
+}
+
+/// Writes `chunks` in `encoding`, with its byte order mark when `bom` is set.
+/// UTF-8 streams straight to disk; other encodings are converted up front so
+/// a character the encoding lacks fails the save before the file is touched.
+pub fn save_document_chunks<'a>(
+    path: &Path,
+    chunks: impl Iterator<Item = &'a str>,
+    eol: EolStyle,
+    encoding: &'static Encoding,
+    bom: bool,
+) -> Result<(), IoError> {
+    let write_err = |source| IoError::Write {
         path: path.display().to_string(),
         source,
-    })
+    };
+    let pieces = chunks.flat_map(|chunk| {
+        chunk.split('\n').enumerate().flat_map(move |(i, piece)| {
+            let newline = match (i, eol) {
+                (0, _) => "",
+                (_, EolStyle::Lf) => "\n",
+                (_, EolStyle::Crlf) => "\r\n",
+            };
+            [newline, piece]
+        })
+    });
+    let bom = if bom { bom_bytes(encoding) } else { b"" };
+    if encoding != UTF_8 {
+        let mut encoded = bom.to_vec();
+        for piece in pieces {
+            let bytes = encode_text(piece, encoding).map_err(|ch| IoError::Unencodable {
+                path: path.display().to_string(),
+                encoding: encoding.name(),
+                ch,
+            })?;
+            encoded.extend_from_slice(&bytes);
+        }
+        return fs::write(path, encoded).map_err(write_err);
+    }
+
+    let file = File::create(path).map_err(write_err)?;
+    let mut out = BufWriter::new(file);
+    out.write_all(bom).map_err(write_err)?;
+    for piece in pieces {
+        out.write_all(piece.as_bytes()).map_err(write_err)?;
+    }
+    out.flush().map_err(write_err)
+}
+
+/// `text` in `encoding`, or the first character it cannot represent.
+/// encoding_rs only decodes UTF-16, so that is encoded here.
+fn encode_text<'t>(text: &'t str, encoding: &'static Encoding) -> Result<Cow<'t, [u8]>, char> {
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let units = text.encode_utf16();
+        let bytes = if encoding == UTF_16LE {
+            units.flat_map(u16::to_le_bytes).collect()
+        } else {
+            units.flat_map(u16::to_be_bytes).collect()
+        };
+        return Ok(Cow::Owned(bytes));
+    }
+    let (bytes, _, had_errors) = encoding.encode(text);
+    if !had_errors {
+        return Ok(bytes);
+    }
+    Err(text
+        .chars()
+        .find(|c| encoding.encode(c.encode_utf8(&mut [0; 4])).2)
+        .unwrap_or(char::REPLACEMENT_CHARACTER))
+}
+
+fn bom_bytes(encoding: &'static Encoding) -> &'static [u8] {
+    if encoding == UTF_16LE {
+        b"\xff\xfe"
+    } else if encoding == UTF_16BE {
+        b"\xfe\xff"
+    } else if encoding == UTF_8 {
+        b"\xef\xbb\xbf"
+    } else {
+        b""
+    }
 }
 
 pub fn detect_eol(content: &str) -> EolStyle {
@@ -87,42 +332,329 @@ pub fn detect_eol(content: &str) -> EolStyle {
 }
 
 pub fn sidecar_path_for(path: &Path) -> PathBuf {
+    sidecar_path_with_suffix(path, ".dopepad.json")
+}
+
+pub fn legacy_sidecar_path_for(path: &Path) -> PathBuf {
+    sidecar_path_with_suffix(path, ".dopedpad.json")
+}
+
+fn sidecar_path_with_suffix(path: &Path, suffix: &str) -> PathBuf {
     let mut out = PathBuf::from(path);
     let mut file_name: OsString = path
         .file_name()
         .map(|s| s.to_os_string())
         .unwrap_or_else(|| OsString::from("untitled.txt"));
-    file_name.push(".dopepad.json");
+    file_name.push(suffix);
     out.set_file_name(file_name);
     out
 }
 
-pub fn load_sidecar(path: &Path) -> Result<ColorMap, IoError> {
+fn migrate_legacy_sidecar(path: &Path) -> Result<(), IoError> {
+    let sidecar = sidecar_path_for(path);
+    let legacy = legacy_sidecar_path_for(path);
+    if sidecar.exists() || !legacy.exists() {
+        return Ok(());
+    }
+    fs::rename(&legacy, &sidecar).map_err(|source| IoError::Write {
+        path: sidecar.display().to_string(),
+        source,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SidecarIndex {
+    #[serde(default = "default_sidecar_version")]
+    version: u32,
+    #[serde(default)]
+    files: BTreeMap<String, Sidecar>,
+}
+
+impl Default for SidecarIndex {
+    fn default() -> Self {
+        Self {
+            version: SIDECAR_VERSION,
+            files: BTreeMap::new(),
+        }
+    }
+}
+
+pub fn index_path_for(path: &Path) -> PathBuf {
+    path.parent()
+        .map(|p| p.join(INDEX_FILE_NAME))
+        .unwrap_or_else(|| PathBuf::from(INDEX_FILE_NAME))
+}
+
+fn index_key_for(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "untitled.txt".to_string())
+}
+
+fn load_entry(path: &Path, storage: SidecarStorage) -> Result<Option<Sidecar>, IoError> {
+    if storage == SidecarStorage::Index {
+        let mut index = load_index(&index_path_for(path))?;
+        if let Some(entry) = index.files.remove(&index_key_for(path)) {
+            return Ok(Some(entry));
+        }
+    }
+    migrate_legacy_sidecar(path)?;
+    read_sidecar(&sidecar_path_for(path))
+}
+
+fn save_entry(path: &Path, entry: Sidecar, storage: SidecarStorage) -> Result<(), IoError> {
+    match storage {
+        SidecarStorage::PerFile => write_sidecar(&sidecar_path_for(path), &entry),
+        SidecarStorage::Index => {
+            let index_path = index_path_for(path);
+            let mut index = load_index(&index_path)?;
+            let key = index_key_for(path);
+            if entry.is_empty() {
+                index.files.remove(&key);
+            } else {
+                index.files.insert(key, entry);
+            }
+            if !index.files.is_empty() || index_path.exists() {
+                save_index(&index_path, &index)?;
+            }
+            remove_if_exists(&sidecar_path_for(path))
+        }
+    }
+}
+
+/// Loads the colors for `path`, dropping any that fall at or past `doc_len`.
+pub fn load_colors(
+    path: &Path,
+    storage: SidecarStorage,
+    doc_len: usize,
+) -> Result<ColorMap, IoError> {
+    load_entry(path, storage)?
+        .map(|entry| sidecar_colors(path, entry, doc_len))
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+/// Writes the colors, keeping any title and tags already stored for `path`.
+pub fn save_colors(path: &Path, colors: &ColorMap, storage: SidecarStorage) -> Result<(), IoError> {
+    let meta = load_meta(path, storage).unwrap_or_default();
+    save_entry(path, Sidecar::from_colors(colors, meta), storage)
+}
+
+pub fn load_meta(path: &Path, storage: SidecarStorage) -> Result<NoteMeta, IoError> {
+    Ok(load_entry(path, storage)?
+        .map(|entry| entry.meta)
+        .unwrap_or_default())
+}
+
+/// Writes the title and tags, keeping any colors already stored for `path`.
+pub fn save_meta(path: &Path, meta: &NoteMeta, storage: SidecarStorage) -> Result<(), IoError> {
+    let mut entry = match load_entry(path, storage)? {
+        Some(entry) => entry.upgraded(),
+        None => Sidecar::from_colors(&ColorMap::new(), NoteMeta::default()),
+    };
+    entry.meta = meta.clone();
+    save_entry(path, entry, storage)
+}
+
+pub fn remove_colors(path: &Path, storage: SidecarStorage) -> Result<(), IoError> {
+    if storage == SidecarStorage::Index {
+        let index_path = index_path_for(path);
+        if index_path.exists() {
+            let mut index = read_index_value(&index_path)?;
+            let removed = index
+                .get_mut("files")
+                .and_then(serde_json::Value::as_object_mut)
+                .and_then(|files| files.remove(&index_key_for(path)));
+            if removed.is_some() {
+                write_index_value(&index_path, &index)?;
+            }
+        }
+    }
+    remove_if_exists(&legacy_sidecar_path_for(path))?;
+    remove_if_exists(&sidecar_path_for(path))
+}
+
+/// Everything `remove_colors` would delete for a note, copied verbatim so it
+/// can be put back even when it does not parse.
+#[derive(Debug, Clone, Default)]
+pub struct SidecarSnapshot {
+    files: Vec<(PathBuf, Vec<u8>)>,
+    index_entry: Option<serde_json::Value>,
+}
+
+pub fn snapshot_sidecar(path: &Path, storage: SidecarStorage) -> Result<SidecarSnapshot, IoError> {
+    let mut snapshot = SidecarSnapshot::default();
+    for sidecar in [legacy_sidecar_path_for(path), sidecar_path_for(path)] {
+        match fs::read(&sidecar) {
+            Ok(bytes) => snapshot.files.push((sidecar, bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(source) => {
+                return Err(IoError::Read {
+                    path: sidecar.display().to_string(),
+                    source,
+                });
+            }
+        }
+    }
+    let index_path = index_path_for(path);
+    if storage == SidecarStorage::Index && index_path.exists() {
+        let mut index = read_index_value(&index_path)?;
+        snapshot.index_entry = index
+            .get_mut("files")
+            .and_then(|files| files.get_mut(index_key_for(path)))
+            .map(serde_json::Value::take);
+    }
+    Ok(snapshot)
+}
+
+/// Writes a snapshot taken by `snapshot_sidecar` back for `path`.
+pub fn restore_sidecar(path: &Path, snapshot: &SidecarSnapshot) -> Result<(), IoError> {
+    for (sidecar, bytes) in &snapshot.files {
+        fs::write(sidecar, bytes).map_err(|source| IoError::Write {
+            path: sidecar.display().to_string(),
+            source,
+        })?;
+    }
+    let Some(entry) = &snapshot.index_entry else {
+        return Ok(());
+    };
+    let index_path = index_path_for(path);
+    let mut index = if index_path.exists() {
+        read_index_value(&index_path)?
+    } else {
+        serde_json::json!({ "version": SIDECAR_VERSION })
+    };
+    let files = index.as_object_mut().map(|index| {
+        index
+            .entry("files")
+            .or_insert_with(|| serde_json::json!({}))
+    });
+    if let Some(serde_json::Value::Object(files)) = files {
+        files.insert(index_key_for(path), entry.clone());
+    }
+    write_index_value(&index_path, &index)
+}
+
+fn read_index_value(path: &Path) -> Result<serde_json::Value, IoError> {
+    let raw = fs::read_to_string(path).map_err(|source| IoError::Read {
+        path: path.display().to_string(),
+        source,
+    })?;
+    serde_json::from_str(&raw).map_err(|source| IoError::SidecarParse {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+fn write_index_value(path: &Path, index: &serde_json::Value) -> Result<(), IoError> {
+    let raw = serde_json::to_string_pretty(index).map_err(|source| IoError::SidecarSerialize {
+        path: path.display().to_string(),
+        source,
+    })?;
+    fs::write(path, raw).map_err(|source| IoError::Write {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+fn load_index(path: &Path) -> Result<SidecarIndex, IoError> {
     if !path.exists() {
-        return Ok(BTreeMap::new());
+        return Ok(SidecarIndex::default());
     }
     let raw = fs::read_to_string(path).map_err(|source| IoError::Read {
         path: path.display().to_string(),
         source,
     })?;
-    let parsed: Sidecar = serde_json::from_str(&raw).map_err(|source| IoError::SidecarParse {
+    parse_versioned(path, &raw)
+}
+
+fn save_index(path: &Path, index: &SidecarIndex) -> Result<(), IoError> {
+    let raw = serde_json::to_string_pretty(index).map_err(|source| IoError::SidecarSerialize {
         path: path.display().to_string(),
         source,
     })?;
-    if parsed.char_colors.is_empty() {
-        Ok(parsed.line_colors)
+    fs::write(path, raw).map_err(|source| IoError::Write {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), IoError> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(source) => Err(IoError::Write {
+            path: path.display().to_string(),
+            source,
+        }),
+    }
+}
+
+fn sidecar_colors(path: &Path, sidecar: Sidecar, doc_len: usize) -> Result<ColorMap, IoError> {
+    let mut colors = if sidecar.char_colors.is_empty() {
+        sidecar.line_colors
     } else {
-        Ok(parsed.char_colors)
+        sidecar.char_colors
+    };
+    colors.split_off(&doc_len);
+    for run in sidecar.char_runs {
+        let end = run
+            .start
+            .checked_add(run.len)
+            .ok_or_else(|| IoError::BadColorRun {
+                path: path.display().to_string(),
+                start: run.start,
+                len: run.len,
+            })?;
+        colors.extend((run.start..end.min(doc_len)).map(|idx| (idx, run.color)));
     }
+    Ok(colors)
 }
 
-pub fn save_sidecar(path: &Path, colors: &ColorMap) -> Result<(), IoError> {
-    let sidecar = Sidecar {
-        char_colors: colors.clone(),
-        line_colors: BTreeMap::new(),
+fn read_sidecar(path: &Path) -> Result<Option<Sidecar>, IoError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let raw = fs::read_to_string(path).map_err(|source| IoError::Read {
+        path: path.display().to_string(),
+        source,
+    })?;
+    parse_versioned(path, &raw).map(Some)
+}
+
+pub fn load_sidecar(path: &Path, doc_len: usize) -> Result<ColorMap, IoError> {
+    read_sidecar(path)?
+        .map(|sidecar| sidecar_colors(path, sidecar, doc_len))
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+fn parse_versioned<T: serde::de::DeserializeOwned>(path: &Path, raw: &str) -> Result<T, IoError> {
+    let parse_err = |source| IoError::SidecarParse {
+        path: path.display().to_string(),
+        source,
     };
+    let value: serde_json::Value = serde_json::from_str(raw).map_err(parse_err)?;
+    let version = value
+        .get("version")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(default_sidecar_version() as u64);
+    match version {
+        1 | 2 => serde_json::from_value(value).map_err(parse_err),
+        _ => Err(IoError::UnsupportedSidecarVersion {
+            path: path.display().to_string(),
+            version,
+        }),
+    }
+}
+
+pub fn save_sidecar(path: &Path, colors: &ColorMap) -> Result<(), IoError> {
+    write_sidecar(path, &Sidecar::from_colors(colors, NoteMeta::default()))
+}
+
+fn write_sidecar(path: &Path, sidecar: &Sidecar) -> Result<(), IoError> {
     let raw =
-        serde_json::to_string_pretty(&sidecar).map_err(|source| IoError::SidecarSerialize {
+        serde_json::to_string_pretty(sidecar).map_err(|source| IoError::SidecarSerialize {
             path: path.display().to_string(),
             source,
         })?;
@@ -136,28 +668,298 @@ pub fn save_sidecar(path: &Path, colors: &ColorMap) -> Result<(), IoError> {
 mod tests {
     use std::collections::BTreeMap;
 
+    use encoding_rs::{UTF_8, UTF_16LE, WINDOWS_1252};
     use tempfile::tempdir;
 
     use super::{
-        EolStyle, detect_eol, load_document, load_sidecar, save_document, save_sidecar,
+        EolStyle, IoError, NoteMeta, SIDECAR_VERSION, SidecarStorage, detect_eol, index_path_for,
+        legacy_sidecar_path_for, load_colors, load_document, load_meta, load_sidecar,
+        remove_colors, save_colors, save_document, save_document_chunks, save_meta, save_sidecar,
         sidecar_path_for,
     };
 
+    const DOC_LEN: usize = 1_000;
+
     #[test]
     fn eol_detection_and_preservation_work() {
         let dir = tempdir().expect("tempdir");
         let path = dir.path().join("f.txt");
         std::fs::write(&path, "a\r\nb\r\n").expect("write");
-        let doc = load_document(&path).expect("load");
+        let doc = load_document(&path, None).expect("load");
         assert_eq!(doc.eol, EolStyle::Crlf);
         assert_eq!(doc.text, "a\nb\n");
 
-        save_document(&path, &doc.text, doc.eol).expect("save");
+        save_document(&path, &doc.text, doc.eol, doc.encoding, doc.bom).expect("save");
         let saved = std::fs::read_to_string(&path).expect("read");
         assert!(saved.contains("\r\n"));
         assert_eq!(detect_eol(&saved), EolStyle::Crlf);
     }
 
+    #[test]
+    fn chunked_save_translates_eol_across_chunks() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("f.txt");
+        let chunks = ["a\nb", "\n", "c\n\nd"];
+        save_document_chunks(&path, chunks.into_iter(), EolStyle::Crlf, UTF_8, false)
+            .expect("save");
+        let saved = std::fs::read_to_string(&path).expect("read");
+        assert_eq!(saved, "a\r\nb\r\nc\r\n\r\nd");
+
+        save_document_chunks(&path, chunks.into_iter(), EolStyle::Lf, UTF_8, false).expect("save");
+        let saved = std::fs::read_to_string(&path).expect("read");
+        assert_eq!(saved, "a\nb\nc\n\nd");
+    }
+
+    #[test]
+    fn latin1_file_roundtrips_without_mangling() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("latin.txt");
+        std::fs::write(&path, b"caf\xe9\n").expect("write");
+        let doc = load_document(&path, None).expect("load");
+        assert_eq!(doc.encoding, WINDOWS_1252);
+        assert_eq!(doc.text, "café\n");
+
+        save_document(&path, &doc.text, doc.eol, doc.encoding, doc.bom).expect("save");
+        assert_eq!(std::fs::read(&path).expect("read"), b"caf\xe9\n");
+    }
+
+    #[test]
+    fn unencodable_characters_fail_the_save_and_keep_the_file() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("latin.txt");
+        std::fs::write(&path, b"caf\xe9\n").expect("write");
+        let doc = load_document(&path, None).expect("load");
+
+        let err = save_document(&path, "café 😀\n", doc.eol, doc.encoding, doc.bom)
+            .expect_err("emoji is not in windows-1252");
+        assert!(matches!(err, IoError::Unencodable { ch: '😀', .. }));
+        assert!(err.to_string().contains("U+1F600"));
+        assert_eq!(std::fs::read(&path).expect("read"), b"caf\xe9\n");
+    }
+
+    #[test]
+    fn utf16_and_bom_files_save_in_their_own_encoding() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("wide.txt");
+        std::fs::write(&path, b"\xff\xfea\x00\r\x00\n\x00").expect("write");
+        let doc = load_document(&path, None).expect("load");
+        assert_eq!((doc.encoding, doc.bom), (UTF_16LE, true));
+        save_document(&path, "a\n→", doc.eol, doc.encoding, doc.bom).expect("save");
+        assert_eq!(
+            std::fs::read(&path).expect("read"),
+            b"\xff\xfea\x00\r\x00\n\x00\x92\x21"
+        );
+
+        std::fs::write(&path, b"\xfe\xff\x00a").expect("write");
+        let doc = load_document(&path, None).expect("load");
+        save_document(&path, "ab", doc.eol, doc.encoding, doc.bom).expect("save");
+        assert_eq!(std::fs::read(&path).expect("read"), b"\xfe\xff\x00a\x00b");
+
+        std::fs::write(&path, b"\xef\xbb\xbfhi").expect("write");
+        let doc = load_document(&path, None).expect("load");
+        assert_eq!(
+            (doc.encoding, doc.bom, doc.text.as_str()),
+            (UTF_8, true, "hi")
+        );
+        save_document(&path, &doc.text, doc.eol, doc.encoding, doc.bom).expect("save");
+        assert_eq!(std::fs::read(&path).expect("read"), b"\xef\xbb\xbfhi");
+    }
+
+    #[test]
+    fn forced_encoding_overrides_detection() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("utf8.txt");
+        std::fs::write(&path, "é").expect("write");
+        let doc = load_document(&path, Some(WINDOWS_1252)).expect("load");
+        assert_eq!(doc.encoding, WINDOWS_1252);
+        assert_eq!(doc.text, "Ã©");
+        assert_eq!(load_document(&path, None).expect("load").encoding, UTF_8);
+    }
+
+    #[test]
+    fn binary_files_are_refused() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("blob.txt");
+        std::fs::write(&path, b"PK\x03\x04\x00\x00data").expect("write");
+        assert!(matches!(
+            load_document(&path, None),
+            Err(IoError::BinaryFile { .. })
+        ));
+
+        std::fs::write(&path, b"\xff\xfea\x00b\x00").expect("write");
+        assert_eq!(load_document(&path, None).expect("utf-16").text, "ab");
+    }
+
+    #[test]
+    fn index_storage_migrates_per_file_sidecars() {
+        let dir = tempdir().expect("tempdir");
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        let mut map = BTreeMap::new();
+        map.insert(3, 4);
+        save_sidecar(&sidecar_path_for(&a), &map).expect("legacy sidecar");
+
+        let loaded = load_colors(&a, SidecarStorage::Index, DOC_LEN).expect("fallback load");
+        assert_eq!(loaded, map);
+
+        save_colors(&a, &loaded, SidecarStorage::Index).expect("save a");
+        save_colors(&b, &map, SidecarStorage::Index).expect("save b");
+        assert!(!sidecar_path_for(&a).exists());
+        assert!(index_path_for(&a).exists());
+        assert_eq!(
+            load_colors(&b, SidecarStorage::Index, DOC_LEN).expect("load b"),
+            map
+        );
+
+        remove_colors(&a, SidecarStorage::Index).expect("remove a");
+        assert!(
+            load_colors(&a, SidecarStorage::Index, DOC_LEN)
+                .expect("load a")
+                .is_empty()
+        );
+        assert_eq!(
+            load_colors(&b, SidecarStorage::Index, DOC_LEN).expect("load b"),
+            map
+        );
+    }
+
+    #[test]
+    fn legacy_typo_sidecar_is_migrated_on_load() {
+        let dir = tempdir().expect("tempdir");
+        let txt = dir.path().join("old.txt");
+        let legacy = legacy_sidecar_path_for(&txt);
+        assert!(legacy.to_string_lossy().ends_with("old.txt.dopedpad.json"));
+        std::fs::write(&legacy, r#"{ "char_colors": { "2": 7 } }"#).expect("write legacy");
+
+        let loaded = load_colors(&txt, SidecarStorage::PerFile, DOC_LEN).expect("load");
+        assert_eq!(loaded.get(&2), Some(&7));
+        assert!(!legacy.exists());
+        assert!(sidecar_path_for(&txt).exists());
+
+        let again = load_colors(&txt, SidecarStorage::PerFile, DOC_LEN).expect("reload");
+        assert_eq!(again, loaded);
+    }
+
+    #[test]
+    fn sidecar_versions_are_checked() {
+        let dir = tempdir().expect("tempdir");
+        let sidecar = dir.path().join("v.txt.dopepad.json");
+
+        std::fs::write(&sidecar, r#"{ "char_colors": { "1": 2 } }"#).expect("write v1");
+        assert_eq!(
+            load_sidecar(&sidecar, DOC_LEN)
+                .expect("implicit v1")
+                .get(&1),
+            Some(&2)
+        );
+
+        std::fs::write(&sidecar, r#"{ "version": 99, "char_colors": {} }"#).expect("write v99");
+        assert!(matches!(
+            load_sidecar(&sidecar, DOC_LEN),
+            Err(IoError::UnsupportedSidecarVersion { version: 99, .. })
+        ));
+
+        save_sidecar(&sidecar, &BTreeMap::new()).expect("save");
+        let raw = std::fs::read_to_string(&sidecar).expect("read");
+        assert!(raw.contains(&format!("\"version\": {SIDECAR_VERSION}")));
+    }
+
+    #[test]
+    fn sidecar_stores_contiguous_runs() {
+        let dir = tempdir().expect("tempdir");
+        let sidecar = dir.path().join("runs.txt.dopepad.json");
+        let mut map = BTreeMap::new();
+        for idx in 0..500 {
+            map.insert(idx, 3);
+        }
+        map.insert(500, 4);
+        map.insert(502, 4);
+
+        save_sidecar(&sidecar, &map).expect("save");
+        let raw = std::fs::read_to_string(&sidecar).expect("read");
+        let json: serde_json::Value = serde_json::from_str(&raw).expect("json");
+        assert_eq!(json["char_runs"].as_array().map(|a| a.len()), Some(3));
+        assert!(json.get("char_colors").is_none());
+        assert_eq!(load_sidecar(&sidecar, DOC_LEN).expect("load"), map);
+    }
+
+    #[test]
+    fn color_runs_are_checked_and_capped_at_the_document_length() {
+        let dir = tempdir().expect("tempdir");
+        let txt = dir.path().join("runs.txt");
+        let sidecar = sidecar_path_for(&txt);
+
+        std::fs::write(
+            &sidecar,
+            format!(
+                r#"{{ "version": 2, "char_runs": [{{ "start": 2, "len": {}, "color": 5 }}] }}"#,
+                usize::MAX - 1
+            ),
+        )
+        .expect("write overflowing run");
+        assert!(matches!(
+            load_colors(&txt, SidecarStorage::PerFile, 4),
+            Err(IoError::BadColorRun { start: 2, .. })
+        ));
+
+        std::fs::write(
+            &sidecar,
+            format!(
+                r#"{{ "version": 2, "char_runs": [{{ "start": 1, "len": {}, "color": 5 }}, {{ "start": 9, "len": 1, "color": 6 }}] }}"#,
+                usize::MAX / 2
+            ),
+        )
+        .expect("write huge run");
+        let colors = load_colors(&txt, SidecarStorage::PerFile, 4).expect("capped");
+        assert_eq!(colors, BTreeMap::from([(1, 5), (2, 5), (3, 5)]));
+
+        std::fs::write(&sidecar, r#"{ "char_colors": { "1": 2, "7": 3 } }"#).expect("write v1");
+        assert_eq!(
+            load_sidecar(&sidecar, 4).expect("v1"),
+            BTreeMap::from([(1, 2)])
+        );
+        let meta = NoteMeta {
+            title: Some("Runs".to_string()),
+            tags: Vec::new(),
+        };
+        save_meta(&txt, &meta, SidecarStorage::PerFile).expect("save meta");
+        assert_eq!(
+            load_colors(&txt, SidecarStorage::PerFile, DOC_LEN).expect("upgraded"),
+            BTreeMap::from([(1, 2), (7, 3)])
+        );
+    }
+
+    #[test]
+    fn colors_survive_crlf_save_and_reload() {
+        use crate::core::TextBuffer;
+
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("crlf.txt");
+        std::fs::write(&path, "one\r\ntwo\r\nthree\r\n").expect("write");
+        let doc = load_document(&path, None).expect("load");
+        let mut b = TextBuffer::from_text(doc.text, Some(path.clone()), false);
+        b.goto_line(3);
+        b.move_right();
+        b.set_current_char_color(Some(6));
+
+        save_document_chunks(&path, b.chunks(), doc.eol, doc.encoding, doc.bom).expect("save");
+        save_colors(&path, &b.char_colors, SidecarStorage::PerFile).expect("save colors");
+        assert!(
+            std::fs::read_to_string(&path)
+                .expect("read")
+                .contains("\r\n")
+        );
+
+        let doc = load_document(&path, None).expect("reload");
+        let mut reloaded = TextBuffer::from_text(doc.text, Some(path.clone()), false);
+        reloaded
+            .set_char_colors(load_colors(&path, SidecarStorage::PerFile, DOC_LEN).expect("colors"));
+        let idx = reloaded.line_start_char_idx(2) + 1;
+        assert_eq!(reloaded.char_color(idx), Some(6));
+        assert_eq!(reloaded.line_text(2).chars().nth(1), Some('h'));
+        assert_eq!(reloaded.char_colors.len(), 1);
+    }
+
     #[test]
     fn sidecar_roundtrip_works() {
         let dir = tempdir().expect("tempdir");
@@ -168,7 +970,33 @@ mod tests {
         map.insert(10, 8);
 
         save_sidecar(&sidecar, &map).expect("save sidecar");
-        let loaded = load_sidecar(&sidecar).expect("load sidecar");
+        let loaded = load_sidecar(&sidecar, DOC_LEN).expect("load sidecar");
         assert_eq!(loaded, map);
     }
+
+    #[test]
+    fn title_and_tags_survive_color_saves_in_both_storages() {
+        let dir = tempdir().expect("tempdir");
+        let txt = dir.path().join("note.txt");
+        let meta = NoteMeta {
+            title: Some("Weekly plan".to_string()),
+            tags: vec!["work".to_string(), "todo".to_string()],
+        };
+        let colors = BTreeMap::from([(3, 4)]);
+
+        for storage in [SidecarStorage::PerFile, SidecarStorage::Index] {
+            save_colors(&txt, &colors, storage).expect("save colors");
+            save_meta(&txt, &meta, storage).expect("save meta");
+            save_colors(&txt, &colors, storage).expect("save colors again");
+            assert_eq!(load_meta(&txt, storage).expect("meta"), meta);
+            assert_eq!(load_colors(&txt, storage, DOC_LEN).expect("colors"), colors);
+
+            save_colors(&txt, &BTreeMap::new(), storage).expect("clear colors");
+            assert_eq!(load_meta(&txt, storage).expect("meta"), meta);
+            save_meta(&txt, &NoteMeta::default(), storage).expect("clear meta");
+            assert!(load_meta(&txt, storage).expect("meta").is_empty());
+        }
+        let index = std::fs::read_to_string(index_path_for(&txt)).expect("index");
+        assert!(!index.contains("note.txt"));
+    }
 }
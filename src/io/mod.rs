@@ -1,6 +1,8 @@
 mod filesystem;
 
 pub use filesystem::{
-    EolStyle, FileData, IoError, load_document, load_sidecar, save_document, save_sidecar,
-    sidecar_path_for,
+    EolStyle, FileData, IoError, NoteMeta, SidecarSnapshot, SidecarStorage, index_path_for,
+    legacy_sidecar_path_for, load_colors, load_document, load_meta, load_sidecar, remove_colors,
+    restore_sidecar, save_colors, save_document, save_document_chunks, save_meta, save_sidecar,
+    sidecar_path_for, snapshot_sidecar,
 };
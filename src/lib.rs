@@ -2,4 +2,6 @@ pub mod app;
 pub mod core;
 pub mod input;
 pub mod io;
+#[doc(hidden)]
+pub mod testing;
 pub mod ui;
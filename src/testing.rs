@@ -0,0 +1,105 @@
+//! Helpers shared by the unit tests and `tests/app_harness.rs` for driving an
+//! `App` with scripted keys and reading back what was drawn.
+
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use encoding_rs::UTF_8;
+use ratatui::Terminal;
+use ratatui::backend::TestBackend;
+
+use crate::app::{App, EventSource};
+use crate::core::TextBuffer;
+use crate::io::EolStyle;
+
+/// Events fed to `run_loop` in order; the loop stops once they run out.
+#[derive(Debug, Default)]
+pub struct Script(VecDeque<Event>);
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn event(mut self, event: Event) -> Self {
+        self.0.push_back(event);
+        self
+    }
+
+    pub fn key(self, code: KeyCode, modifiers: KeyModifiers) -> Self {
+        self.event(Event::Key(KeyEvent::new(code, modifiers)))
+    }
+
+    pub fn press(self, code: KeyCode) -> Self {
+        self.key(code, KeyModifiers::NONE)
+    }
+
+    pub fn ctrl(self, c: char) -> Self {
+        self.key(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    pub fn type_str(mut self, text: &str) -> Self {
+        for c in text.chars() {
+            self = self.press(KeyCode::Char(c));
+        }
+        self
+    }
+
+    pub fn repeat(mut self, code: KeyCode, times: usize) -> Self {
+        for _ in 0..times {
+            self = self.press(code);
+        }
+        self
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl EventSource for Script {
+    fn next_event(&mut self, _timeout: Duration) -> io::Result<Option<Event>> {
+        Ok(self.0.pop_front())
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+pub fn app(buffer: TextBuffer, root: &Path) -> App {
+    App::new(buffer, EolStyle::Lf, UTF_8, false, root.to_path_buf())
+}
+
+pub fn terminal() -> Terminal<TestBackend> {
+    Terminal::new(TestBackend::new(120, 40)).expect("terminal")
+}
+
+/// Every cell symbol of the last drawn frame, row after row.
+pub fn screen(terminal: &Terminal<TestBackend>) -> String {
+    terminal
+        .backend()
+        .buffer()
+        .content()
+        .iter()
+        .map(|cell| cell.symbol())
+        .collect()
+}
+
+/// Cell index of the first occurrence of `text` in a `screen` string.
+pub fn cell_of(screen: &str, text: &str) -> usize {
+    screen[..screen.find(text).expect(text)].chars().count()
+}
+
+pub fn press(app: &mut App, code: KeyCode) {
+    app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+}
+
+pub fn type_str(app: &mut App, text: &str) {
+    for c in text.chars() {
+        press(app, KeyCode::Char(c));
+    }
+}
@@ -0,0 +1,403 @@
+use std::ops::Range;
+use std::path::Path;
+
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+
+const MARKDOWN_EXTENSIONS: [&str; 2] = ["md", "markdown"];
+
+pub fn is_markdown_path(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+        MARKDOWN_EXTENSIONS
+            .iter()
+            .any(|m| e.eq_ignore_ascii_case(m))
+    })
+}
+
+/// Light Markdown styling for one raw line, as char ranges into it.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MarkdownLine {
+    pub heading: bool,
+    pub bullet: Option<usize>,
+    pub bold: Vec<Range<usize>>,
+    pub italic: Vec<Range<usize>>,
+}
+
+pub fn markdown_line(line: &str) -> MarkdownLine {
+    let chars: Vec<char> = line.chars().collect();
+    let indent = chars
+        .iter()
+        .take_while(|c| **c == ' ' || **c == '\t')
+        .count();
+    let rest = &chars[indent..];
+
+    let hashes = rest.iter().take_while(|c| **c == '#').count();
+    let heading =
+        indent <= 3 && (1..=6).contains(&hashes) && rest.get(hashes).is_none_or(|c| *c == ' ');
+    let bullet = (matches!(rest.first(), Some('-' | '*' | '+')) && rest.get(1) == Some(&' '))
+        .then_some(indent);
+
+    let mut out = MarkdownLine {
+        heading,
+        bullet,
+        ..MarkdownLine::default()
+    };
+    let mut i = bullet.map_or(0, |b| b + 1);
+    while i < chars.len() {
+        match chars[i] {
+            '`' => {
+                i = find(&chars, i + 1, |j| chars[j] == '`').map_or(chars.len(), |j| j + 1);
+            }
+            '*' if chars.get(i + 1) == Some(&'*') && opens(&chars, i + 2) => {
+                match find(&chars, i + 2, |j| {
+                    chars[j] == '*' && chars.get(j + 1) == Some(&'*') && closes(&chars, j)
+                }) {
+                    Some(j) => {
+                        out.bold.push(i..j + 2);
+                        i = j + 2;
+                    }
+                    None => i += 2,
+                }
+            }
+            marker @ ('*' | '_')
+                if opens(&chars, i + 1) && (marker == '*' || !word_before(&chars, i)) =>
+            {
+                match find(&chars, i + 1, |j| {
+                    chars[j] == marker
+                        && closes(&chars, j)
+                        && (marker == '*' || !chars.get(j + 1).is_some_and(|c| c.is_alphanumeric()))
+                }) {
+                    Some(j) => {
+                        out.italic.push(i..j + 1);
+                        i = j + 1;
+                    }
+                    None => i += 1,
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    out
+}
+
+/// Rendered Markdown, with the source line each rendered line came from so
+/// the pane can follow the editor cursor.
+#[derive(Debug, Default)]
+pub struct Preview {
+    pub lines: Vec<Line<'static>>,
+    pub sources: Vec<usize>,
+}
+
+impl Preview {
+    /// First rendered line to show so the block at `cursor_line` sits about a
+    /// third of the way down a pane `height` rows tall.
+    pub fn scroll_for(&self, cursor_line: usize, height: usize) -> usize {
+        let at = self.sources.partition_point(|&s| s <= cursor_line);
+        at.saturating_sub(1).saturating_sub(height / 3)
+    }
+}
+
+pub fn render_preview(text: &str) -> Preview {
+    let mut builder = PreviewBuilder {
+        line_starts: std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect(),
+        ..PreviewBuilder::default()
+    };
+    for (event, range) in Parser::new(text).into_offset_iter() {
+        builder.source = builder.line_starts.partition_point(|&s| s <= range.start) - 1;
+        builder.event(event);
+    }
+    builder.flush();
+    while builder
+        .preview
+        .lines
+        .last()
+        .is_some_and(|l| l.spans.is_empty())
+    {
+        builder.preview.lines.pop();
+        builder.preview.sources.pop();
+    }
+    builder.preview
+}
+
+#[derive(Default)]
+struct PreviewBuilder {
+    preview: Preview,
+    line_starts: Vec<usize>,
+    source: usize,
+    line_source: Option<usize>,
+    spans: Vec<Span<'static>>,
+    heading: Option<HeadingLevel>,
+    bold: usize,
+    italic: usize,
+    link: usize,
+    code_block: bool,
+    quote: usize,
+    lists: Vec<Option<u64>>,
+    marker: Option<String>,
+    hanging: usize,
+}
+
+impl PreviewBuilder {
+    fn event(&mut self, event: Event<'_>) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) if self.code_block => {
+                for line in text.lines() {
+                    self.push(format!("    {line}"), Style::default().fg(Color::Yellow));
+                    self.flush();
+                }
+            }
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => {
+                let style = self.style();
+                self.push(text.to_string(), style);
+            }
+            Event::Code(code) => self.push(code.to_string(), Style::default().fg(Color::Yellow)),
+            Event::TaskListMarker(done) => {
+                let style = self.style();
+                self.push(if done { "[x] " } else { "[ ] " }.to_string(), style);
+            }
+            Event::SoftBreak | Event::HardBreak => self.flush(),
+            Event::Rule => {
+                self.flush();
+                self.push("─".repeat(24), Style::default().fg(Color::DarkGray));
+                self.flush();
+                self.blank();
+            }
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag<'_>) {
+        match tag {
+            Tag::Heading { level, .. } => {
+                self.flush();
+                self.heading = Some(level);
+            }
+            Tag::BlockQuote(_) => {
+                self.flush();
+                self.quote += 1;
+            }
+            Tag::CodeBlock(_) => {
+                self.flush();
+                self.code_block = true;
+            }
+            Tag::List(start) => {
+                self.flush();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush();
+                let depth = self.lists.len().saturating_sub(1);
+                let bullet = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.marker = Some(format!("{}{bullet}", "  ".repeat(depth)));
+            }
+            Tag::Emphasis => self.italic += 1,
+            Tag::Strong => self.bold += 1,
+            Tag::Link { .. } => self.link += 1,
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Heading(_) => {
+                self.flush();
+                self.heading = None;
+                self.blank();
+            }
+            TagEnd::Paragraph => {
+                self.flush();
+                if self.lists.is_empty() {
+                    self.blank();
+                }
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush();
+                self.quote -= 1;
+                self.blank();
+            }
+            TagEnd::CodeBlock => {
+                self.flush();
+                self.code_block = false;
+                self.blank();
+            }
+            TagEnd::List(_) => {
+                self.flush();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank();
+                }
+            }
+            TagEnd::Item => {
+                self.flush();
+                self.marker = None;
+                self.hanging = 0;
+            }
+            TagEnd::Emphasis => self.italic -= 1,
+            TagEnd::Strong => self.bold -= 1,
+            TagEnd::Link => self.link -= 1,
+            _ => {}
+        }
+    }
+
+    fn style(&self) -> Style {
+        let mut style = Style::default();
+        match self.heading {
+            Some(HeadingLevel::H1) => {
+                style = style
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+            }
+            Some(HeadingLevel::H2) => style = style.fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            Some(_) => style = style.add_modifier(Modifier::BOLD),
+            None => {}
+        }
+        if self.bold > 0 {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        if self.italic > 0 {
+            style = style.add_modifier(Modifier::ITALIC);
+        }
+        if self.link > 0 {
+            style = style.fg(Color::Blue).add_modifier(Modifier::UNDERLINED);
+        }
+        style
+    }
+
+    fn push(&mut self, text: String, style: Style) {
+        if self.spans.is_empty() {
+            self.line_source = Some(self.source);
+            if self.quote > 0 {
+                self.spans.push(Span::styled(
+                    "│ ".repeat(self.quote),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            if let Some(marker) = self.marker.take() {
+                self.hanging = marker.chars().count();
+                self.spans
+                    .push(Span::styled(marker, Style::default().fg(Color::Cyan)));
+            } else if self.hanging > 0 {
+                self.spans.push(Span::raw(" ".repeat(self.hanging)));
+            }
+        }
+        self.spans.push(Span::styled(text, style));
+    }
+
+    fn flush(&mut self) {
+        if self.spans.is_empty() {
+            return;
+        }
+        let spans = std::mem::take(&mut self.spans);
+        self.preview.lines.push(Line::from(spans));
+        self.preview
+            .sources
+            .push(self.line_source.take().unwrap_or(self.source));
+    }
+
+    fn blank(&mut self) {
+        if self
+            .preview
+            .lines
+            .last()
+            .is_some_and(|l| !l.spans.is_empty())
+        {
+            self.preview.lines.push(Line::default());
+            let source = self.preview.sources.last().copied().unwrap_or(0);
+            self.preview.sources.push(source);
+        }
+    }
+}
+
+fn find(chars: &[char], from: usize, pred: impl Fn(usize) -> bool) -> Option<usize> {
+    (from..chars.len()).find(|&j| pred(j))
+}
+
+fn opens(chars: &[char], after: usize) -> bool {
+    chars
+        .get(after)
+        .is_some_and(|c| !c.is_whitespace() && *c != '*')
+}
+
+fn closes(chars: &[char], at: usize) -> bool {
+    at > 0 && !chars[at - 1].is_whitespace()
+}
+
+fn word_before(chars: &[char], at: usize) -> bool {
+    at > 0 && chars[at - 1].is_alphanumeric()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{MarkdownLine, is_markdown_path, markdown_line, render_preview};
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn headings_bullets_and_emphasis_are_found() {
+        assert!(markdown_line("## Plan").heading);
+        assert!(!markdown_line("#hashtag").heading);
+        assert!(!markdown_line("####### seven").heading);
+
+        let line = markdown_line("  - buy *milk* and **eggs**");
+        assert_eq!(line.bullet, Some(2));
+        assert_eq!(line.italic, [8..14]);
+        assert_eq!(line.bold, [19..27]);
+
+        assert_eq!(
+            markdown_line("snake_case_name and `*code*`"),
+            MarkdownLine::default()
+        );
+        assert_eq!(markdown_line("a _word_ here").italic, [2..8]);
+        assert_eq!(markdown_line("2 * 3 * 4").italic, []);
+    }
+
+    #[test]
+    fn preview_renders_blocks_and_maps_them_to_source_lines() {
+        let text = "# Title\n\nSome *soft*\ntext.\n\n- one\n- two\n  1. nested\n\n> quoted\n\n```\nlet x;\n```\n";
+        let preview = render_preview(text);
+        let rendered: Vec<String> = preview
+            .lines
+            .iter()
+            .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect())
+            .collect();
+        assert_eq!(
+            rendered,
+            [
+                "Title",
+                "",
+                "Some soft",
+                "text.",
+                "",
+                "• one",
+                "• two",
+                "  1. nested",
+                "",
+                "│ quoted",
+                "",
+                "    let x;",
+            ]
+        );
+        assert_eq!(preview.sources, [0, 0, 2, 3, 3, 5, 6, 7, 7, 9, 9, 12]);
+        assert_eq!(preview.scroll_for(6, 3), 5);
+        assert_eq!(preview.scroll_for(0, 30), 0);
+    }
+
+    #[test]
+    fn only_markdown_extensions_count() {
+        assert!(is_markdown_path(Path::new("notes/plan.md")));
+        assert!(is_markdown_path(Path::new("README.Markdown")));
+        assert!(!is_markdown_path(Path::new("plan.txt")));
+    }
+}
@@ -1,13 +1,24 @@
+mod markdown;
+
+use std::ops::Range;
+
+use chrono::{DateTime, Local};
 use ratatui::Frame;
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
-use tui_textarea::TextArea;
+use tui_textarea::{CursorMove, TextArea};
 use unicode_width::UnicodeWidthChar;
 
-use crate::app::{AppMode, ConfirmChoice, FileTree, Overlay, TreeNodeKind};
-use crate::core::TextBuffer;
+use crate::app::{
+    AppMode, ConfirmChoice, FileTree, NameIssue, NoteForm, Overlay, TreeGroup, TreeNodeKind,
+    TreeSort, UnsavedChoice,
+};
+use crate::core::{Dictionary, TextBuffer};
+use crate::input::{Keymap, help_lines};
+pub use markdown::is_markdown_path;
+use markdown::{markdown_line, render_preview};
 
 const ASCII_FULL: [&str; 9] = [
     "▓█████▄  ▒█████   ██▓███  ▓█████  ██▓███   ▄▄▄      ▓█████▄",
@@ -29,6 +40,10 @@ const ASCII_COMPACT: [&str; 4] = [
 ];
 
 const ASCII_MICRO: &str = "▓█████▄  ▒█████   ██▓███  ▓█████  ██▓███";
+const MIN_WIDTH: u16 = 20;
+const MIN_HEIGHT: u16 = 5;
+const TAB_WIDTH: usize = 4;
+const DECOR_MARGIN: usize = 64;
 pub struct UiModel<'a> {
     pub buffer: &'a TextBuffer,
     pub mode: AppMode,
@@ -36,19 +51,46 @@ pub struct UiModel<'a> {
     pub file_title: String,
     pub hint: String,
     pub no_style: bool,
+    pub encoding: &'static str,
+    pub eol: &'static str,
     pub file_tree: &'a FileTree,
     pub categories: &'a [String],
+    pub name_issue: Option<NameIssue>,
+    pub keymap: Keymap,
+    pub normal_mode: bool,
+    pub clock: Option<String>,
+    pub indent_guides: bool,
+    pub spell: Option<&'a Dictionary>,
+    pub markdown_preview: bool,
+    pub trailing_whitespace: bool,
+    pub ruler: Option<usize>,
+    pub recording: bool,
 }
 
 pub fn draw(frame: &mut Frame<'_>, model: UiModel<'_>) {
     let size = frame.area();
-    if size.width >= 100 {
+    if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+        draw_too_small(frame, size);
+    } else if size.width >= 100 {
         draw_wide(frame, size, model);
     } else {
         draw_narrow(frame, size, model);
     }
 }
 
+fn draw_too_small(frame: &mut Frame<'_>, area: Rect) {
+    let text = format!(
+        "Terminal too small ({}x{}), need {MIN_WIDTH}x{MIN_HEIGHT}",
+        area.width, area.height
+    );
+    frame.render_widget(
+        Paragraph::new(text)
+            .style(Style::default().fg(Color::Yellow))
+            .wrap(Wrap { trim: true }),
+        area,
+    );
+}
+
 fn draw_wide(frame: &mut Frame<'_>, area: Rect, model: UiModel<'_>) {
     let sidebar_width = 68.min(area.width.saturating_sub(20)).max(28);
     let chunks = Layout::default()
@@ -61,13 +103,45 @@ fn draw_wide(frame: &mut Frame<'_>, area: Rect, model: UiModel<'_>) {
         .split(chunks[0]);
 
     draw_ascii_sidebar(frame, body[0], &model);
-    let cursor = draw_editor(frame, body[1], &model);
+    let preview =
+        model.markdown_preview && model.buffer.path.as_deref().is_some_and(is_markdown_path);
+    let cursor = if preview {
+        let halves = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(body[1]);
+        draw_preview(frame, halves[1], model.buffer);
+        draw_editor(frame, halves[0], &model)
+    } else {
+        draw_editor(frame, body[1], &model)
+    };
     draw_status(frame, chunks[1], &model);
 
+    let overlay_cursor = draw_overlay(frame, area, &model);
+    let cursor = match model.overlay {
+        Overlay::None => cursor,
+        _ => overlay_cursor,
+    };
     if let Some((x, y)) = cursor {
         frame.set_cursor_position((x, y));
     }
-    draw_overlay(frame, area, model.overlay, model.categories);
+}
+
+fn draw_preview(frame: &mut Frame<'_>, area: Rect, buffer: &TextBuffer) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(panel_border(false))
+        .title("Preview");
+    let height = block.inner(area).height as usize;
+    let preview = render_preview(&buffer.as_string());
+    let scroll = preview.scroll_for(buffer.cursor.line, height);
+    frame.render_widget(
+        Paragraph::new(preview.lines)
+            .block(block)
+            .wrap(Wrap { trim: false })
+            .scroll((scroll.min(u16::MAX as usize) as u16, 0)),
+        area,
+    );
 }
 
 fn draw_narrow(frame: &mut Frame<'_>, area: Rect, model: UiModel<'_>) {
@@ -92,10 +166,14 @@ fn draw_narrow(frame: &mut Frame<'_>, area: Rect, model: UiModel<'_>) {
 
     let cursor = draw_editor(frame, chunks[1], &model);
     draw_status(frame, chunks[2], &model);
+    let overlay_cursor = draw_overlay(frame, area, &model);
+    let cursor = match model.overlay {
+        Overlay::None => cursor,
+        _ => overlay_cursor,
+    };
     if let Some((x, y)) = cursor {
         frame.set_cursor_position((x, y));
     }
-    draw_overlay(frame, area, model.overlay, model.categories);
 }
 
 fn draw_ascii_sidebar(frame: &mut Frame<'_>, area: Rect, model: &UiModel<'_>) {
@@ -107,12 +185,13 @@ fn draw_ascii_sidebar(frame: &mut Frame<'_>, area: Rect, model: &UiModel<'_>) {
         &[ASCII_MICRO]
     };
 
-    let ascii_height = (variant.len() as u16).min(area.height.saturating_sub(6));
+    let ascii_height = (variant.len() as u16).min(area.height.saturating_sub(11));
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(ascii_height.max(1)),
             Constraint::Length(6),
+            Constraint::Length(5),
             Constraint::Min(1),
         ])
         .split(area);
@@ -138,20 +217,50 @@ fn draw_ascii_sidebar(frame: &mut Frame<'_>, area: Rect, model: &UiModel<'_>) {
         chunks[1],
     );
 
+    frame.render_widget(
+        Paragraph::new(color_legend_lines(model.buffer.active_color()))
+            .block(Block::default().title("Colors").borders(Borders::ALL)),
+        chunks[2],
+    );
+
+    let dirty_path = model.buffer.path.as_ref().filter(|_| model.buffer.dirty);
     let mut tree_lines = Vec::new();
     for (idx, node) in model.file_tree.nodes.iter().enumerate() {
+        if !model.file_tree.is_visible(node) {
+            continue;
+        }
         let selected = model.file_tree.focus && idx == model.file_tree.selected;
         let marker = if selected { ">" } else { " " };
+        let unsaved = dirty_path.is_some() && node.path.as_ref() == dirty_path;
         let style = match node.kind {
             TreeNodeKind::Category => Style::default().add_modifier(Modifier::BOLD),
             TreeNodeKind::Empty => Style::default().fg(Color::DarkGray),
+            TreeNodeKind::File if unsaved => Style::default().fg(Color::Yellow),
             TreeNodeKind::File => Style::default(),
         };
+        let label = if unsaved {
+            format!("{} *", node.label)
+        } else {
+            node.label.clone()
+        };
         tree_lines.push(Line::from(vec![
             Span::raw(format!("{marker} ")),
-            Span::styled(node.label.clone(), style),
+            Span::styled(label, style),
         ]));
     }
+    let sort = match model.file_tree.sort {
+        TreeSort::Name => "",
+        TreeSort::Modified => " (recent)",
+    };
+    let group = match model.file_tree.group {
+        TreeGroup::Category => "",
+        TreeGroup::Tag => " by tag",
+    };
+    let files_title = if model.file_tree.filter.is_empty() && !model.file_tree.filtering {
+        format!("Files{group}{sort}")
+    } else {
+        format!("Files{group}{sort} /{}", model.file_tree.filter)
+    };
     if tree_lines.is_empty() {
         tree_lines.push(Line::from("Tree is empty."));
         tree_lines.push(Line::from("Press C to create a category."));
@@ -159,34 +268,110 @@ fn draw_ascii_sidebar(frame: &mut Frame<'_>, area: Rect, model: &UiModel<'_>) {
 
     frame.render_widget(
         Paragraph::new(tree_lines)
-            .block(Block::default().title("Files").borders(Borders::ALL))
+            .block(
+                Block::default()
+                    .title(files_title)
+                    .borders(Borders::ALL)
+                    .border_style(panel_border(model.file_tree.focus)),
+            )
             .wrap(Wrap { trim: false }),
-        chunks[2],
+        chunks[3],
     );
 }
 
+fn panel_border(focused: bool) -> Style {
+    if focused {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default().fg(Color::DarkGray)
+    }
+}
+
+fn color_legend_lines(active: Option<u8>) -> Vec<Line<'static>> {
+    let entry = |id: u8| {
+        let key = if id == 0 { 10 } else { id + 1 };
+        let label = format!(" F{key:<2} C{id} {:<11}", color_name(id));
+        let label_style = if active == Some(id) || (id == 0 && active.is_none()) {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        vec![
+            Span::styled("██", Style::default().fg(color_for_id(id))),
+            Span::styled(label, label_style),
+        ]
+    };
+    (0..3u8)
+        .map(|row| {
+            Line::from(
+                (1..=3u8)
+                    .flat_map(|col| entry((row * 3 + col) % 9))
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect()
+}
+
 fn draw_status(frame: &mut Frame<'_>, area: Rect, model: &UiModel<'_>) {
     let dirty = if model.buffer.dirty { "*" } else { "" };
     let mode = match model.mode {
         AppMode::Edit => "EDIT",
         AppMode::ReadOnly => "READONLY",
     };
+    let typing = if model.normal_mode {
+        "NOR"
+    } else if model.buffer.append_only() {
+        "APP"
+    } else if model.buffer.overwrite {
+        "OVR"
+    } else {
+        "INS"
+    };
+    let typing = if model.recording {
+        format!("{typing} REC")
+    } else {
+        typing.to_string()
+    };
     let ln = model.buffer.cursor.line + 1;
     let col = model.buffer.cursor.col + 1;
+    let cursors = match model.buffer.extra_cursors.len() {
+        0 => String::new(),
+        n => format!(" (+{n} cursors)"),
+    };
     let color = model
         .buffer
         .current_char_color()
         .map(|c| format!("C{c}"))
         .unwrap_or_else(|| "C0".to_string());
+    let clock = model
+        .clock
+        .as_deref()
+        .map(|c| format!("{c} | "))
+        .unwrap_or_default();
     let text = format!(
-        " {}{} | {} | Ln {}, Col {} | {} | {}",
-        model.file_title, dirty, mode, ln, col, color, model.hint
+        " {}{} | {} | {} | {} {} | Ln {}, Col {}{} | {} | {}{}",
+        model.file_title,
+        dirty,
+        mode,
+        typing,
+        model.encoding,
+        model.eol,
+        ln,
+        col,
+        cursors,
+        color,
+        clock,
+        model.hint
     );
     frame.render_widget(Paragraph::new(text), area);
 }
 
 fn draw_editor(frame: &mut Frame<'_>, area: Rect, model: &UiModel<'_>) -> Option<(u16, u16)> {
-    let block = Block::default().borders(Borders::ALL).title("Editor");
+    let focused = !model.file_tree.focus;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(panel_border(focused))
+        .title("Editor");
     let inner = block.inner(area);
     frame.render_widget(block, area);
 
@@ -199,121 +384,345 @@ fn draw_editor(frame: &mut Frame<'_>, area: Rect, model: &UiModel<'_>) -> Option
     let height = inner.height as usize;
     let mut lines: Vec<Line<'_>> = Vec::with_capacity(height);
     let mut cursor_xy: Option<(u16, u16)> = None;
+    let line_starts = buffer.line_start_char_indices(top, height);
+    let markdown = buffer.path.as_deref().is_some_and(is_markdown_path);
+    let left_col = buffer.viewport.left_col;
+    let brackets = visible_bracket_pair(buffer, &line_starts, left_col, inner.width as usize);
+    let scan = top.saturating_sub(DECOR_MARGIN)..top + height + DECOR_MARGIN;
 
     for row in 0..height {
         let line_idx = top + row;
-        if line_idx >= buffer.line_count() {
+        let Some(&line_start_idx) = line_starts.get(row) else {
             lines.push(Line::from("~"));
             continue;
-        }
+        };
 
-        let source = buffer.line_text(line_idx);
-        let line_start_idx = buffer.line_start_char_idx(line_idx);
+        let source = buffer.line_slice(line_idx, left_col, inner.width as usize);
+        let cursor_offset =
+            (line_idx == buffer.cursor.line).then(|| buffer.cursor.col.saturating_sub(left_col));
+        let mut decor = if model.spell.is_some() || markdown || model.trailing_whitespace {
+            LineDecor::for_line(
+                buffer,
+                line_idx,
+                model,
+                markdown,
+                left_col,
+                inner.width as usize,
+            )
+        } else {
+            LineDecor::default()
+        };
+        decor.brackets = brackets
+            .iter()
+            .filter(|(r, _)| *r == row)
+            .map(|(_, offset)| *offset)
+            .collect();
+        decor.cursors = buffer
+            .extra_cursors
+            .iter()
+            .filter(|c| c.line == line_idx)
+            .filter_map(|c| c.col.checked_sub(left_col))
+            .collect();
         let (mut line, cursor_x_on_line) = render_styled_line(
             buffer,
             &source,
-            line_start_idx,
-            buffer.viewport.left_col,
+            line_start_idx + left_col.min(buffer.line_len_chars(line_idx)),
             inner.width as usize,
-            buffer.cursor.col,
-            line_idx == buffer.cursor.line,
+            cursor_offset,
             model.no_style,
+            &decor,
         );
 
+        if model.indent_guides {
+            add_indent_guides(
+                &mut line,
+                guide_depth(buffer, line_idx, &scan, left_col + inner.width as usize),
+                left_col,
+                inner.width as usize,
+            );
+        }
         if line_idx == buffer.cursor.line {
             line.style = line.style.add_modifier(Modifier::UNDERLINED);
         }
         lines.push(line);
 
-        if line_idx == buffer.cursor.line {
+        if focused && line_idx == buffer.cursor.line {
             let x = inner.x + cursor_x_on_line as u16;
             let y = inner.y + row as u16;
             cursor_xy = Some((x, y));
         }
     }
 
-    if let Overlay::Search { state, .. } = model.overlay {
-        if let Some(curr) = state.current {
-            if let Some(&line_idx) = state.matches.get(curr) {
-                if line_idx >= top && line_idx < top + height {
-                    let row = line_idx - top;
-                    let mut st = lines[row].style;
-                    st = st.add_modifier(Modifier::UNDERLINED);
-                    lines[row].style = st;
-                }
-            }
-        }
+    if let Overlay::Search { state, .. } = model.overlay
+        && let Some(curr) = state.current
+        && let Some(&line_idx) = state.matches.get(curr)
+        && line_idx >= top
+        && line_idx < top + height
+    {
+        let row = line_idx - top;
+        let mut st = lines[row].style;
+        st = st.add_modifier(Modifier::UNDERLINED);
+        lines[row].style = st;
     }
 
     frame.render_widget(Paragraph::new(lines), inner);
+    if let Some(ruler) = model.ruler
+        && let Some(x) = ruler.checked_sub(buffer.viewport.left_col)
+        && x < inner.width as usize
+    {
+        let cells = frame.buffer_mut();
+        for y in inner.top()..inner.bottom() {
+            let cell = &mut cells[(inner.x + x as u16, y)];
+            if cell.symbol() == " " {
+                cell.set_symbol("│").set_fg(Color::DarkGray);
+            }
+        }
+    }
     cursor_xy
 }
 
+/// Extra styling for one visible line, as char ranges into the visible slice.
+#[derive(Debug, Default)]
+struct LineDecor {
+    misspelled: Vec<Range<usize>>,
+    heading: bool,
+    bullet: Option<usize>,
+    bold: Vec<Range<usize>>,
+    italic: Vec<Range<usize>>,
+    trailing_from: Option<usize>,
+    brackets: Vec<usize>,
+    cursors: Vec<usize>,
+}
+
+impl LineDecor {
+    /// Scans only the visible columns plus `DECOR_MARGIN` on each side, so a
+    /// long line costs no more per frame than a short one. Heading and bullet
+    /// markers come from the start of the line; trailing whitespace is only
+    /// marked when the line ends inside the scanned window.
+    fn for_line(
+        buffer: &TextBuffer,
+        line_idx: usize,
+        model: &UiModel<'_>,
+        markdown: bool,
+        left_col: usize,
+        width: usize,
+    ) -> Self {
+        let from = left_col.saturating_sub(DECOR_MARGIN);
+        let take = left_col + width + DECOR_MARGIN - from;
+        let text = buffer.line_slice(line_idx, from, take);
+        let visible = |ranges: Vec<Range<usize>>| -> Vec<Range<usize>> {
+            ranges
+                .into_iter()
+                .map(|r| r.start + from..r.end + from)
+                .filter(|r| r.end > left_col)
+                .map(|r| r.start.saturating_sub(left_col)..r.end - left_col)
+                .collect()
+        };
+        let mut decor = Self {
+            misspelled: visible(
+                model
+                    .spell
+                    .map_or_else(Vec::new, |dict| dict.misspelled(&text)),
+            ),
+            ..Self::default()
+        };
+        if model.trailing_whitespace && buffer.line_len_chars(line_idx) <= from + take {
+            let len = from + text.chars().count();
+            let start = from + text.trim_end_matches([' ', '\t']).chars().count();
+            decor.trailing_from =
+                (start < len && len > left_col).then(|| start.saturating_sub(left_col));
+        }
+        if markdown {
+            let md = markdown_line(&text);
+            let (heading, bullet) = if from == 0 {
+                (md.heading, md.bullet)
+            } else {
+                let head = markdown_line(&buffer.line_slice(line_idx, 0, DECOR_MARGIN));
+                (head.heading, head.bullet)
+            };
+            decor.heading = heading;
+            decor.bullet = bullet.and_then(|b| b.checked_sub(left_col));
+            decor.bold = visible(md.bold);
+            decor.italic = visible(md.italic);
+        }
+        decor
+    }
+}
+
+/// Screen row and column offset of the bracket under the cursor and its
+/// match, or nothing when either one is outside the visible text.
+fn visible_bracket_pair(
+    buffer: &TextBuffer,
+    line_starts: &[usize],
+    left_col: usize,
+    width: usize,
+) -> Vec<(usize, usize)> {
+    let (Some(&first), Some(&last)) = (line_starts.first(), line_starts.last()) else {
+        return Vec::new();
+    };
+    let last_line = buffer.viewport.top_line + line_starts.len() - 1;
+    let Some((a, b)) = buffer.matching_bracket(first..last + buffer.line_len_chars(last_line))
+    else {
+        return Vec::new();
+    };
+    let place = |idx: usize| {
+        let row = line_starts.partition_point(|&s| s <= idx) - 1;
+        let col = (idx - line_starts[row]).checked_sub(left_col)?;
+        (col < width).then_some((row, col))
+    };
+    match (place(a), place(b)) {
+        (Some(a), Some(b)) => vec![a, b],
+        _ => Vec::new(),
+    }
+}
+
 fn render_styled_line(
     buffer: &TextBuffer,
     source: &str,
-    line_start_idx: usize,
-    left_col: usize,
+    first_char_idx: usize,
     max_cols: usize,
-    cursor_col: usize,
-    cursor_line: bool,
+    cursor_offset: Option<usize>,
     no_style: bool,
+    decor: &LineDecor,
 ) -> (Line<'static>, usize) {
     let mut spans: Vec<Span<'static>> = Vec::new();
     let mut col = 0usize;
-    let mut cursor_x = 0usize;
-    let mut char_idx_in_line = 0usize;
+    let mut cursor_x = None;
+    let mut rendered_chars = 0usize;
 
-    for ch in source.chars() {
+    for (offset, ch) in source.chars().enumerate() {
+        if col >= max_cols {
+            break;
+        }
         let (render_chars, source_width) = if ch == '\t' {
-            let spaces = 4 - (col % 4);
+            let spaces = TAB_WIDTH - (col % TAB_WIDTH);
             (vec![' '; spaces], spaces)
+        } else if decor.bullet == Some(offset) {
+            (vec!['•'], 1)
         } else {
             let w = UnicodeWidthChar::width(ch).unwrap_or(1).max(1);
             (vec![ch], w)
         };
         let next_col = col + source_width;
-        if next_col <= left_col {
-            col = next_col;
-            char_idx_in_line += 1;
-            continue;
-        }
-        if col >= left_col + max_cols {
-            break;
-        }
 
-        if cursor_line && col <= cursor_col && cursor_col < next_col {
-            cursor_x = col.saturating_sub(left_col);
+        if cursor_offset == Some(offset) {
+            cursor_x = Some(col);
         }
 
         let mut style = Style::default();
-        if !no_style {
-            if let Some(cid) = buffer.char_color(line_start_idx + char_idx_in_line) {
-                style = style.fg(color_for_id(cid));
-            }
+        if !no_style && let Some(cid) = buffer.char_color(first_char_idx + offset) {
+            style = style.fg(color_for_id(cid));
+        }
+        if decor.heading || decor.bold.iter().any(|r| r.contains(&offset)) {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        if decor.italic.iter().any(|r| r.contains(&offset)) {
+            style = style.add_modifier(Modifier::ITALIC);
+        }
+        if decor.bullet == Some(offset) {
+            style = style.fg(Color::Cyan);
+        }
+        if decor.brackets.contains(&offset) {
+            style = style.bg(Color::DarkGray).add_modifier(Modifier::BOLD);
+        }
+        if decor.trailing_from.is_some_and(|from| offset >= from) {
+            style = style.bg(Color::Red);
+        }
+        if decor.misspelled.iter().any(|r| r.contains(&offset)) {
+            style = style
+                .add_modifier(Modifier::UNDERLINED)
+                .underline_color(Color::Red);
+        }
+        if decor.cursors.contains(&offset) {
+            style = style.add_modifier(Modifier::REVERSED);
         }
         for rc in render_chars {
-            if col >= left_col + max_cols {
+            if col >= max_cols {
                 break;
             }
             spans.push(Span::styled(rc.to_string(), style));
             col += 1;
         }
-        if ch != '\t' {
-            col = next_col;
-        } else if col < next_col {
-            col = next_col;
+        col = next_col;
+        rendered_chars += 1;
+    }
+    if decor.cursors.contains(&rendered_chars) && col < max_cols {
+        spans.push(Span::styled(
+            " ",
+            Style::default().add_modifier(Modifier::REVERSED),
+        ));
+    }
+
+    let cursor_x = match (cursor_x, cursor_offset) {
+        (Some(x), _) => x,
+        (None, Some(offset)) => col + offset.saturating_sub(rendered_chars),
+        (None, None) => 0,
+    };
+
+    (Line::from(spans), cursor_x.min(max_cols.saturating_sub(1)))
+}
+
+/// Display width of the leading whitespace, or `None` for a blank line.
+fn indent_width(text: &str) -> Option<usize> {
+    let mut width = 0;
+    for ch in text.chars() {
+        match ch {
+            ' ' => width += 1,
+            '\t' => width += TAB_WIDTH - width % TAB_WIDTH,
+            _ => return Some(width),
         }
-        char_idx_in_line += 1;
     }
+    None
+}
+
+/// Indent of a line for guide drawing, read no further than column `cols`.
+/// Blank lines take the shallower of the surrounding non-blank lines within
+/// `scan`, so guides run through gaps inside a block.
+fn guide_depth(buffer: &TextBuffer, line_idx: usize, scan: &Range<usize>, cols: usize) -> usize {
+    let indent = |line: usize| {
+        let lead = buffer.line_slice(line, 0, cols);
+        indent_width(&lead).or_else(|| (buffer.line_len_chars(line) > cols).then_some(cols))
+    };
+    if let Some(width) = indent(line_idx) {
+        return width;
+    }
+    let above = (scan.start..line_idx).rev().find_map(indent);
+    let below = (line_idx + 1..scan.end.min(buffer.line_count())).find_map(indent);
+    above.zip(below).map_or(0, |(a, b)| a.min(b))
+}
 
-    if cursor_line && cursor_col >= col {
-        cursor_x = cursor_col
-            .saturating_sub(left_col)
-            .min(max_cols.saturating_sub(1));
+/// Replaces blank cells at each tab stop inside the indent with a dim `│`.
+/// Only cell contents change, so cursor columns stay as rendered.
+fn add_indent_guides(line: &mut Line<'static>, depth: usize, left_col: usize, max_cols: usize) {
+    let guide = Style::default().fg(Color::DarkGray);
+    for stop in (0..depth).step_by(TAB_WIDTH) {
+        let Some(x) = stop.checked_sub(left_col) else {
+            continue;
+        };
+        if x >= max_cols {
+            break;
+        }
+        while line.spans.len() <= x {
+            line.spans.push(Span::raw(" "));
+        }
+        if line.spans[x].content == " " {
+            line.spans[x] = Span::styled("│", line.spans[x].style.patch(guide));
+        }
     }
+}
 
-    (Line::from(spans), cursor_x)
+fn color_name(id: u8) -> &'static str {
+    match id {
+        1 => "yellow",
+        2 => "cyan",
+        3 => "green",
+        4 => "blue",
+        5 => "red",
+        6 => "magenta",
+        7 => "lt yellow",
+        8 => "lt cyan",
+        _ => "reset",
+    }
 }
 
 fn color_for_id(id: u8) -> Color {
@@ -330,22 +739,32 @@ fn color_for_id(id: u8) -> Color {
     }
 }
 
-fn draw_overlay(frame: &mut Frame<'_>, area: Rect, overlay: &Overlay, categories: &[String]) {
-    match overlay {
+fn draw_overlay(frame: &mut Frame<'_>, area: Rect, model: &UiModel<'_>) -> Option<(u16, u16)> {
+    let mut cursor = None;
+    match model.overlay {
         Overlay::None => {}
-        Overlay::Help => {
-            let rect = centered_rect(70, 70, area);
+        Overlay::Help { scroll } => {
+            let rect = help_rect(area);
             frame.render_widget(Clear, rect);
-            let text = vec![
-                Line::from("F1 Help | Ctrl+F Search | Ctrl+G Goto | Ctrl+O Tree"),
-                Line::from("Ctrl+N New | Ctrl+S Save | Ctrl+Shift+S Save As | Ctrl+Q Quit"),
-                Line::from("F2..F9 set char color | F10 reset color"),
-                Line::from("Tree mode: Up/Down, Enter open, N new, Del/D delete, Esc back"),
-                Line::from("Esc close overlay"),
-            ];
+            let max_scroll = help_max_scroll(area.height, model.keymap);
+            let scroll = (*scroll).min(max_scroll);
+            let text: Vec<Line> = help_lines(model.keymap)
+                .into_iter()
+                .map(Line::from)
+                .collect();
+            let title = if max_scroll > 0 {
+                format!(
+                    "Help ({}/{}) Up/Down/PgUp/PgDn scroll",
+                    scroll + 1,
+                    max_scroll + 1
+                )
+            } else {
+                "Help".to_string()
+            };
             let widget = Paragraph::new(text)
                 .alignment(Alignment::Left)
-                .block(Block::default().title("Help").borders(Borders::ALL));
+                .scroll((scroll as u16, 0))
+                .block(Block::default().title(title).borders(Borders::ALL));
             frame.render_widget(widget, rect);
         }
         Overlay::Search { input, state } => {
@@ -355,10 +774,13 @@ fn draw_overlay(frame: &mut Frame<'_>, area: Rect, overlay: &Overlay, categories
                 .current
                 .map(|i| format!("{}/{}", i + 1, state.matches.len()))
                 .unwrap_or_else(|| "0/0".to_string());
-            let mut textarea = TextArea::default();
-            textarea.insert_str(input);
-            textarea.set_block(Block::default().title("Search").borders(Borders::ALL));
-            frame.render_widget(&textarea, rect);
+            cursor = Some(draw_input(
+                frame,
+                rect,
+                "Search",
+                input.as_str(),
+                input.caret(),
+            ));
 
             let footer = Rect {
                 x: rect.x + 2,
@@ -371,100 +793,106 @@ fn draw_overlay(frame: &mut Frame<'_>, area: Rect, overlay: &Overlay, categories
         Overlay::Goto { input } => {
             let rect = centered_rect(40, 20, area);
             frame.render_widget(Clear, rect);
-            let mut textarea = TextArea::default();
-            textarea.insert_str(input);
-            textarea.set_block(Block::default().title("Goto Line").borders(Borders::ALL));
-            frame.render_widget(&textarea, rect);
-        }
-        Overlay::SaveAs {
-            filename,
-            category_index,
+            cursor = Some(draw_input(
+                frame,
+                rect,
+                "Goto Line (N or N%)",
+                input,
+                input.chars().count(),
+            ));
+        }
+        Overlay::NoteMeta {
+            path,
+            title,
+            tags,
+            tags_focus,
         } => {
-            let rect = centered_rect(80, 40, area);
+            let rect = centered_rect(60, 40, area);
             frame.render_widget(Clear, rect);
-
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
                     Constraint::Length(3),
-                    Constraint::Min(3),
-                    Constraint::Length(2),
+                    Constraint::Length(3),
+                    Constraint::Min(1),
                 ])
                 .split(rect);
-
-            let mut textarea = TextArea::default();
-            textarea.insert_str(filename);
-            textarea.set_block(Block::default().title("File name").borders(Borders::ALL));
-            frame.render_widget(&textarea, chunks[0]);
-
-            let mut lines = Vec::new();
-            for (idx, category) in categories.iter().enumerate() {
-                let marker = if idx == *category_index { ">" } else { " " };
-                lines.push(Line::from(format!("{marker} {category}")));
-            }
-            frame.render_widget(
-                Paragraph::new(lines).block(
-                    Block::default()
-                        .title("Category (Up/Down)")
-                        .borders(Borders::ALL),
-                ),
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let title_cursor = draw_input(
+                frame,
+                chunks[0],
+                &format!("Title for {name}"),
+                title.as_str(),
+                title.caret(),
+            );
+            let tags_cursor = draw_input(
+                frame,
                 chunks[1],
+                "Tags (comma separated)",
+                tags.as_str(),
+                tags.caret(),
             );
-
             frame.render_widget(
-                Paragraph::new("Enter save | Esc cancel")
+                Paragraph::new("Enter save | Tab switch field | Esc cancel")
                     .block(Block::default().borders(Borders::ALL)),
                 chunks[2],
             );
+            cursor = Some(if *tags_focus {
+                tags_cursor
+            } else {
+                title_cursor
+            });
         }
-        Overlay::NewFile {
-            filename,
-            category_index,
-        } => {
-            let rect = centered_rect(80, 40, area);
+        Overlay::CommandLine { input } => {
+            let rect = Rect {
+                x: area.x,
+                y: area.bottom().saturating_sub(3),
+                width: area.width,
+                height: 3.min(area.height),
+            };
             frame.render_widget(Clear, rect);
-
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Length(3),
-                    Constraint::Min(3),
-                    Constraint::Length(2),
-                ])
-                .split(rect);
-
-            let mut textarea = TextArea::default();
-            textarea.insert_str(filename);
-            textarea.set_block(Block::default().title("New file").borders(Borders::ALL));
-            frame.render_widget(&textarea, chunks[0]);
-
-            let mut lines = Vec::new();
-            for (idx, category) in categories.iter().enumerate() {
-                let marker = if idx == *category_index { ">" } else { " " };
-                lines.push(Line::from(format!("{marker} {category}")));
-            }
-            frame.render_widget(
-                Paragraph::new(lines).block(
-                    Block::default()
-                        .title("Category (Up/Down)")
-                        .borders(Borders::ALL),
+            cursor = Some(draw_input(
+                frame,
+                rect,
+                "Command (:w :q :q! :wq :<line> :!<shell>)",
+                input.as_str(),
+                input.caret(),
+            ));
+        }
+        Overlay::SaveAs { form } => {
+            cursor = Some(draw_note_form(
+                frame,
+                area,
+                form,
+                ("File name", "Enter save | Tab category | Esc cancel"),
+                model,
+            ));
+        }
+        Overlay::NewFile { form } => {
+            cursor = Some(draw_note_form(
+                frame,
+                area,
+                form,
+                (
+                    "New file",
+                    "Enter create and open | Tab category | Esc cancel",
                 ),
-                chunks[1],
-            );
-
-            frame.render_widget(
-                Paragraph::new("Enter create and open | Esc cancel")
-                    .block(Block::default().borders(Borders::ALL)),
-                chunks[2],
-            );
+                model,
+            ));
         }
         Overlay::NewCategory { name, .. } => {
             let rect = centered_rect(65, 25, area);
             frame.render_widget(Clear, rect);
-            let mut textarea = TextArea::default();
-            textarea.insert_str(name);
-            textarea.set_block(Block::default().title("New category").borders(Borders::ALL));
-            frame.render_widget(&textarea, rect);
+            cursor = Some(draw_input(
+                frame,
+                rect,
+                "New category",
+                name,
+                name.chars().count(),
+            ));
             let footer = Rect {
                 x: rect.x + 2,
                 y: rect.y + rect.height.saturating_sub(1),
@@ -475,6 +903,32 @@ fn draw_overlay(frame: &mut Frame<'_>, area: Rect, overlay: &Overlay, categories
         }
         Overlay::ConfirmUnsaved {
             file_name, choice, ..
+        } => {
+            let rect = centered_rect(70, 30, area);
+            frame.render_widget(Clear, rect);
+            let buttons = UnsavedChoice::ALL
+                .iter()
+                .map(|c| {
+                    if c == choice {
+                        format!("[{}]", c.label())
+                    } else {
+                        format!(" {} ", c.label())
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("   ");
+            let widget = Paragraph::new(format!(
+                "Unsaved changes in {}.\nSave before leaving?\n\n{}\n\nS save | D discard | C cancel",
+                file_name, buttons
+            ))
+            .alignment(Alignment::Center)
+            .block(Block::default().title("Confirm").borders(Borders::ALL));
+            frame.render_widget(widget, rect);
+        }
+        Overlay::ConfirmDelete {
+            file_name,
+            path,
+            choice,
         } => {
             let rect = centered_rect(70, 30, area);
             frame.render_widget(Clear, rect);
@@ -488,17 +942,30 @@ fn draw_overlay(frame: &mut Frame<'_>, area: Rect, overlay: &Overlay, categories
             } else {
                 " No "
             };
+            let location = match path
+                .parent()
+                .and_then(|p| p.file_name())
+                .map(|c| c.to_string_lossy())
+            {
+                Some(category) => format!("{category}/{file_name}"),
+                None => file_name.clone(),
+            };
             let widget = Paragraph::new(format!(
-                "Unsaved changes in {}.\nSave before leaving?\n\n{}   {}",
-                file_name, yes, no
+                "Do you want to delete {}?\n{}\n\n{}   {}",
+                location,
+                path.display(),
+                yes,
+                no
             ))
             .alignment(Alignment::Center)
-            .block(Block::default().title("Confirm").borders(Borders::ALL));
+            .block(
+                Block::default()
+                    .title("Confirm Delete")
+                    .borders(Borders::ALL),
+            );
             frame.render_widget(widget, rect);
         }
-        Overlay::ConfirmDelete {
-            file_name, choice, ..
-        } => {
+        Overlay::ConfirmOverwrite { path, choice, .. } => {
             let rect = centered_rect(70, 30, area);
             frame.render_widget(Clear, rect);
             let yes = if *choice == ConfirmChoice::Yes {
@@ -511,18 +978,140 @@ fn draw_overlay(frame: &mut Frame<'_>, area: Rect, overlay: &Overlay, categories
             } else {
                 " No "
             };
+            let file_name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
             let widget = Paragraph::new(format!(
-                "Do you want to delete {}?\n\n{}   {}",
+                "{} already exists.\nOverwrite it?\n\n{}   {}",
                 file_name, yes, no
             ))
             .alignment(Alignment::Center)
             .block(
                 Block::default()
-                    .title("Confirm Delete")
+                    .title("Confirm Overwrite")
                     .borders(Borders::ALL),
             );
             frame.render_widget(widget, rect);
         }
+        Overlay::ConfirmCategory { name, choice, .. } => {
+            let rect = centered_rect(70, 30, area);
+            frame.render_widget(Clear, rect);
+            let yes = if *choice == ConfirmChoice::Yes {
+                "[Yes]"
+            } else {
+                " Yes "
+            };
+            let no = if *choice == ConfirmChoice::No {
+                "[No]"
+            } else {
+                " No "
+            };
+            let widget = Paragraph::new(format!(
+                "Category {} does not exist.\nCreate it?\n\n{}   {}",
+                name, yes, no
+            ))
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .title("Confirm New Category")
+                    .borders(Borders::ALL),
+            );
+            frame.render_widget(widget, rect);
+        }
+        Overlay::FileInfo {
+            file_name,
+            size,
+            modified,
+            lines,
+            estimated,
+        } => {
+            let rect = centered_rect(60, 30, area);
+            frame.render_widget(Clear, rect);
+            let modified = modified
+                .map(|t| {
+                    DateTime::<Local>::from(t)
+                        .format("%Y-%m-%d %H:%M:%S")
+                        .to_string()
+                })
+                .unwrap_or_else(|| "unknown".to_string());
+            let approx = if *estimated { "~" } else { "" };
+            let text = vec![
+                Line::from(format!("Size:     {size} bytes")),
+                Line::from(format!("Modified: {modified}")),
+                Line::from(format!("Lines:    {approx}{lines}")),
+                Line::from(""),
+                Line::from("Enter/Esc close"),
+            ];
+            let widget = Paragraph::new(text).alignment(Alignment::Left).block(
+                Block::default()
+                    .title(file_name.as_str())
+                    .borders(Borders::ALL),
+            );
+            frame.render_widget(widget, rect);
+        }
+        Overlay::RecoverDraft { note, choice, .. } => {
+            let rect = centered_rect(70, 30, area);
+            frame.render_widget(Clear, rect);
+            let yes = if *choice == ConfirmChoice::Yes {
+                "[Yes]"
+            } else {
+                " Yes "
+            };
+            let no = if *choice == ConfirmChoice::No {
+                "[No]"
+            } else {
+                " No "
+            };
+            let found = match note {
+                Some(name) => {
+                    format!("Unsaved changes to {name} from a previous session were found.")
+                }
+                None => "An unsaved draft from a previous session was found.".to_string(),
+            };
+            let widget = Paragraph::new(format!("{found}\nRecover it?\n\n{yes}   {no}"))
+                .alignment(Alignment::Center)
+                .block(
+                    Block::default()
+                        .title("Recover Draft")
+                        .borders(Borders::ALL),
+                );
+            frame.render_widget(widget, rect);
+        }
+        Overlay::Outline { items, selected } => {
+            let rect = centered_rect(70, 60, area);
+            frame.render_widget(Clear, rect);
+            let lines: Vec<Line> = if items.is_empty() {
+                vec![Line::from("No headings (lines starting with #)")]
+            } else {
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, item)| {
+                        let marker = if idx == *selected { ">" } else { " " };
+                        let indent = "  ".repeat(item.level.saturating_sub(1));
+                        Line::from(format!("{marker} {indent}{}", item.text))
+                    })
+                    .collect()
+            };
+            let visible = rect.height.saturating_sub(2) as usize;
+            let scroll = list_scroll(*selected, visible);
+            let title = if items.len() > visible {
+                format!(
+                    "Outline (Enter jump, Esc close) {}/{}",
+                    selected + 1,
+                    items.len()
+                )
+            } else {
+                "Outline (Enter jump, Esc close)".to_string()
+            };
+            frame.render_widget(
+                Paragraph::new(lines)
+                    .scroll((scroll as u16, 0))
+                    .block(Block::default().title(title).borders(Borders::ALL)),
+                rect,
+            );
+        }
         Overlay::Error { message } => {
             let rect = centered_rect(80, 30, area);
             frame.render_widget(Clear, rect);
@@ -532,6 +1121,128 @@ fn draw_overlay(frame: &mut Frame<'_>, area: Rect, overlay: &Overlay, categories
             frame.render_widget(widget, rect);
         }
     }
+    cursor
+}
+
+fn draw_note_form(
+    frame: &mut Frame<'_>,
+    area: Rect,
+    form: &NoteForm,
+    (title, hint): (&str, &'static str),
+    model: &UiModel<'_>,
+) -> (u16, u16) {
+    let rect = centered_rect(80, 40, area);
+    frame.render_widget(Clear, rect);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(3),
+            Constraint::Length(3),
+        ])
+        .split(rect);
+
+    let file_cursor = draw_input(
+        frame,
+        chunks[0],
+        title,
+        form.filename.as_str(),
+        form.filename.caret(),
+    );
+    let category_cursor = draw_input(
+        frame,
+        chunks[1],
+        "Category (type + Tab to complete)",
+        form.category.as_str(),
+        form.category.caret(),
+    );
+
+    let mut lines = Vec::new();
+    for (idx, category) in model.categories.iter().enumerate() {
+        let marker = if idx == form.category_index { ">" } else { " " };
+        lines.push(Line::from(format!("{marker} {category}")));
+    }
+    let visible = chunks[2].height.saturating_sub(2) as usize;
+    let scroll = list_scroll(form.category_index, visible);
+    let title = if lines.len() > visible {
+        format!(
+            "Category (Up/Down) {}/{}",
+            form.category_index + 1,
+            lines.len()
+        )
+    } else {
+        "Category (Up/Down)".to_string()
+    };
+    frame.render_widget(
+        Paragraph::new(lines)
+            .scroll((scroll as u16, 0))
+            .block(Block::default().title(title).borders(Borders::ALL)),
+        chunks[2],
+    );
+
+    frame.render_widget(name_footer(hint, model.name_issue), chunks[3]);
+    if form.category_focus {
+        category_cursor
+    } else {
+        file_cursor
+    }
+}
+
+/// First row of the page of `visible` rows that contains `selected`.
+fn list_scroll(selected: usize, visible: usize) -> usize {
+    let visible = visible.max(1);
+    selected / visible * visible
+}
+
+fn name_footer(hint: &'static str, issue: Option<NameIssue>) -> Paragraph<'static> {
+    let line = match issue {
+        Some(issue) if issue.blocks_enter() => Line::from(vec![
+            Span::styled(issue.message(), Style::default().fg(Color::Red)),
+            Span::raw(" | Esc cancel"),
+        ]),
+        Some(issue) => Line::from(vec![
+            Span::styled(issue.message(), Style::default().fg(Color::Yellow)),
+            Span::raw(" | Esc cancel"),
+        ]),
+        None => Line::from(hint),
+    };
+    Paragraph::new(line).block(Block::default().borders(Borders::ALL))
+}
+
+fn draw_input(
+    frame: &mut Frame<'_>,
+    rect: Rect,
+    title: &str,
+    input: &str,
+    caret: usize,
+) -> (u16, u16) {
+    let mut textarea = TextArea::default();
+    textarea.insert_str(input);
+    textarea.move_cursor(CursorMove::Jump(0, caret as u16));
+    textarea.set_cursor_style(Style::default());
+    textarea.set_block(Block::default().title(title).borders(Borders::ALL));
+    frame.render_widget(&textarea, rect);
+
+    let width: usize = input
+        .chars()
+        .take(caret)
+        .map(|ch| UnicodeWidthChar::width(ch).unwrap_or(1))
+        .sum();
+    let max_x = rect.x + rect.width.saturating_sub(2);
+    let x = (rect.x as usize + 1 + width).min(max_x as usize) as u16;
+    (x, rect.y + 1)
+}
+
+fn help_rect(area: Rect) -> Rect {
+    centered_rect(70, 70, area)
+}
+
+pub fn help_max_scroll(screen_height: u16, keymap: Keymap) -> usize {
+    let rect = help_rect(Rect::new(0, 0, 100, screen_height));
+    let visible = rect.height.saturating_sub(2) as usize;
+    help_lines(keymap).len().saturating_sub(visible)
 }
 
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
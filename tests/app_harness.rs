@@ -0,0 +1,431 @@
+use std::fs;
+
+use crossterm::event::{KeyCode, KeyModifiers};
+use dopepad::app::run_loop;
+use dopepad::core::{Dictionary, TextBuffer};
+use dopepad::testing::{Script, app, cell_of, screen, terminal};
+use ratatui::Terminal;
+use ratatui::backend::TestBackend;
+use tempfile::tempdir;
+
+#[test]
+fn new_category_new_file_type_and_save() {
+    let dir = tempdir().expect("tempdir");
+    let notes = dir.path().join("notes");
+    fs::create_dir_all(&notes).expect("notes root");
+
+    let mut app = app(TextBuffer::new(None, false), &notes);
+    let mut terminal = terminal();
+    let mut script = Script::new()
+        .ctrl('n')
+        .type_str("work")
+        .press(KeyCode::Enter)
+        .repeat(KeyCode::Backspace, "new_note.txt".len())
+        .type_str("todo")
+        .press(KeyCode::Enter)
+        .type_str("hi")
+        .press(KeyCode::Home)
+        .press(KeyCode::F(3))
+        .ctrl('s')
+        .ctrl('q');
+
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+
+    let note = notes.join("work").join("todo.txt");
+    assert_eq!(fs::read_to_string(&note).expect("note"), "hi");
+    let sidecar =
+        fs::read_to_string(notes.join("work").join("todo.txt.dopepad.json")).expect("sidecar");
+    let json: serde_json::Value = serde_json::from_str(&sidecar).expect("json");
+    assert_eq!(json["char_runs"][0]["color"], 2);
+    assert_eq!(json["char_runs"][0]["start"], 0);
+    assert!(!app.running);
+    assert!(!app.buffer.dirty);
+}
+
+#[test]
+fn scripted_events_render_into_the_test_backend() {
+    let dir = tempdir().expect("tempdir");
+    let mut app = app(TextBuffer::new(None, false), dir.path());
+    let mut terminal = terminal();
+    let mut script = Script::new().type_str("hello harness");
+
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+
+    let screen = screen(&terminal);
+    assert!(screen.contains("hello harness"));
+    assert!(app.running);
+}
+
+#[test]
+fn tree_marks_the_open_note_while_it_has_unsaved_changes() {
+    let dir = tempdir().expect("tempdir");
+    let notes = dir.path().join("notes");
+    fs::create_dir_all(notes.join("work")).expect("category");
+    fs::write(notes.join("work").join("plan.txt"), "plan").expect("note");
+    fs::write(notes.join("work").join("other.txt"), "other").expect("note");
+
+    let mut app = app(TextBuffer::new(None, false), &notes);
+    let mut terminal = terminal();
+
+    let mut script = Script::new()
+        .ctrl('o')
+        .press(KeyCode::Down)
+        .press(KeyCode::Enter)
+        .type_str("!");
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+    assert!(screen(&terminal).contains("plan.txt *"));
+    assert!(!screen(&terminal).contains("other.txt *"));
+
+    let mut script = Script::new().ctrl('s');
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+    assert!(!screen(&terminal).contains("plan.txt *"));
+}
+
+#[test]
+fn sidebar_legend_lists_color_keys_and_marks_the_active_one() {
+    let dir = tempdir().expect("tempdir");
+    let mut app = app(TextBuffer::new(None, false), dir.path());
+    let mut terminal = terminal();
+    let mut script = Script::new().press(KeyCode::F(4));
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+
+    let screen = screen(&terminal);
+    let buffer = terminal.backend().buffer();
+    assert!(screen.contains("F2  C1 yellow"));
+    assert!(screen.contains("F10 C0 reset"));
+    assert!(
+        buffer.content()[cell_of(&screen, "F4  C3 green")]
+            .modifier
+            .contains(ratatui::style::Modifier::REVERSED)
+    );
+}
+
+#[test]
+fn focused_panel_border_follows_tree_focus() {
+    let dir = tempdir().expect("tempdir");
+    let mut app = app(TextBuffer::new(None, false), dir.path());
+    let mut terminal = terminal();
+    let editor_corner = |terminal: &Terminal<TestBackend>| terminal.backend().buffer()[(68, 0)].fg;
+
+    run_loop(&mut app, &mut terminal, &mut Script::new()).expect("run loop");
+    assert_eq!(editor_corner(&terminal), ratatui::style::Color::Cyan);
+
+    run_loop(&mut app, &mut terminal, &mut Script::new().ctrl('o')).expect("run loop");
+    assert_eq!(editor_corner(&terminal), ratatui::style::Color::DarkGray);
+}
+
+#[test]
+fn terminal_cursor_sits_after_the_overlay_input() {
+    let dir = tempdir().expect("tempdir");
+    let mut app = app(TextBuffer::new(None, false), dir.path());
+    let mut terminal = terminal();
+    let mut script = Script::new().ctrl('f').type_str("zq");
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+
+    let at = cell_of(&screen(&terminal), "zq") as u16;
+    let (x, y) = (at % 120 + 2, at / 120);
+    terminal.backend_mut().assert_cursor_position((x, y));
+}
+
+#[test]
+fn category_picker_scrolls_to_keep_the_selection_visible() {
+    let dir = tempdir().expect("tempdir");
+    let notes = dir.path().join("notes");
+    for i in 0..20 {
+        fs::create_dir_all(notes.join(format!("c{i:02}"))).expect("category");
+    }
+    let mut app = app(TextBuffer::new(None, false), &notes);
+    let mut terminal = Terminal::new(TestBackend::new(120, 30)).expect("terminal");
+    let mut script = Script::new().ctrl('n').press(KeyCode::Up);
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+
+    let screen = screen(&terminal);
+    assert!(screen.contains("> c19"));
+    assert!(screen.contains("20/20"));
+}
+
+#[test]
+fn indent_guides_mark_tab_stops_without_moving_the_cursor() {
+    let dir = tempdir().expect("tempdir");
+    let mut app = app(
+        TextBuffer::from_text("top\n    item\n\n        sub\nend".into(), None, false),
+        dir.path(),
+    );
+    app.indent_guides = true;
+    let mut terminal = terminal();
+    let mut script = Script::new().repeat(KeyCode::Down, 3).press(KeyCode::End);
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+
+    let rows: Vec<String> = (0..40)
+        .map(|y| {
+            (0..120)
+                .map(|x| terminal.backend().buffer()[(x, y)].symbol().to_string())
+                .collect()
+        })
+        .collect();
+    let row_with = |text: &str| rows.iter().position(|r| r.contains(text)).expect(text);
+    let sub = row_with("│   │   sub");
+    let cell = |row: usize, col: usize| rows[row].chars().nth(col).expect("cell");
+    let guide = rows[sub][..rows[sub].find("│   │   sub").expect("sub")]
+        .chars()
+        .count();
+    assert_eq!(cell(row_with("│   item"), guide), '│');
+    assert_eq!(cell(sub - 1, guide), '│');
+    assert_eq!(cell(sub - 1, guide + 4), ' ');
+    assert_eq!(cell(row_with("top"), guide), 't');
+
+    let x = guide + "│   │   sub".chars().count();
+    terminal
+        .backend_mut()
+        .assert_cursor_position((x as u16, sub as u16));
+}
+
+#[test]
+fn spellcheck_underlines_unknown_words_in_red() {
+    let dir = tempdir().expect("tempdir");
+    let mut app = app(
+        TextBuffer::from_text("first\nthe cta sat".into(), None, false),
+        dir.path(),
+    );
+    app.spell = Some(Dictionary::from_word_list("first\nthe\nsat\n"));
+    let mut terminal = terminal();
+    run_loop(&mut app, &mut terminal, &mut Script::new()).expect("run loop");
+
+    let buffer = terminal.backend().buffer();
+    let at = cell_of(&screen(&terminal), "the cta sat");
+    let red = |i: usize| {
+        let cell = &buffer.content()[at + i];
+        cell.modifier.contains(ratatui::style::Modifier::UNDERLINED)
+            && cell.underline_color == ratatui::style::Color::Red
+    };
+    assert!(!red(0));
+    assert!(red(4) && red(5) && red(6));
+    assert!(!red(8));
+}
+
+#[test]
+fn decorations_hold_at_the_end_of_a_long_scrolled_line() {
+    use ratatui::style::{Color, Modifier};
+
+    let dir = tempdir().expect("tempdir");
+    let text = format!("# {}cta *sat*  ", "the ".repeat(2000));
+    let mut app = app(
+        TextBuffer::from_text(text, Some(dir.path().join("long.md")), false),
+        dir.path(),
+    );
+    app.spell = Some(Dictionary::from_word_list("the\nsat\n"));
+    app.trailing_whitespace = true;
+    let mut terminal = terminal();
+    let mut script = Script::new().press(KeyCode::End);
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+    assert!(app.buffer.viewport.left_col > 1000);
+
+    let buffer = terminal.backend().buffer();
+    let at = cell_of(&screen(&terminal), "the cta *sat*") + 4;
+    let cell = |i: usize| &buffer.content()[at + i];
+    assert!(cell(0).modifier.contains(Modifier::BOLD));
+    assert_eq!(cell(0).underline_color, Color::Red);
+    assert!(cell(5).modifier.contains(Modifier::ITALIC));
+    assert_ne!(cell(5).underline_color, Color::Red);
+    assert_eq!(cell(8).bg, Color::Reset);
+    assert_eq!(cell(9).bg, Color::Red);
+    assert_eq!(cell(10).bg, Color::Red);
+}
+
+#[test]
+fn markdown_notes_get_light_styling_but_text_notes_do_not() {
+    use ratatui::style::Modifier;
+
+    let dir = tempdir().expect("tempdir");
+    let render = |name: &str| {
+        let text = "# Plan\n- buy *milk*\nend";
+        let mut app = app(
+            TextBuffer::from_text(text.into(), Some(dir.path().join(name)), false),
+            dir.path(),
+        );
+        let mut terminal = terminal();
+        let mut script = Script::new().repeat(KeyCode::Down, 2);
+        run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+        (screen(&terminal), terminal.backend().buffer().clone())
+    };
+
+    let (screen, md) = render("plan.md");
+    let heading = cell_of(&screen, "# Plan");
+    assert!(md.content()[heading + 2].modifier.contains(Modifier::BOLD));
+    let bullet = cell_of(&screen, "• buy *milk*");
+    assert!(md.content()[bullet + 6].modifier.contains(Modifier::ITALIC));
+    assert!(!md.content()[bullet + 2].modifier.contains(Modifier::ITALIC));
+
+    let (screen, txt) = render("plan.txt");
+    let heading = cell_of(&screen, "# Plan");
+    assert!(!txt.content()[heading + 2].modifier.contains(Modifier::BOLD));
+    assert!(cell_of(&screen, "- buy *milk*") > 0);
+}
+
+#[test]
+fn markdown_preview_pane_renders_and_follows_the_cursor() {
+    let dir = tempdir().expect("tempdir");
+    let mut text = String::from("# Top\n\n");
+    for i in 0..60 {
+        text.push_str(&format!("- item {i:02}\n"));
+    }
+    let mut app = app(
+        TextBuffer::from_text(text, Some(dir.path().join("list.md")), false),
+        dir.path(),
+    );
+    let mut terminal = Terminal::new(TestBackend::new(160, 40)).expect("terminal");
+
+    let mut script = Script::new().press(KeyCode::F(12));
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+    assert!(app.markdown_preview);
+    assert!(screen(&terminal).contains("Preview"));
+    assert!(screen(&terminal).contains("• item 00"));
+
+    let mut script = Script::new().repeat(KeyCode::Down, 55);
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+    assert!(screen(&terminal).contains("• item 53"));
+    assert!(!screen(&terminal).contains("• item 00"));
+
+    let mut script = Script::new().press(KeyCode::F(12));
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+    assert!(!screen(&terminal).contains("Preview"));
+}
+
+#[test]
+fn trailing_whitespace_is_highlighted_without_moving_the_cursor() {
+    use ratatui::style::Color;
+
+    let dir = tempdir().expect("tempdir");
+    let mut app = app(
+        TextBuffer::from_text("keep  \nclean\ntab\t".into(), None, false),
+        dir.path(),
+    );
+    app.trailing_whitespace = true;
+    let mut terminal = terminal();
+    let mut script = Script::new().press(KeyCode::End);
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+
+    let screen = screen(&terminal);
+    let buffer = terminal.backend().buffer();
+    let at = |text: &str| cell_of(&screen, text);
+    let bg = |i: usize| buffer.content()[i].bg;
+    let keep = at("keep");
+    assert_eq!(bg(keep + 3), Color::Reset);
+    assert_eq!(bg(keep + 4), Color::Red);
+    assert_eq!(bg(keep + 5), Color::Red);
+    assert_eq!(bg(keep + 6), Color::Reset);
+    assert!((0..5).all(|i| bg(at("clean") + i) == Color::Reset));
+    assert_eq!(bg(at("tab") + 3), Color::Red);
+
+    let (x, y) = ((keep % 120 + 6) as u16, (keep / 120) as u16);
+    terminal.backend_mut().assert_cursor_position((x, y));
+}
+
+#[test]
+fn ruler_follows_horizontal_scrolling() {
+    let dir = tempdir().expect("tempdir");
+    let mut app = app(
+        TextBuffer::from_text(format!("short\n{}", "x".repeat(60)), None, false),
+        dir.path(),
+    );
+    app.ruler = Some(30);
+    let mut terminal = terminal();
+    let rows = |terminal: &Terminal<TestBackend>| -> Vec<Vec<String>> {
+        let buffer = terminal.backend().buffer();
+        (0..40)
+            .map(|y| {
+                (0..120)
+                    .map(|x| buffer[(x, y)].symbol().to_string())
+                    .collect()
+            })
+            .collect()
+    };
+    let text_x = |rows: &[Vec<String>]| {
+        rows.iter()
+            .find_map(|r| r.iter().position(|c| c == "s").filter(|&x| r[x + 1] == "h"))
+            .expect("text start") as u16
+    };
+    let ruler_x = |rows: &[Vec<String>], from: u16| {
+        let blank = rows
+            .iter()
+            .find(|r| r[from as usize] == "~")
+            .expect("blank row");
+        (from..120)
+            .find(|&x| blank[x as usize] == "│")
+            .expect("ruler")
+    };
+
+    run_loop(&mut app, &mut terminal, &mut Script::new()).expect("run loop");
+    let screen = rows(&terminal);
+    let left = text_x(&screen);
+    assert_eq!(ruler_x(&screen, left), left + 30);
+
+    let mut script = Script::new().press(KeyCode::Down).press(KeyCode::End);
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+    let scrolled = app.buffer.viewport.left_col as u16;
+    assert!(scrolled > 0);
+    assert_eq!(ruler_x(&rows(&terminal), left), left + 30 - scrolled);
+}
+
+#[test]
+fn bracket_under_the_cursor_and_its_match_are_highlighted() {
+    use ratatui::style::Color;
+
+    let dir = tempdir().expect("tempdir");
+    let mut app = app(
+        TextBuffer::from_text("call(a, [b]) (".into(), None, false),
+        dir.path(),
+    );
+    let mut terminal = terminal();
+    let mut script = Script::new().repeat(KeyCode::Right, 4);
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+
+    let buffer = terminal.backend().buffer();
+    let at = cell_of(&screen(&terminal), "call(");
+    let bg = |col: usize| buffer.content()[at + col].bg;
+    assert_eq!(bg(4), Color::DarkGray);
+    assert_eq!(bg(11), Color::DarkGray);
+    assert_eq!(bg(8), Color::Reset);
+
+    let mut script = Script::new().press(KeyCode::End);
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+    let buffer = terminal.backend().buffer();
+    assert!((0..14).all(|col| buffer.content()[at + col].bg == Color::Reset));
+}
+
+#[test]
+fn extra_cursors_type_on_every_line_and_are_drawn_reversed() {
+    use ratatui::style::Modifier;
+
+    let dir = tempdir().expect("tempdir");
+    let mut app = app(
+        TextBuffer::from_text("one\ntwo\nsix".into(), None, false),
+        dir.path(),
+    );
+    let mut terminal = terminal();
+    let mut script = Script::new()
+        .key(KeyCode::Down, KeyModifiers::ALT)
+        .key(KeyCode::Down, KeyModifiers::ALT)
+        .type_str("- ");
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+    assert_eq!(app.buffer.as_string(), "- one\n- two\n- six");
+
+    let screen = screen(&terminal);
+    let buffer = terminal.backend().buffer();
+    assert!(screen.contains("(+2 cursors)"));
+    let at = |text: &str| cell_of(&screen, text);
+    let reversed = |i: usize| buffer.content()[i].modifier.contains(Modifier::REVERSED);
+    assert!(!reversed(at("- one") + 2));
+    assert!(reversed(at("- two") + 2));
+    assert!(reversed(at("- six") + 2));
+
+    let mut script = Script::new().press(KeyCode::Esc);
+    run_loop(&mut app, &mut terminal, &mut script).expect("run loop");
+    assert!(app.buffer.extra_cursors.is_empty());
+    let buffer = terminal.backend().buffer();
+    assert!(
+        !buffer.content()[at("- two") + 5]
+            .modifier
+            .contains(Modifier::REVERSED)
+    );
+}